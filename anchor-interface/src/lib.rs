@@ -27,11 +27,20 @@
 //!     sequence,
 //! )?;
 //! ```
+//!
+//! `mark_used` (or `cpi::ensure_and_mark`, which spells out that the bucket
+//! is created on demand) aborts the whole transaction on replay. To skip duplicates
+//! gracefully instead, use `cpi::mark_if_unused` with the same accounts; it
+//! returns `false` when the sequence was already used. `cpi::is_used` reads
+//! the bitmap without a CPI, but is advisory only (see its docs).
+//!
+//! To read a bucket inside your own instruction, declare it as
+//! `Account<'info, Bitmap>`; Anchor then checks the owner and tag for you.
 
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
     instruction::{AccountMeta, Instruction},
-    program::invoke_signed,
+    program::{get_return_data, invoke, invoke_signed},
 };
 
 pub static ID: Pubkey = id();
@@ -40,40 +49,86 @@ pub const fn id() -> Pubkey {
     Pubkey::from_str_const(env!("NOREPLAY_PROGRAM_ID"))
 }
 
-/// Instruction discriminators (must match the Pinocchio program).
-pub const CREATE_BITMAP: u8 = 0;
-pub const MARK_USED: u8 = 1;
+/// Instruction discriminators, re-exported from the program crate.
+pub use solana_noreplay::instruction::{
+    CHECK_USED, CREATE_BITMAP, MARK_USED, RANGE_ANY_USED, RETURN_STATUS_FLAG, VERIFY_BUCKET,
+};
 
-/// Maximum namespace length (64 bytes = 2 chunks of 32 bytes).
-pub const MAX_NAMESPACE_LEN: usize = 64;
+/// Status bytes returned by VerifyBucket (the program's `BucketStatus`).
+pub mod bucket_status {
+    use solana_noreplay::state::BucketStatus;
 
-/// Bits per bitmap bucket (1024 bits = 128 bytes).
-pub const BITS_PER_BUCKET: u64 = 1024;
+    pub const HEALTHY: u8 = BucketStatus::Healthy as u8;
+    pub const NOT_OWNED: u8 = BucketStatus::NotOwned as u8;
+    pub const WRONG_SIZE: u8 = BucketStatus::WrongSize as u8;
+    pub const WRONG_ADDRESS: u8 = BucketStatus::WrongAddress as u8;
+    pub const WRONG_FORMAT: u8 = BucketStatus::WrongFormat as u8;
+}
 
-/// Size of bitmap account data (1 byte bump + 128 bytes bitmap).
-pub const BITMAP_ACCOUNT_SIZE: usize = 129;
+/// Layout constants, re-exported from the program crate so that PDAs derived
+/// here always match the deployed program.
+pub use solana_noreplay::state::{
+    bit_position, bucket_of, BITMAP_ACCOUNT_SIZE, BITMAP_BYTES, BITMAP_TAG, BITS_PER_BUCKET,
+};
+pub use solana_noreplay::{is_reserved_namespace, DerivePdaError, MAX_NAMESPACE_LEN};
 
 /// Derive the bitmap PDA for a given authority, namespace, and sequence.
 ///
-/// Seeds: `[authority, ns_chunk_0 (0-32 bytes), ns_chunk_1 (0-32 bytes), bucket_index (8 bytes LE)]`
+/// Seeds: `[authority, ns_chunk_0 (0-32 bytes), ns_chunk_1 (0-32 bytes), ns_chunk_2 (0-32 bytes), bucket_index (8 bytes LE)]`
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`]; use
+/// [`try_derive_bitmap_pda`] for untrusted input.
 pub fn derive_bitmap_pda(authority: &Pubkey, namespace: &[u8], sequence: u64) -> (Pubkey, u8) {
-    let bucket_index = sequence / BITS_PER_BUCKET;
-    let bucket_bytes = bucket_index.to_le_bytes();
-
-    // Split namespace into chunks (max 32 bytes each)
-    let mid = namespace.len().min(32);
-    let ns_chunk_0 = &namespace[..mid];
-    let ns_chunk_1 = &namespace[mid..];
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
+    let bucket_bytes = bucket_of(sequence).to_le_bytes();
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = namespace_chunks(namespace);
 
     Pubkey::find_program_address(
-        &[authority.as_ref(), ns_chunk_0, ns_chunk_1, &bucket_bytes],
+        &[
+            authority.as_ref(),
+            ns_chunk_0,
+            ns_chunk_1,
+            ns_chunk_2,
+            &bucket_bytes,
+        ],
         &ID,
     )
 }
 
+/// Split namespace into seed chunks (max 32 bytes each)
+fn namespace_chunks(namespace: &[u8]) -> [&[u8]; 3] {
+    let first = namespace.len().min(32);
+    let second = namespace.len().min(64);
+    [
+        &namespace[..first],
+        &namespace[first..second],
+        &namespace[second..],
+    ]
+}
+
+/// Fallible [`derive_bitmap_pda`], rejecting namespaces the program would.
+pub fn try_derive_bitmap_pda(
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> core::result::Result<(Pubkey, u8), DerivePdaError> {
+    if namespace.len() > MAX_NAMESPACE_LEN {
+        return Err(DerivePdaError::NamespaceTooLong);
+    }
+    if is_reserved_namespace(namespace) {
+        return Err(DerivePdaError::ReservedNamespace);
+    }
+    Ok(derive_bitmap_pda(authority, namespace, sequence))
+}
+
 /// Build instruction data for CreateBitmap or MarkUsed.
 ///
-/// Format: `[discriminator (1)][namespace_len (2 LE)][namespace (0-64)][sequence (8 LE)]`
+/// Format: `[discriminator (1)][namespace_len (2 LE)][namespace (0-96)][sequence (8 LE)]`
 fn build_instruction_data(discriminator: u8, namespace: &[u8], sequence: u64) -> Vec<u8> {
     let namespace_len = namespace.len() as u16;
     let mut data = Vec::with_capacity(1 + 2 + namespace.len() + 8);
@@ -84,9 +139,67 @@ fn build_instruction_data(discriminator: u8, namespace: &[u8], sequence: u64) ->
     data
 }
 
+/// Bitmap bucket account, so Anchor programs can take
+/// `Account<'info, Bitmap>` and inspect it inside their own instructions.
+///
+/// Read-only: the bitmap is only ever written by the NoReplay program, and
+/// the owner check makes Anchor skip serializing it back on exit.
+#[derive(Clone)]
+pub struct Bitmap {
+    pub bump: u8,
+    pub bitmap: [u8; BITMAP_BYTES],
+}
+
+impl Bitmap {
+    /// Check if `sequence` is marked as used. Only meaningful for sequences
+    /// in this bucket, i.e. those the bitmap PDA was derived for.
+    pub fn is_used(&self, sequence: u64) -> bool {
+        let (byte_index, bit_offset) = bit_position(sequence);
+        self.bitmap[byte_index] & (1 << bit_offset) != 0
+    }
+
+    /// Count the sequences marked as used in this bucket.
+    pub fn count_used(&self) -> u32 {
+        self.bitmap.iter().map(|byte| byte.count_ones()).sum()
+    }
+}
+
+impl AccountDeserialize for Bitmap {
+    fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+        if buf.len() < BITMAP_ACCOUNT_SIZE {
+            return err!(ErrorCode::AccountDidNotDeserialize);
+        }
+        if !buf.starts_with(&BITMAP_TAG) {
+            return err!(ErrorCode::AccountDiscriminatorMismatch);
+        }
+        Self::try_deserialize_unchecked(buf)
+    }
+
+    fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+        let Some(account) = solana_noreplay::state::BitmapAccountRef::from_slice(buf) else {
+            return err!(ErrorCode::AccountDidNotDeserialize);
+        };
+        let bitmap = Self {
+            bump: *account.bump,
+            bitmap: *account.bitmap,
+        };
+        *buf = &buf[BITMAP_ACCOUNT_SIZE..];
+        Ok(bitmap)
+    }
+}
+
+impl AccountSerialize for Bitmap {}
+
+impl Owner for Bitmap {
+    fn owner() -> Pubkey {
+        ID
+    }
+}
+
 /// CPI module for invoking solana-noreplay instructions.
 pub mod cpi {
     use super::*;
+    use solana_noreplay::state::{check_tag, BitmapAccountRef};
 
     /// Accounts for the CreateBitmap instruction.
     ///
@@ -187,6 +300,68 @@ pub mod cpi {
         Ok(())
     }
 
+    /// [`create_bitmap`], returning the bucket's address and bump.
+    ///
+    /// Both come from [`derive_bitmap_pda`](crate::derive_bitmap_pda) with the
+    /// same authority, namespace and sequence as the instruction, after the
+    /// program has accepted the passed account as that PDA, so they always
+    /// name the account that was written. Costs one `find_program_address`
+    /// on top of the CPI.
+    ///
+    /// ```ignore
+    /// let (bitmap, _bump) = cpi::create_bitmap_with_pda(
+    ///     CpiContext::new(
+    ///         ctx.accounts.noreplay_program.to_account_info(),
+    ///         cpi::CreateBitmap {
+    ///             payer: ctx.accounts.payer.to_account_info(),
+    ///             authority: ctx.accounts.emitter.to_account_info(),
+    ///             bitmap: ctx.accounts.replay_bitmap.to_account_info(),
+    ///             system_program: ctx.accounts.system_program.to_account_info(),
+    ///         },
+    ///     ),
+    ///     namespace,
+    ///     sequence,
+    /// )?;
+    ///
+    /// // Hand the same bucket to a later instruction
+    /// let ix = Instruction {
+    ///     program_id: downstream_program,
+    ///     accounts: vec![
+    ///         AccountMeta::new_readonly(emitter, false),
+    ///         AccountMeta::new_readonly(bitmap, false),
+    ///     ],
+    ///     data,
+    /// };
+    /// ```
+    pub fn create_bitmap_with_pda<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, CreateBitmap<'info>>,
+        namespace: &[u8],
+        sequence: u64,
+    ) -> Result<(Pubkey, u8)> {
+        let authority = *ctx.accounts.authority.key;
+
+        // The program rejects namespaces over MAX_NAMESPACE_LEN, so the
+        // derivation below can't panic once this succeeds
+        create_bitmap(ctx, namespace, sequence)?;
+
+        Ok(crate::derive_bitmap_pda(&authority, namespace, sequence))
+    }
+
+    /// Details of a successful [`mark_used`], for audit trails.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MarkResult {
+        /// Bitmap PDA that was written (verified by the program).
+        pub pda: Pubkey,
+        /// Whether this call created the bitmap PDA.
+        pub newly_created: bool,
+        /// Bucket index of the sequence.
+        pub bucket: u64,
+        /// Status byte the program returned (`1` = newly marked). Always
+        /// `true` here, since `mark_used` fails on replay, but read from the
+        /// program rather than assumed.
+        pub newly_marked: bool,
+    }
+
     /// Mark a sequence number as used for replay protection.
     ///
     /// The authority MUST sign to prevent adversaries from marking sequences
@@ -194,19 +369,287 @@ pub mod cpi {
     /// a PDA that the calling program signs for.
     ///
     /// Returns an error if the sequence was already marked as used (replay detected).
+    /// On success, returns the PDA touched and whether it was created; both
+    /// come from the passed account (which the program has just verified),
+    /// so no extra derivation is needed.
     pub fn mark_used<'info>(
         ctx: CpiContext<'_, '_, '_, 'info, MarkUsed<'info>>,
         namespace: &[u8],
         sequence: u64,
-    ) -> Result<()> {
+    ) -> Result<MarkResult> {
+        let newly_created = *ctx.accounts.bitmap.owner != crate::ID;
+
+        let mut data = build_instruction_data(MARK_USED, namespace, sequence);
+        data.push(RETURN_STATUS_FLAG);
         let ix = Instruction {
             program_id: crate::ID,
             accounts: ctx.accounts.to_account_metas(None),
-            data: build_instruction_data(MARK_USED, namespace, sequence),
+            data,
         };
 
         invoke_signed(&ix, &ctx.accounts.to_account_infos(), ctx.signer_seeds)?;
 
-        Ok(())
+        let newly_marked = match get_return_data() {
+            Some((program_id, data)) if program_id == crate::ID && data.len() == 1 => data[0] != 0,
+            _ => return err!(ErrorCode::InstructionDidNotDeserialize),
+        };
+
+        Ok(MarkResult {
+            pda: *ctx.accounts.bitmap.key,
+            newly_created,
+            bucket: bucket_of(sequence),
+            newly_marked,
+        })
+    }
+
+    /// Create the bucket if needed, then mark `sequence` as used.
+    ///
+    /// A single MarkUsed CPI: the program creates a missing bitmap PDA itself,
+    /// so there is no need to [`create_bitmap`] first (that would cost a
+    /// second CPI and derivation). Same accounts and replay behaviour as
+    /// [`mark_used`]; `MarkResult::newly_created` tells which path was taken.
+    ///
+    /// ```ignore
+    /// let result = cpi::ensure_and_mark(
+    ///     CpiContext::new_with_signer(
+    ///         ctx.accounts.noreplay_program.to_account_info(),
+    ///         cpi::MarkUsed {
+    ///             payer: ctx.accounts.payer.to_account_info(),
+    ///             authority: ctx.accounts.emitter.to_account_info(),
+    ///             bitmap: ctx.accounts.replay_bitmap.to_account_info(),
+    ///             system_program: ctx.accounts.system_program.to_account_info(),
+    ///         },
+    ///         &[&authority_seeds],
+    ///     ),
+    ///     namespace,
+    ///     sequence,
+    /// )?;
+    /// if result.newly_created {
+    ///     msg!("opened bucket {}", result.bucket);
+    /// }
+    /// ```
+    pub fn ensure_and_mark<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, MarkUsed<'info>>,
+        namespace: &[u8],
+        sequence: u64,
+    ) -> Result<MarkResult> {
+        mark_used(ctx, namespace, sequence)
+    }
+
+    /// Accounts for the VerifyBucket instruction.
+    pub struct VerifyBucket<'info> {
+        /// Authority for the replay protection namespace (does NOT need to sign).
+        pub authority: AccountInfo<'info>,
+
+        /// Bitmap PDA to check.
+        pub bitmap: AccountInfo<'info>,
+    }
+
+    impl<'info> ToAccountMetas for VerifyBucket<'info> {
+        fn to_account_metas(&self, _is_signer: Option<bool>) -> Vec<AccountMeta> {
+            vec![
+                AccountMeta::new_readonly(*self.authority.key, false),
+                AccountMeta::new_readonly(*self.bitmap.key, false),
+            ]
+        }
+    }
+
+    impl<'info> ToAccountInfos<'info> for VerifyBucket<'info> {
+        fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+            vec![self.authority.clone(), self.bitmap.clone()]
+        }
+    }
+
+    /// Health-check a bucket account.
+    ///
+    /// Returns one of the [`bucket_status`](crate::bucket_status) codes. An
+    /// unhealthy bucket is reported, not treated as an error.
+    pub fn verify_bucket<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, VerifyBucket<'info>>,
+        namespace: &[u8],
+        sequence: u64,
+    ) -> Result<u8> {
+        let ix = Instruction {
+            program_id: crate::ID,
+            accounts: ctx.accounts.to_account_metas(None),
+            data: build_instruction_data(VERIFY_BUCKET, namespace, sequence),
+        };
+
+        invoke(&ix, &ctx.accounts.to_account_infos())?;
+
+        match get_return_data() {
+            Some((program_id, data)) if program_id == crate::ID && data.len() == 1 => Ok(data[0]),
+            _ => err!(ErrorCode::InstructionDidNotDeserialize),
+        }
+    }
+
+    /// Accounts for the CheckUsed instruction.
+    pub struct CheckUsed<'info> {
+        /// Authority for the replay protection namespace (does NOT need to sign).
+        pub authority: AccountInfo<'info>,
+
+        /// Bitmap PDA to read (may not exist yet).
+        pub bitmap: AccountInfo<'info>,
+    }
+
+    impl<'info> ToAccountMetas for CheckUsed<'info> {
+        fn to_account_metas(&self, _is_signer: Option<bool>) -> Vec<AccountMeta> {
+            vec![
+                AccountMeta::new_readonly(*self.authority.key, false),
+                AccountMeta::new_readonly(*self.bitmap.key, false),
+            ]
+        }
+    }
+
+    impl<'info> ToAccountInfos<'info> for CheckUsed<'info> {
+        fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+            vec![self.authority.clone(), self.bitmap.clone()]
+        }
+    }
+
+    /// Ask the program whether `sequence` is marked, via CPI.
+    ///
+    /// Like [`is_used`], but the program itself reads and verifies the
+    /// bucket. Returns `false` if the bucket doesn't exist yet. Neither
+    /// account signs, so no signer seeds are needed.
+    ///
+    /// ```ignore
+    /// let used = cpi::check_used(
+    ///     CpiContext::new(
+    ///         ctx.accounts.noreplay_program.to_account_info(),
+    ///         cpi::CheckUsed {
+    ///             authority: ctx.accounts.emitter.to_account_info(),
+    ///             bitmap: ctx.accounts.replay_bitmap.to_account_info(),
+    ///         },
+    ///     ),
+    ///     namespace,
+    ///     sequence,
+    /// )?;
+    /// ```
+    pub fn check_used<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, CheckUsed<'info>>,
+        namespace: &[u8],
+        sequence: u64,
+    ) -> Result<bool> {
+        let ix = Instruction {
+            program_id: crate::ID,
+            accounts: ctx.accounts.to_account_metas(None),
+            data: build_instruction_data(CHECK_USED, namespace, sequence),
+        };
+
+        invoke(&ix, &ctx.accounts.to_account_infos())?;
+
+        match get_return_data() {
+            Some((program_id, data)) if program_id == crate::ID && data.len() == 1 => {
+                Ok(data[0] != 0)
+            }
+            _ => err!(ErrorCode::InstructionDidNotDeserialize),
+        }
+    }
+
+    /// Ask the program whether any sequence in `start..start + count` is
+    /// marked, via CPI. Uses the same accounts as [`check_used`].
+    ///
+    /// The run must be non-empty (`InvalidInstructionData`) and lie within
+    /// one bucket (`InvalidBucket`). Returns `false` if the bucket doesn't
+    /// exist yet.
+    pub fn range_any_used<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, CheckUsed<'info>>,
+        namespace: &[u8],
+        start: u64,
+        count: u64,
+    ) -> Result<bool> {
+        // Same prefix as the single-sequence layout, with `count` appended
+        let mut data = build_instruction_data(RANGE_ANY_USED, namespace, start);
+        data.extend_from_slice(&count.to_le_bytes());
+
+        let ix = Instruction {
+            program_id: crate::ID,
+            accounts: ctx.accounts.to_account_metas(None),
+            data,
+        };
+
+        invoke(&ix, &ctx.accounts.to_account_infos())?;
+
+        match get_return_data() {
+            Some((program_id, data)) if program_id == crate::ID && data.len() == 1 => {
+                Ok(data[0] != 0)
+            }
+            _ => err!(ErrorCode::InstructionDidNotDeserialize),
+        }
+    }
+
+    /// Check whether `sequence` is already marked in `bitmap`, without a CPI.
+    ///
+    /// Reads the account data directly. A bitmap that doesn't exist yet (not
+    /// owned by the NoReplay program) has no used sequences. For an existing
+    /// bitmap the address is verified against `authority`/`namespace` using
+    /// the stored bump, so a wrong account can't report a false "used".
+    ///
+    /// This is advisory: a `false` result for a not-yet-created account is not
+    /// verified against the derived address. Only `mark_used` is
+    /// authoritative, so never skip marking based on this alone; use
+    /// [`mark_if_unused`] to branch on duplicates safely.
+    pub fn is_used(
+        bitmap: &AccountInfo,
+        authority: &Pubkey,
+        namespace: &[u8],
+        sequence: u64,
+    ) -> Result<bool> {
+        if *bitmap.owner != crate::ID {
+            return Ok(false);
+        }
+
+        let data = bitmap.try_borrow_data()?;
+        let Some(account) = BitmapAccountRef::from_slice(&data) else {
+            return err!(ErrorCode::AccountDidNotDeserialize);
+        };
+        if check_tag(account.tag).is_err() {
+            return err!(ErrorCode::AccountDiscriminatorMismatch);
+        }
+
+        let bucket_bytes = bucket_of(sequence).to_le_bytes();
+        let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = namespace_chunks(namespace);
+        let expected = Pubkey::create_program_address(
+            &[
+                authority.as_ref(),
+                ns_chunk_0,
+                ns_chunk_1,
+                ns_chunk_2,
+                &bucket_bytes,
+                &[*account.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+        if *bitmap.key != expected {
+            return err!(ErrorCode::ConstraintSeeds);
+        }
+
+        Ok(account.is_used(sequence))
+    }
+
+    /// Mark a sequence as used unless it already is.
+    ///
+    /// Returns `Ok(true)` if the sequence was newly marked and `Ok(false)` if
+    /// it was already used, instead of aborting the transaction on replay.
+    /// Aggregators processing batches can use this to skip duplicates.
+    pub fn mark_if_unused<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, MarkUsed<'info>>,
+        namespace: &[u8],
+        sequence: u64,
+    ) -> Result<bool> {
+        if is_used(
+            &ctx.accounts.bitmap,
+            ctx.accounts.authority.key,
+            namespace,
+            sequence,
+        )? {
+            return Ok(false);
+        }
+
+        mark_used(ctx, namespace, sequence)?;
+
+        Ok(true)
     }
 }
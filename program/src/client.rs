@@ -25,35 +25,476 @@
 //! ```
 
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    account::Account,
+    compute_budget::ComputeBudgetInstruction,
+    ed25519_program,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    program_error::ProgramError,
     pubkey::Pubkey,
-    system_program,
+    rent::Rent,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_program, sysvar,
+    transaction::{Transaction, TransactionError},
 };
 
 /// Program ID for the NoReplay program (set via `NOREPLAY_PROGRAM_ID` env var at compile time).
 pub const PROGRAM_ID: Pubkey = Pubkey::from_str_const(env!("NOREPLAY_PROGRAM_ID"));
 
-/// Size of each seed component for namespace chunking.
-const SEED_CHUNK_SIZE: usize = 32;
-
 /// Derive the bitmap PDA for a given authority, namespace, and sequence.
 ///
-/// Seeds are always: `[authority, ns_chunk_0, ns_chunk_1, bucket_index]`
+/// Seeds are always: `[authority, ns_chunk_0, ns_chunk_1, ns_chunk_2, bucket_index]`
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`]; use
+/// [`try_derive_bitmap_pda`] for untrusted input.
 pub fn derive_bitmap_pda(authority: &Pubkey, namespace: &[u8], sequence: u64) -> (Pubkey, u8) {
-    let bucket_index = sequence / crate::state::BITS_PER_BUCKET;
+    derive_bitmap_pda_for_program(&PROGRAM_ID, authority, namespace, sequence)
+}
+
+/// [`derive_bitmap_pda`] for a deployment at `program_id` rather than the
+/// compile-time [`PROGRAM_ID`], e.g. to target several clusters from one
+/// binary.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_bitmap_pda_for_program(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> (Pubkey, u8) {
+    derive_bucket_pda_for_program(program_id, authority, namespace, bucket_of(sequence))
+}
+
+/// Fallible [`derive_bitmap_pda`], rejecting namespaces the program would.
+pub fn try_derive_bitmap_pda(
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> Result<(Pubkey, u8), DerivePdaError> {
+    if namespace.len() > MAX_NAMESPACE_LEN {
+        return Err(DerivePdaError::NamespaceTooLong);
+    }
+    if is_reserved_namespace(namespace) {
+        return Err(DerivePdaError::ReservedNamespace);
+    }
+    Ok(derive_bitmap_pda(authority, namespace, sequence))
+}
+
+/// Check a cached bucket address and bump offline, before spending a fee.
+///
+/// Runs `create_program_address` with `bump`, as the program does for an
+/// existing bucket, and compares the result with `expected`. `false` for a
+/// namespace the program would reject. This doesn't prove `bump` canonical;
+/// cache the one [`derive_bitmap_pda`] returns.
+pub fn verify_pda(
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+    bump: u8,
+    expected: &Pubkey,
+) -> bool {
+    if namespace.len() > MAX_NAMESPACE_LEN || is_reserved_namespace(namespace) {
+        return false;
+    }
+    let bucket_bytes = bucket_of(sequence).to_le_bytes();
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+
+    let seeds: [&[u8]; 6] = [
+        authority.as_ref(),
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+        &bucket_bytes,
+        &[bump],
+    ];
+
+    Pubkey::create_program_address(&seeds, &PROGRAM_ID).is_ok_and(|pda| pda == *expected)
+}
+
+/// Derive the bitmap PDA for a given authority, namespace, and bucket index.
+///
+/// Useful for enumeration code that works with buckets rather than sequences.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_bucket_pda(authority: &Pubkey, namespace: &[u8], bucket_index: u64) -> (Pubkey, u8) {
+    derive_bucket_pda_for_program(&PROGRAM_ID, authority, namespace, bucket_index)
+}
+
+/// [`derive_bucket_pda`] for a deployment at `program_id`.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_bucket_pda_for_program(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    namespace: &[u8],
+    bucket_index: u64,
+) -> (Pubkey, u8) {
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
     let bucket_bytes = bucket_index.to_le_bytes();
-    let mid = namespace.len().min(SEED_CHUNK_SIZE);
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+
+    let seeds: [&[u8]; 5] = [
+        authority.as_ref(),
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+        &bucket_bytes,
+    ];
+
+    Pubkey::find_program_address(&seeds, program_id)
+}
+
+/// Derive the bitmap PDA for `(authority, namespace, sequence)` in `domain`.
+///
+/// Seeds are `[authority, tag, tag, domain, ns_chunk_0, ns_chunk_1, ns_chunk_2, bucket_index]`
+/// with `tag` = [`DOMAIN_SEED_TAG`]. Never equal to an undomained bucket or
+/// one in another domain, so separate replay systems under one authority
+/// can reuse namespaces.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_bitmap_pda_domain(
+    authority: &Pubkey,
+    domain: u8,
+    namespace: &[u8],
+    sequence: u64,
+) -> (Pubkey, u8) {
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
+    let bucket_bytes = bucket_of(sequence).to_le_bytes();
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+
+    let seeds: [&[u8]; 8] = [
+        authority.as_ref(),
+        DOMAIN_SEED_TAG,
+        DOMAIN_SEED_TAG,
+        &[domain],
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+        &bucket_bytes,
+    ];
+
+    Pubkey::find_program_address(&seeds, &PROGRAM_ID)
+}
+
+/// Derive the bitmap PDA for a u128 `sequence`.
+///
+/// Seeds are `[authority, tag, tag, tag, ns_chunk_0, ns_chunk_1, ns_chunk_2, bucket_index]`
+/// with `tag` = [`U128_SEED_TAG`] and a 16-byte LE `bucket_index`, so the
+/// bucket never equals a u64 bucket, even for sequences that fit in a u64.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_bitmap_pda_u128(
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u128,
+) -> (Pubkey, u8) {
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
+    let bucket_bytes = bucket_of_u128(sequence).to_le_bytes();
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+
+    let seeds: [&[u8]; 8] = [
+        authority.as_ref(),
+        U128_SEED_TAG,
+        U128_SEED_TAG,
+        U128_SEED_TAG,
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+        &bucket_bytes,
+    ];
+
+    Pubkey::find_program_address(&seeds, &PROGRAM_ID)
+}
+
+/// Derive the committed bucket PDA for `(authority, namespace, sequence)`.
+///
+/// Seeds are `[authority, tag, tag, tag, ns_chunk_0, ns_chunk_1, ns_chunk_2, bucket_index]`
+/// with `tag` = [`COMMITTED_SEED_TAG`], so a committed bucket never equals a
+/// plain or domained bitmap bucket of the same namespace.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_committed_pda(authority: &Pubkey, namespace: &[u8], sequence: u64) -> (Pubkey, u8) {
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
+    let bucket_bytes = bucket_of(sequence).to_le_bytes();
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
 
-    let seeds: [&[u8]; 4] = [
+    let seeds: [&[u8]; 8] = [
         authority.as_ref(),
-        &namespace[..mid],
-        &namespace[mid..],
+        COMMITTED_SEED_TAG,
+        COMMITTED_SEED_TAG,
+        COMMITTED_SEED_TAG,
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
         &bucket_bytes,
     ];
 
     Pubkey::find_program_address(&seeds, &PROGRAM_ID)
 }
 
+/// Derive the watermark PDA for `(authority, namespace)`.
+///
+/// Seeds are `[authority, tag, tag, tag, ns_chunk_0, ns_chunk_1, ns_chunk_2]` with `tag` =
+/// [`WATERMARK_SEED_TAG`]; there is one watermark per namespace, never equal
+/// to any bitmap bucket.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_watermark_pda(authority: &Pubkey, namespace: &[u8]) -> (Pubkey, u8) {
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+
+    let seeds: [&[u8]; 7] = [
+        authority.as_ref(),
+        WATERMARK_SEED_TAG,
+        WATERMARK_SEED_TAG,
+        WATERMARK_SEED_TAG,
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+    ];
+
+    Pubkey::find_program_address(&seeds, &PROGRAM_ID)
+}
+
+/// Derive the sliding-window PDA for `(authority, namespace)`.
+///
+/// Seeds are those of [`derive_watermark_pda`] with [`WINDOW_SEED_TAG`] as
+/// the tag.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_window_pda(authority: &Pubkey, namespace: &[u8]) -> (Pubkey, u8) {
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+
+    let seeds: [&[u8]; 7] = [
+        authority.as_ref(),
+        WINDOW_SEED_TAG,
+        WINDOW_SEED_TAG,
+        WINDOW_SEED_TAG,
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+    ];
+
+    Pubkey::find_program_address(&seeds, &PROGRAM_ID)
+}
+
+/// Derive the namespace config PDA for `(authority, namespace)`.
+///
+/// Seeds are those of [`derive_watermark_pda`] with [`CONFIG_SEED_TAG`] as
+/// the tag.
+///
+/// # Panics
+///
+/// Panics if `namespace` is longer than [`MAX_NAMESPACE_LEN`].
+pub fn derive_config_pda(authority: &Pubkey, namespace: &[u8]) -> (Pubkey, u8) {
+    assert!(
+        namespace.len() <= MAX_NAMESPACE_LEN,
+        "namespace exceeds MAX_NAMESPACE_LEN"
+    );
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+
+    let seeds: [&[u8]; 7] = [
+        authority.as_ref(),
+        CONFIG_SEED_TAG,
+        CONFIG_SEED_TAG,
+        CONFIG_SEED_TAG,
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+    ];
+
+    Pubkey::find_program_address(&seeds, &PROGRAM_ID)
+}
+
+/// Hand a marking instruction's authorization to the namespace config's
+/// current authority (see [`InitConfig`]).
+///
+/// Appends the `config` PDA ([`derive_config_pda`]) and `current_authority`
+/// after the instruction's accounts, and drops the authority's signature
+/// (account 1 of every marking instruction). The authority stays in the
+/// seeds, so buckets keep their addresses. Once the config exists,
+/// `current_authority` must sign and match it, or the program fails with
+/// `AuthorityMismatch`.
+///
+/// The config goes after every fixed account, so not for
+/// [`MarkUsed::instruction_for_existing_bucket`], whose empty system program
+/// slot it would take. [`MarkUsedDelegated`] is authorized by an ed25519
+/// signature instead; see [`MarkUsedDelegated::instructions_with_config`].
+pub fn with_config_authority(
+    mut ix: Instruction,
+    config: &Pubkey,
+    current_authority: &Pubkey,
+) -> Instruction {
+    ix.accounts[1].is_signer = false;
+    ix.accounts.extend([
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*current_authority, true),
+    ]);
+    ix
+}
+
+/// Check whether `pda` is the bucket for `(authority, namespace, sequence)`.
+///
+/// Returns the canonical bump if it is, `None` otherwise. This is the
+/// "does this account belong to me?" check for an address seen elsewhere.
+pub fn pda_matches(
+    pda: &Pubkey,
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> Option<u8> {
+    let (expected, bump) = derive_bitmap_pda(authority, namespace, sequence);
+    (expected == *pda).then_some(bump)
+}
+
+/// Debug builds: warn on stderr if `ix`'s bitmap PDA (account 2) isn't
+/// `derive(ix.program_id)`. Release builds skip the derivation.
+fn check_bitmap_program_id(
+    builder: &str,
+    ix: &Instruction,
+    derive: impl FnOnce(&Pubkey) -> (Pubkey, u8),
+) -> bool {
+    if cfg!(not(debug_assertions)) {
+        return true;
+    }
+    let expected = derive(&ix.program_id).0;
+    let matches = ix
+        .accounts
+        .get(2)
+        .is_some_and(|meta| meta.pubkey == expected);
+    if !matches {
+        eprintln!(
+            "warning: {builder} instruction for program {} doesn't carry its bitmap PDA {expected}; \
+             was it derived for another deployment?",
+            ix.program_id,
+        );
+    }
+    matches
+}
+
+/// Every intermediate value of a bucket PDA derivation, for debugging.
+///
+/// Print it on both sides of a PDA mismatch to see which seed differs (e.g.
+/// a `bucket_index` computed with a different `BITS_PER_BUCKET`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationExplain {
+    /// Authority seed.
+    pub authority_bytes: [u8; 32],
+    /// First namespace seed chunk (bytes 0..32).
+    pub ns_chunk_0: Vec<u8>,
+    /// Second namespace seed chunk (bytes 32..64, possibly empty).
+    pub ns_chunk_1: Vec<u8>,
+    /// Third namespace seed chunk (bytes 64..96, possibly empty).
+    pub ns_chunk_2: Vec<u8>,
+    /// `sequence / BITS_PER_BUCKET`.
+    pub bucket_index: u64,
+    /// Bucket index seed (`bucket_index` as u64 LE).
+    pub bucket_bytes: [u8; 8],
+    /// Derived PDA.
+    pub pda: Pubkey,
+    /// Canonical bump.
+    pub bump: u8,
+}
+
+/// Derive the bitmap PDA for `(authority, namespace, sequence)`, returning
+/// all seed values along the way.
+pub fn explain_derivation(
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> DerivationExplain {
+    let bucket_index = bucket_of(sequence);
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+    let (pda, bump) = derive_bucket_pda(authority, namespace, bucket_index);
+
+    DerivationExplain {
+        authority_bytes: authority.to_bytes(),
+        ns_chunk_0: ns_chunk_0.to_vec(),
+        ns_chunk_1: ns_chunk_1.to_vec(),
+        ns_chunk_2: ns_chunk_2.to_vec(),
+        bucket_index,
+        bucket_bytes: bucket_index.to_le_bytes(),
+        pda,
+        bump,
+    }
+}
+
+/// Derive `(bucket_index, pda, bump)` for every bucket in `buckets`, in order.
+pub fn derive_bucket_pdas(
+    authority: &Pubkey,
+    namespace: &[u8],
+    buckets: std::ops::Range<u64>,
+) -> Vec<(u64, Pubkey, u8)> {
+    buckets
+        .map(|bucket_index| {
+            let (pda, bump) = derive_bucket_pda(authority, namespace, bucket_index);
+            (bucket_index, pda, bump)
+        })
+        .collect()
+}
+
+/// Parallel [`derive_bucket_pdas`] for large prefund plans.
+///
+/// Each derivation is an independent `find_program_address` loop, so buckets
+/// are spread across the rayon pool. Output is identical to the sequential
+/// version (sorted by bucket index).
+#[cfg(feature = "rayon")]
+pub fn derive_bucket_pdas_parallel(
+    authority: &Pubkey,
+    namespace: &[u8],
+    buckets: std::ops::Range<u64>,
+) -> Vec<(u64, Pubkey, u8)> {
+    use rayon::prelude::*;
+
+    buckets
+        .into_par_iter()
+        .map(|bucket_index| {
+            let (pda, bump) = derive_bucket_pda(authority, namespace, bucket_index);
+            (bucket_index, pda, bump)
+        })
+        .collect()
+}
+
 /// Build instruction data for namespace + sequence.
 pub fn build_instruction_data(discriminator: u8, namespace: &[u8], sequence: u64) -> Vec<u8> {
     let namespace_len = namespace.len() as u16;
@@ -65,6 +506,53 @@ pub fn build_instruction_data(discriminator: u8, namespace: &[u8], sequence: u64
     data
 }
 
+/// Typed form of the `[disc][namespace_len][namespace][sequence]` layout
+/// shared by CreateBitmap, MarkUsed and the other single-sequence
+/// instructions, for tooling that logs or inspects instructions.
+///
+/// The Borsh derives give the struct its own serialization (for storing or
+/// sending it between services); it is NOT the wire format, since Borsh
+/// prefixes `namespace` with a u32 length. Use [`to_bytes`](Self::to_bytes)
+/// and [`from_bytes`](Self::from_bytes) for instruction data.
+#[cfg(feature = "borsh")]
+#[derive(Debug, Clone, PartialEq, Eq, borsh::BorshSerialize, borsh::BorshDeserialize)]
+pub struct NoReplayInstruction {
+    pub discriminator: u8,
+    pub namespace: Vec<u8>,
+    pub sequence: u64,
+}
+
+#[cfg(feature = "borsh")]
+impl NoReplayInstruction {
+    /// Instruction data, identical to [`build_instruction_data`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        build_instruction_data(self.discriminator, &self.namespace, self.sequence)
+    }
+
+    /// Parse instruction data in this layout.
+    ///
+    /// Returns `None` for anything the program's parser would reject as
+    /// malformed: truncated or trailing bytes, or a namespace longer than
+    /// [`MAX_NAMESPACE_LEN`]. The discriminator isn't checked.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&discriminator, rest) = bytes.split_first()?;
+        let (namespace_len, rest) = rest.split_first_chunk::<2>()?;
+        let namespace_len = u16::from_le_bytes(*namespace_len) as usize;
+        if !(MIN_NAMESPACE_LEN..=MAX_NAMESPACE_LEN).contains(&namespace_len)
+            || rest.len() != namespace_len + 8
+        {
+            return None;
+        }
+        let (namespace, sequence) = rest.split_at(namespace_len);
+
+        Some(Self {
+            discriminator,
+            namespace: namespace.to_vec(),
+            sequence: u64::from_le_bytes(sequence.try_into().ok()?),
+        })
+    }
+}
+
 /// Builder for CreateBitmap instruction.
 ///
 /// Creates a bitmap PDA permissionlessly. Anyone can call this to pre-create
@@ -93,19 +581,46 @@ pub struct CreateBitmap<'a> {
     pub payer: &'a Pubkey,
     /// Authority that owns the replay protection namespace (does NOT need to sign).
     pub authority: &'a Pubkey,
-    /// Application-specific namespace (max 64 bytes).
+    /// Application-specific namespace (max 96 bytes).
     pub namespace: &'a [u8],
     /// Sequence number (determines which bucket to create).
     pub sequence: u64,
 }
 
-impl CreateBitmap<'_> {
+impl<'a> CreateBitmap<'a> {
+    /// Const constructor for a namespace known at compile time. An oversized
+    /// namespace fails to compile instead of failing on-chain.
+    pub const fn with_namespace<const N: usize>(
+        payer: &'a Pubkey,
+        authority: &'a Pubkey,
+        namespace: &'a [u8; N],
+        sequence: u64,
+    ) -> Self {
+        const {
+            assert!(
+                N <= MAX_NAMESPACE_LEN,
+                "namespace exceeds MAX_NAMESPACE_LEN"
+            )
+        };
+        Self {
+            payer,
+            authority,
+            namespace: namespace.as_slice(),
+            sequence,
+        }
+    }
+
     /// Build the CreateBitmap instruction.
     pub fn instruction(&self) -> Instruction {
-        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+        self.instruction_for_program(&PROGRAM_ID)
+    }
+
+    /// Build the CreateBitmap instruction for a deployment at `program_id`.
+    pub fn instruction_for_program(&self, program_id: &Pubkey) -> Instruction {
+        let (pda, _bump) = self.pda_for_program(program_id);
 
         Instruction {
-            program_id: PROGRAM_ID,
+            program_id: *program_id,
             accounts: vec![
                 AccountMeta::new(*self.payer, true),
                 AccountMeta::new_readonly(*self.authority, false),
@@ -122,7 +637,21 @@ impl CreateBitmap<'_> {
 
     /// Get the PDA that will be created.
     pub fn pda(&self) -> (Pubkey, u8) {
-        derive_bitmap_pda(self.authority, self.namespace, self.sequence)
+        self.pda_for_program(&PROGRAM_ID)
+    }
+
+    /// Get the PDA that will be created by the deployment at `program_id`.
+    pub fn pda_for_program(&self, program_id: &Pubkey) -> (Pubkey, u8) {
+        derive_bitmap_pda_for_program(program_id, self.authority, self.namespace, self.sequence)
+    }
+
+    /// Debug builds only: check that `ix` carries the PDA this builder derives
+    /// for `ix.program_id`, warning on stderr if not. See
+    /// [`MarkUsed::check_program_id`].
+    pub fn check_program_id(&self, ix: &Instruction) -> bool {
+        check_bitmap_program_id("CreateBitmap", ix, |program_id| {
+            self.pda_for_program(program_id)
+        })
     }
 }
 
@@ -153,19 +682,49 @@ pub struct MarkUsed<'a> {
     pub payer: &'a Pubkey,
     /// Authority that owns the replay protection namespace (MUST sign).
     pub authority: &'a Pubkey,
-    /// Application-specific namespace (max 64 bytes).
+    /// Application-specific namespace (max 96 bytes).
     pub namespace: &'a [u8],
     /// Sequence number to mark as used.
     pub sequence: u64,
 }
 
-impl MarkUsed<'_> {
+impl<'a> MarkUsed<'a> {
+    /// Const constructor for a namespace known at compile time. An oversized
+    /// namespace fails to compile instead of failing on-chain.
+    pub const fn with_namespace<const N: usize>(
+        payer: &'a Pubkey,
+        authority: &'a Pubkey,
+        namespace: &'a [u8; N],
+        sequence: u64,
+    ) -> Self {
+        const {
+            assert!(
+                N <= MAX_NAMESPACE_LEN,
+                "namespace exceeds MAX_NAMESPACE_LEN"
+            )
+        };
+        Self {
+            payer,
+            authority,
+            namespace: namespace.as_slice(),
+            sequence,
+        }
+    }
+
     /// Build the MarkUsed instruction.
+    ///
+    /// The data is the minimal `[disc][namespace_len][namespace][sequence]`
+    /// layout, `11 + namespace.len()` bytes, with no optional fields.
     pub fn instruction(&self) -> Instruction {
-        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+        self.instruction_for_program(&PROGRAM_ID)
+    }
+
+    /// Build the MarkUsed instruction for a deployment at `program_id`.
+    pub fn instruction_for_program(&self, program_id: &Pubkey) -> Instruction {
+        let (pda, _bump) = self.pda_for_program(program_id);
 
         Instruction {
-            program_id: PROGRAM_ID,
+            program_id: *program_id,
             accounts: vec![
                 AccountMeta::new(*self.payer, true),
                 AccountMeta::new_readonly(*self.authority, true),
@@ -180,13 +739,2182 @@ impl MarkUsed<'_> {
         }
     }
 
+    /// Build the MarkUsed instruction without the system program account.
+    ///
+    /// Only valid once the bucket exists (e.g. it was prefunded with
+    /// CreateBitmap); otherwise the program fails with `NotEnoughAccountKeys`.
+    pub fn instruction_for_existing_bucket(&self) -> Instruction {
+        let mut ix = self.instruction();
+        ix.accounts.pop();
+        ix
+    }
+
+    /// Build the MarkUsed instruction with [`RETURN_STATUS_FLAG`] appended.
+    ///
+    /// On success the program writes `1` (newly marked) as return data, so
+    /// a CPI caller can read it with `get_return_data` instead of re-reading
+    /// the bucket. Costs one extra data byte and a `set_return_data` call.
+    pub fn instruction_with_status(&self) -> Instruction {
+        let mut ix = self.instruction();
+        ix.data.push(crate::instruction::RETURN_STATUS_FLAG);
+        ix
+    }
+
+    /// Build the MarkUsed instruction authorized by the namespace config
+    /// (see [`InitConfig`]) instead of the authority's signature; see
+    /// [`with_config_authority`].
+    pub fn instruction_with_config(&self, current_authority: &Pubkey) -> Instruction {
+        with_config_authority(
+            self.instruction(),
+            &derive_config_pda(self.authority, self.namespace).0,
+            current_authority,
+        )
+    }
+
+    /// One [`MarkUsedBatch`] instruction per bucket touched by `sequences`.
+    ///
+    /// Sequences are grouped with [`group_by_bucket`], so duplicates are
+    /// dropped rather than failing the batch. Instructions come out in
+    /// ascending bucket order; a bucket with many sequences still yields a
+    /// single instruction, so split very large sets to fit the transaction
+    /// size limit.
+    pub fn batch_instructions(
+        payer: &Pubkey,
+        authority: &Pubkey,
+        namespace: &[u8],
+        sequences: &[u64],
+    ) -> Vec<Instruction> {
+        group_by_bucket(sequences)
+            .values()
+            .map(|bucket_sequences| {
+                MarkUsedBatch {
+                    payer,
+                    authority,
+                    namespace,
+                    sequences: bucket_sequences,
+                }
+                .instruction()
+            })
+            .collect()
+    }
+
     /// Get the PDA that will be used/created.
     pub fn pda(&self) -> (Pubkey, u8) {
-        derive_bitmap_pda(self.authority, self.namespace, self.sequence)
+        self.pda_for_program(&PROGRAM_ID)
+    }
+
+    /// Get the PDA that the deployment at `program_id` will use/create.
+    pub fn pda_for_program(&self, program_id: &Pubkey) -> (Pubkey, u8) {
+        derive_bitmap_pda_for_program(program_id, self.authority, self.namespace, self.sequence)
+    }
+
+    /// Debug builds only: check that `ix` carries the PDA this builder derives
+    /// for `ix.program_id`, warning on stderr if not.
+    ///
+    /// Catches a tool that targets several deployments mixing them up, e.g.
+    /// swapping in [`pda`](Self::pda) (compile-time [`PROGRAM_ID`]) for the
+    /// bucket of an [`instruction_for_program`](Self::instruction_for_program).
+    /// Such an instruction looks valid but fails on-chain with
+    /// `BitmapPdaMismatch`. Release builds always return `true`.
+    pub fn check_program_id(&self, ix: &Instruction) -> bool {
+        check_bitmap_program_id("MarkUsed", ix, |program_id| {
+            self.pda_for_program(program_id)
+        })
+    }
+
+    /// Build a signed transaction with a compute-unit price and limit.
+    ///
+    /// Prepends `set_compute_unit_price(micro_lamports_per_cu)` and
+    /// `set_compute_unit_limit` to the MarkUsed instruction. `cu_limit`
+    /// defaults to [`recommended_compute_budget`] for a single instruction.
+    pub fn into_transaction_with_priority(
+        self,
+        blockhash: Hash,
+        payer: &Keypair,
+        authority: &Keypair,
+        micro_lamports_per_cu: u64,
+        cu_limit: Option<u32>,
+    ) -> Transaction {
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_price(micro_lamports_per_cu),
+            ComputeBudgetInstruction::set_compute_unit_limit(
+                cu_limit.unwrap_or_else(|| recommended_compute_budget(1)),
+            ),
+            self.instruction(),
+        ];
+
+        if payer.pubkey() == authority.pubkey() {
+            Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&payer.pubkey()),
+                &[payer],
+                blockhash,
+            )
+        } else {
+            Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&payer.pubkey()),
+                &[payer, authority],
+                blockhash,
+            )
+        }
     }
 }
 
-// Re-export useful constants for clients
-pub use crate::instruction::{CREATE_BITMAP, MARK_USED};
-pub use crate::state::{BITMAP_ACCOUNT_SIZE, BITS_PER_BUCKET};
-pub use crate::MAX_NAMESPACE_LEN;
+/// Upper bound on compute units for one MarkUsed or CreateBitmap instruction.
+///
+/// Covers the worst case (PDA derivation plus the 3-CPI pre-funded creation
+/// path) with headroom; see the `compute_units` bench for measured costs.
+pub const MAX_COMPUTE_UNITS_PER_INSTRUCTION: u32 = 25_000;
+
+/// Compute-unit limit for a transaction carrying `instruction_count` NoReplay
+/// instructions plus the two compute-budget instructions.
+pub fn recommended_compute_budget(instruction_count: usize) -> u32 {
+    const COMPUTE_BUDGET_INSTRUCTIONS: u32 = 2 * 150;
+
+    (instruction_count as u32)
+        .saturating_mul(MAX_COMPUTE_UNITS_PER_INSTRUCTION)
+        .saturating_add(COMPUTE_BUDGET_INSTRUCTIONS)
+}
+
+/// Builder for MarkUsedDomain instruction.
+///
+/// Same as [`MarkUsed`], but the bucket lives in `domain`'s PDA space (see
+/// [`derive_bitmap_pda_domain`]).
+pub struct MarkUsedDomain<'a> {
+    /// Account that pays for PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Partition of the authority's PDA space.
+    pub domain: u8,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to mark as used.
+    pub sequence: u64,
+}
+
+impl MarkUsedDomain<'_> {
+    /// Build the MarkUsedDomain instruction.
+    ///
+    /// Data is `[disc][domain][namespace_len][namespace][sequence]`.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = self.pda();
+
+        let namespace_len = self.namespace.len() as u16;
+        let mut data = Vec::with_capacity(2 + 2 + self.namespace.len() + 8);
+        data.push(crate::instruction::MARK_USED_DOMAIN);
+        data.push(self.domain);
+        data.extend_from_slice(&namespace_len.to_le_bytes());
+        data.extend_from_slice(self.namespace);
+        data.extend_from_slice(&self.sequence.to_le_bytes());
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data,
+        }
+    }
+
+    /// Get the PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_bitmap_pda_domain(self.authority, self.domain, self.namespace, self.sequence)
+    }
+}
+
+/// Builder for MarkUsedCommitted instruction.
+///
+/// Same as [`MarkUsed`], but the bucket is a committed bucket (see
+/// [`derive_committed_pda`]) that also folds `message_hash` into its
+/// accumulator; read it back with [`DecodedCommittedBitmap`].
+pub struct MarkUsedCommitted<'a> {
+    /// Account that pays for PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to mark as used.
+    pub sequence: u64,
+    /// Hash of the message being marked.
+    pub message_hash: &'a [u8; 32],
+}
+
+impl MarkUsedCommitted<'_> {
+    /// Build the MarkUsedCommitted instruction.
+    ///
+    /// Data is `[disc][message_hash][namespace_len][namespace][sequence]`.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = self.pda();
+
+        let namespace_len = self.namespace.len() as u16;
+        let mut data = Vec::with_capacity(1 + 32 + 2 + self.namespace.len() + 8);
+        data.push(crate::instruction::MARK_USED_COMMITTED);
+        data.extend_from_slice(self.message_hash);
+        data.extend_from_slice(&namespace_len.to_le_bytes());
+        data.extend_from_slice(self.namespace);
+        data.extend_from_slice(&self.sequence.to_le_bytes());
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data,
+        }
+    }
+
+    /// Get the PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_committed_pda(self.authority, self.namespace, self.sequence)
+    }
+}
+
+/// Builder for MarkAbove instruction.
+///
+/// Raises the namespace's high watermark (see [`derive_watermark_pda`]).
+/// Fails on-chain unless `sequence` is greater than every sequence marked
+/// before, so it only suits emitters whose sequences strictly increase.
+pub struct MarkAbove<'a> {
+    /// Account that pays for PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to mark as used.
+    pub sequence: u64,
+}
+
+impl MarkAbove<'_> {
+    /// Build the MarkAbove instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = self.pda();
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::MARK_ABOVE,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+
+    /// Get the watermark PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_watermark_pda(self.authority, self.namespace)
+    }
+}
+
+/// Builder for MigrateBucket instruction.
+///
+/// Rewrites a pre-tag bucket ([`LEGACY_BITMAP_ACCOUNT_SIZE`] bytes) in the
+/// current layout, keeping its marks; the payer funds the rent delta (see
+/// [`rent_delta_for_upgrade`]). Permissionless, like [`CreateBitmap`].
+pub struct MigrateBucket<'a> {
+    /// Account that pays the rent delta.
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (does NOT sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Any sequence in the bucket to migrate.
+    pub sequence: u64,
+}
+
+impl MigrateBucket<'_> {
+    /// Build the MigrateBucket instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, false),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::MIGRATE_BUCKET,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+}
+
+/// Builder for MarkWindowed instruction.
+///
+/// Marks `sequence` in the namespace's sliding window (see
+/// [`derive_window_pda`]). Only the last [`WINDOW_BITS`] sequences up to the
+/// highest one marked are tracked; anything older fails on-chain with
+/// `SequenceTooOld`, so it only suits protocols that never deliver a message
+/// that late.
+pub struct MarkWindowed<'a> {
+    /// Account that pays for PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to mark as used.
+    pub sequence: u64,
+}
+
+impl MarkWindowed<'_> {
+    /// Build the MarkWindowed instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = self.pda();
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::MARK_WINDOWED,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+
+    /// Get the window PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_window_pda(self.authority, self.namespace)
+    }
+}
+
+/// Build InitConfig/SetAuthority data: `[disc][namespace_len][namespace][new_authority]`.
+fn build_config_data(discriminator: u8, namespace: &[u8], new_authority: &Pubkey) -> Vec<u8> {
+    let namespace_len = namespace.len() as u16;
+    let mut data = Vec::with_capacity(1 + 2 + namespace.len() + 32);
+    data.push(discriminator);
+    data.extend_from_slice(&namespace_len.to_le_bytes());
+    data.extend_from_slice(namespace);
+    data.extend_from_slice(new_authority.as_ref());
+    data
+}
+
+/// Builder for InitConfig instruction.
+///
+/// Hands marking rights for `(authority, namespace)` to `current_authority`
+/// (e.g. a multisig), checked by [`MarkUsed::instruction_with_config`].
+/// The authority's own signature keeps working for MarkUsed: the program
+/// only sees a config that is passed to it, so this moves authority to a
+/// new key but can't revoke a leaked one.
+pub struct InitConfig<'a> {
+    /// Account that pays for the config PDA.
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Key recorded as the namespace's current authority.
+    pub current_authority: &'a Pubkey,
+}
+
+impl InitConfig<'_> {
+    /// Build the InitConfig instruction.
+    pub fn instruction(&self) -> Instruction {
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(self.pda().0, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: build_config_data(
+                crate::instruction::INIT_CONFIG,
+                self.namespace,
+                self.current_authority,
+            ),
+        }
+    }
+
+    /// Get the config PDA that will be created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_config_pda(self.authority, self.namespace)
+    }
+}
+
+/// Builder for SetAuthority instruction.
+///
+/// Replaces the config's current authority; signed by the current one.
+pub struct SetAuthority<'a> {
+    /// Key currently recorded in the config (MUST sign).
+    pub current_authority: &'a Pubkey,
+    /// Authority the namespace's PDAs are derived from (does NOT sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Key to record in its place.
+    pub new_authority: &'a Pubkey,
+}
+
+impl SetAuthority<'_> {
+    /// Build the SetAuthority instruction.
+    pub fn instruction(&self) -> Instruction {
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.current_authority, true),
+                AccountMeta::new_readonly(*self.authority, false),
+                AccountMeta::new(derive_config_pda(self.authority, self.namespace).0, false),
+            ],
+            data: build_config_data(
+                crate::instruction::SET_AUTHORITY,
+                self.namespace,
+                self.new_authority,
+            ),
+        }
+    }
+}
+
+/// Builder for MarkUsedU128 instruction.
+///
+/// Same as [`MarkUsed`] for sequences wider than u64 (see
+/// [`derive_bitmap_pda_u128`]).
+pub struct MarkUsedU128<'a> {
+    /// Account that pays for PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to mark as used.
+    pub sequence: u128,
+}
+
+impl MarkUsedU128<'_> {
+    /// Build the MarkUsedU128 instruction.
+    ///
+    /// Data is `[disc][namespace_len][namespace][sequence: u128 LE]`.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = self.pda();
+
+        let namespace_len = self.namespace.len() as u16;
+        let mut data = Vec::with_capacity(1 + 2 + self.namespace.len() + 16);
+        data.push(crate::instruction::MARK_USED_U128);
+        data.extend_from_slice(&namespace_len.to_le_bytes());
+        data.extend_from_slice(self.namespace);
+        data.extend_from_slice(&self.sequence.to_le_bytes());
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data,
+        }
+    }
+
+    /// Get the PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_bitmap_pda_u128(self.authority, self.namespace, self.sequence)
+    }
+}
+
+/// Builder for MarkUsedIdempotent instruction.
+///
+/// Ensures a sequence number is marked as used. Same accounts as MarkUsed
+/// (authority MUST sign), but succeeds if the sequence was already marked.
+/// The program's return data is one byte: `1` if this call marked the
+/// sequence, `0` if it was already marked.
+///
+/// # Example
+///
+/// ```ignore
+/// let ix = MarkUsedIdempotent {
+///     payer: &payer_pubkey,
+///     authority: &authority_pubkey,
+///     namespace: b"my_namespace",
+///     sequence: 42,
+/// }.instruction();
+/// ```
+pub struct MarkUsedIdempotent<'a> {
+    /// Account that pays for PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to mark as used.
+    pub sequence: u64,
+}
+
+impl MarkUsedIdempotent<'_> {
+    /// Build the MarkUsedIdempotent instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::MARK_USED_IDEMPOTENT,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+
+    /// Get the PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_bitmap_pda(self.authority, self.namespace, self.sequence)
+    }
+}
+
+/// Builder for MultiBucketMark instruction.
+///
+/// Marks sequences spanning several buckets in one instruction. One bitmap
+/// PDA is passed per distinct bucket (in order of first appearance), so at
+/// most [`MAX_MULTI_BUCKET_ACCOUNTS`] distinct buckets are accepted on-chain.
+///
+/// # Accounts
+///
+/// 0. `[signer, writable]` Payer - pays for PDA creation if needed
+/// 1. `[signer]` Authority - must sign; goes into PDA seeds
+/// 2. `[]` System program
+/// 3. `[writable]` Bitmap PDAs, one per distinct bucket, occupying index 3
+///    onward
+pub struct MultiBucketMark<'a> {
+    /// Account that pays for PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence numbers to mark as used (max 255).
+    pub sequences: &'a [u64],
+}
+
+impl MultiBucketMark<'_> {
+    /// Build the MultiBucketMark instruction.
+    pub fn instruction(&self) -> Instruction {
+        let mut buckets: Vec<u64> = Vec::new();
+        let mut entries = Vec::with_capacity(self.sequences.len() * MULTI_BUCKET_ENTRY_SIZE);
+        for &sequence in self.sequences {
+            let bucket_index = bucket_of(sequence);
+            let pda_index = match buckets.iter().position(|&b| b == bucket_index) {
+                Some(pda_index) => pda_index,
+                None => {
+                    buckets.push(bucket_index);
+                    buckets.len() - 1
+                }
+            };
+            entries.push(pda_index as u8);
+            entries.extend_from_slice(&sequence.to_le_bytes());
+        }
+
+        let mut accounts = vec![
+            AccountMeta::new(*self.payer, true),
+            AccountMeta::new_readonly(*self.authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        accounts.extend(buckets.iter().map(|&bucket_index| {
+            AccountMeta::new(
+                derive_bucket_pda(self.authority, self.namespace, bucket_index).0,
+                false,
+            )
+        }));
+
+        let mut data = Vec::with_capacity(1 + 2 + self.namespace.len() + 1 + entries.len());
+        data.push(crate::instruction::MULTI_BUCKET_MARK);
+        data.extend_from_slice(&(self.namespace.len() as u16).to_le_bytes());
+        data.extend_from_slice(self.namespace);
+        data.push(self.sequences.len() as u8);
+        data.extend_from_slice(&entries);
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts,
+            data,
+        }
+    }
+}
+
+/// Group sequences by bucket index (`sequence / BITS_PER_BUCKET`).
+///
+/// Each bucket's sequences are sorted and deduplicated, ready to pass to
+/// [`MarkUsedBatch`], which rejects repeats within a batch.
+pub fn group_by_bucket(sequences: &[u64]) -> std::collections::BTreeMap<u64, Vec<u64>> {
+    let mut buckets: std::collections::BTreeMap<u64, Vec<u64>> = std::collections::BTreeMap::new();
+    for &sequence in sequences {
+        buckets
+            .entry(bucket_of(sequence))
+            .or_default()
+            .push(sequence);
+    }
+    for bucket_sequences in buckets.values_mut() {
+        bucket_sequences.sort_unstable();
+        bucket_sequences.dedup();
+    }
+    buckets
+}
+
+/// Builder for MarkUsedBatch instruction.
+///
+/// Marks several sequences in one bucket with a single instruction. All
+/// sequences must map to the same bucket and the authority must sign, as for
+/// [`MarkUsed`].
+///
+/// # Accounts
+///
+/// 1. `[signer, writable]` Payer - pays for PDA creation if needed
+/// 2. `[signer]` Authority - must sign; goes into PDA seeds
+/// 3. `[writable]` Bitmap PDA
+/// 4. `[]` System program
+pub struct MarkUsedBatch<'a> {
+    /// Account that pays for PDA creation if needed.
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence numbers to mark, all in one bucket. Must be non-empty.
+    pub sequences: &'a [u64],
+}
+
+impl MarkUsedBatch<'_> {
+    /// Build the MarkUsedBatch instruction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sequences` is empty.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequences[0]);
+
+        let mut data =
+            Vec::with_capacity(1 + 2 + self.namespace.len() + 2 + 8 * self.sequences.len());
+        data.push(crate::instruction::MARK_USED_BATCH);
+        data.extend_from_slice(&(self.namespace.len() as u16).to_le_bytes());
+        data.extend_from_slice(self.namespace);
+        data.extend_from_slice(&(self.sequences.len() as u16).to_le_bytes());
+        for sequence in self.sequences {
+            data.extend_from_slice(&sequence.to_le_bytes());
+        }
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data,
+        }
+    }
+}
+
+/// Builder for MarkUsedRange instruction.
+///
+/// Marks the contiguous sequences `start..=end`, which must lie in one
+/// bucket. Authority MUST sign, as for [`MarkUsed`].
+///
+/// # Accounts
+///
+/// 1. `[signer, writable]` Payer - pays for PDA creation if needed
+/// 2. `[signer]` Authority - must sign; goes into PDA seeds
+/// 3. `[writable]` Bitmap PDA
+/// 4. `[]` System program
+///
+/// # Example
+///
+/// ```ignore
+/// let ix = MarkUsedRange {
+///     payer: &payer_pubkey,
+///     authority: &authority_pubkey,
+///     namespace: b"my_namespace",
+///     start: 100,
+///     end: 163,
+/// }.instruction();
+/// ```
+pub struct MarkUsedRange<'a> {
+    /// Account that pays for PDA creation if needed.
+    pub payer: &'a Pubkey,
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// First sequence number to mark.
+    pub start: u64,
+    /// Last sequence number to mark (inclusive).
+    pub end: u64,
+}
+
+impl MarkUsedRange<'_> {
+    /// Build the MarkUsedRange instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.start);
+
+        let mut data = Vec::with_capacity(1 + 2 + self.namespace.len() + 16);
+        data.push(crate::instruction::MARK_USED_RANGE);
+        data.extend_from_slice(&(self.namespace.len() as u16).to_le_bytes());
+        data.extend_from_slice(self.namespace);
+        data.extend_from_slice(&self.start.to_le_bytes());
+        data.extend_from_slice(&self.end.to_le_bytes());
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data,
+        }
+    }
+
+    /// Get the PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_bitmap_pda(self.authority, self.namespace, self.start)
+    }
+}
+
+/// Builder for CloseBitmap instruction.
+///
+/// Reclaims the rent of a bucket whose sequences are all marked. The program
+/// rejects buckets with any unused sequence (`BucketNotFullyUsed`).
+///
+/// Closing discards the bucket's record: a recreated bucket starts empty, so
+/// only close buckets whose sequences the application already rejects by
+/// other means (e.g. a monotonic lower bound on accepted sequences).
+///
+/// # Accounts
+///
+/// 1. `[signer]` Authority - must sign; goes into PDA seeds
+/// 2. `[writable]` Bitmap PDA
+/// 3. `[writable]` Recipient - receives the bucket's lamports
+///
+/// Once the namespace has a config, only its current authority may close
+/// its buckets; see [`CloseBitmap::instruction_with_config`].
+pub struct CloseBitmap<'a> {
+    /// Authority that owns the replay protection namespace (MUST sign).
+    pub authority: &'a Pubkey,
+    /// Account that receives the reclaimed lamports.
+    pub recipient: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Any sequence number in the bucket to close.
+    pub sequence: u64,
+}
+
+impl CloseBitmap<'_> {
+    /// Build the CloseBitmap instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.authority, true),
+                AccountMeta::new(pda, false),
+                AccountMeta::new(*self.recipient, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::CLOSE_BITMAP,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+
+    /// Build the CloseBitmap instruction authorized by the namespace config
+    /// (see [`InitConfig`]) instead of the authority's signature, as
+    /// [`with_config_authority`] does for marks.
+    pub fn instruction_with_config(&self, current_authority: &Pubkey) -> Instruction {
+        let mut ix = self.instruction();
+        ix.accounts[0].is_signer = false;
+        ix.accounts.extend([
+            AccountMeta::new_readonly(derive_config_pda(self.authority, self.namespace).0, false),
+            AccountMeta::new_readonly(*current_authority, true),
+        ]);
+        ix
+    }
+}
+
+/// Builder for CheckUsed instruction.
+///
+/// Read-only and permissionless: the program writes `1` as return data if
+/// the sequence is marked and `0` otherwise (including when the bucket
+/// doesn't exist yet).
+///
+/// # Accounts
+///
+/// 1. `[]` Authority - goes into PDA seeds (does NOT need to sign)
+/// 2. `[]` Bitmap PDA
+pub struct CheckUsed<'a> {
+    /// Authority that owns the replay protection namespace.
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to check.
+    pub sequence: u64,
+}
+
+impl CheckUsed<'_> {
+    /// Build the CheckUsed instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.authority, false),
+                AccountMeta::new_readonly(pda, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::CHECK_USED,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+}
+
+/// Builder for RangeAnyUsed instruction.
+///
+/// Read-only and permissionless: the program writes `1` as return data if
+/// any sequence in `start..start + count` is marked and `0` otherwise. The
+/// run must be non-empty and lie within one bucket.
+///
+/// # Accounts
+///
+/// 1. `[]` Authority - goes into PDA seeds (does NOT need to sign)
+/// 2. `[]` Bitmap PDA
+pub struct RangeAnyUsed<'a> {
+    /// Authority that owns the replay protection namespace.
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// First sequence number to check.
+    pub start: u64,
+    /// Number of consecutive sequences to check.
+    pub count: u64,
+}
+
+impl RangeAnyUsed<'_> {
+    /// Build the RangeAnyUsed instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.start);
+
+        let mut data = Vec::with_capacity(1 + 2 + self.namespace.len() + 16);
+        data.push(crate::instruction::RANGE_ANY_USED);
+        data.extend_from_slice(&(self.namespace.len() as u16).to_le_bytes());
+        data.extend_from_slice(self.namespace);
+        data.extend_from_slice(&self.start.to_le_bytes());
+        data.extend_from_slice(&self.count.to_le_bytes());
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.authority, false),
+                AccountMeta::new_readonly(pda, false),
+            ],
+            data,
+        }
+    }
+}
+
+/// Builder for VerifyBucket instruction.
+///
+/// Read-only health check for monitoring: the program writes one
+/// [`BucketStatus`] byte as return data (decode it with
+/// `BucketStatus::try_from`). Simulate it or include it in any transaction.
+///
+/// # Accounts
+///
+/// 1. `[]` Authority - goes into PDA seeds (does NOT need to sign)
+/// 2. `[]` Bitmap PDA
+pub struct VerifyBucket<'a> {
+    /// Authority that owns the replay protection namespace.
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Any sequence in the bucket to check.
+    pub sequence: u64,
+}
+
+impl VerifyBucket<'_> {
+    /// Build the VerifyBucket instruction.
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.authority, false),
+                AccountMeta::new_readonly(pda, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::VERIFY_BUCKET,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+}
+
+/// Builder for MarkUsedDelegated instruction.
+///
+/// Lets a relayer mark a sequence on the authority's behalf. The authority
+/// signs [`delegation_message`] off-chain (e.g. with `Keypair::sign_message`)
+/// and hands the signature to the relayer, who submits the pair returned by
+/// [`MarkUsedDelegated::instructions`] in order, in one transaction.
+///
+/// The authority must be a keypair: PDAs cannot produce ed25519 signatures.
+///
+/// # Accounts
+///
+/// 1. `[signer, writable]` Payer - relayer; pays for PDA creation if needed
+/// 2. `[]` Authority - goes into PDA seeds (does NOT sign the transaction)
+/// 3. `[writable]` Bitmap PDA
+/// 4. `[]` System program
+/// 5. `[]` Instructions sysvar
+pub struct MarkUsedDelegated<'a> {
+    /// Relayer paying for the transaction and PDA creation (if needed).
+    pub payer: &'a Pubkey,
+    /// Authority that signed the off-chain authorization.
+    pub authority: &'a Pubkey,
+    /// Application-specific namespace (max 96 bytes).
+    pub namespace: &'a [u8],
+    /// Sequence number to mark as used.
+    pub sequence: u64,
+}
+
+impl MarkUsedDelegated<'_> {
+    /// Build the MarkUsedDelegated instruction alone.
+    ///
+    /// It must be immediately preceded by the matching ed25519 instruction;
+    /// prefer [`MarkUsedDelegated::instructions`].
+    pub fn instruction(&self) -> Instruction {
+        let (pda, _bump) = derive_bitmap_pda(self.authority, self.namespace, self.sequence);
+
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.payer, true),
+                AccountMeta::new_readonly(*self.authority, false),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(sysvar::instructions::ID, false),
+            ],
+            data: build_instruction_data(
+                crate::instruction::MARK_USED_DELEGATED,
+                self.namespace,
+                self.sequence,
+            ),
+        }
+    }
+
+    /// Build the ed25519 verification instruction followed by MarkUsedDelegated.
+    pub fn instructions(&self, signature: &Signature) -> [Instruction; 2] {
+        [
+            ed25519_instruction(
+                self.authority,
+                signature,
+                &delegation_message(self.authority, self.namespace, self.sequence),
+            ),
+            self.instruction(),
+        ]
+    }
+
+    /// [`MarkUsedDelegated::instructions`] for an authorization signed by
+    /// the namespace config's `current_authority` (see [`InitConfig`]).
+    ///
+    /// Passes the config PDA after the instructions sysvar; once the config
+    /// exists, the program only accepts its current authority's signature.
+    pub fn instructions_with_config(
+        &self,
+        current_authority: &Pubkey,
+        signature: &Signature,
+    ) -> [Instruction; 2] {
+        let mut ix = self.instruction();
+        ix.accounts.push(AccountMeta::new_readonly(
+            derive_config_pda(self.authority, self.namespace).0,
+            false,
+        ));
+        [
+            ed25519_instruction(
+                current_authority,
+                signature,
+                &delegation_message(self.authority, self.namespace, self.sequence),
+            ),
+            ix,
+        ]
+    }
+
+    /// Get the PDA that will be used/created.
+    pub fn pda(&self) -> (Pubkey, u8) {
+        derive_bitmap_pda(self.authority, self.namespace, self.sequence)
+    }
+}
+
+/// Message the authority signs to authorize MarkUsedDelegated for
+/// `(authority, namespace, sequence)` on this program.
+///
+/// `authority` is the one in the bucket's seeds, also when the namespace
+/// config's current authority signs.
+///
+/// Format: `DELEGATION_DOMAIN || program_id || authority || namespace_len
+/// (u16 LE) || namespace || sequence (u64 LE)`.
+pub fn delegation_message(authority: &Pubkey, namespace: &[u8], sequence: u64) -> Vec<u8> {
+    let mut message =
+        Vec::with_capacity(DELEGATION_DOMAIN.len() + 32 + 32 + 2 + namespace.len() + 8);
+    message.extend_from_slice(DELEGATION_DOMAIN);
+    message.extend_from_slice(PROGRAM_ID.as_ref());
+    message.extend_from_slice(authority.as_ref());
+    message.extend_from_slice(&(namespace.len() as u16).to_le_bytes());
+    message.extend_from_slice(namespace);
+    message.extend_from_slice(&sequence.to_le_bytes());
+    message
+}
+
+/// Build an ed25519 precompile instruction verifying one signature, with
+/// public key, signature and message all stored in the instruction itself
+/// (the only form MarkUsedDelegated accepts).
+pub fn ed25519_instruction(
+    public_key: &Pubkey,
+    signature: &Signature,
+    message: &[u8],
+) -> Instruction {
+    const HEADER_LEN: u16 = 2;
+    const OFFSETS_LEN: u16 = 14;
+    const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+    let public_key_offset = HEADER_LEN + OFFSETS_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+
+    let mut data = Vec::with_capacity(message_offset as usize + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for field in [
+        signature_offset,
+        CURRENT_INSTRUCTION,
+        public_key_offset,
+        CURRENT_INSTRUCTION,
+        message_offset,
+        message.len() as u16,
+        CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(public_key.as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Mark `sequence` in both namespaces during a namespace migration.
+///
+/// Both are plain MarkUsed instructions, so the transaction fails as a replay
+/// if the sequence was already consumed under either namespace, and on
+/// success it is consumed under both. Submit them together in one transaction
+/// for the whole overlap window; see "Rotating namespaces" in the README.
+pub fn dual_mark(
+    payer: &Pubkey,
+    authority: &Pubkey,
+    old_namespace: &[u8],
+    new_namespace: &[u8],
+    sequence: u64,
+) -> Vec<Instruction> {
+    [old_namespace, new_namespace]
+        .into_iter()
+        .map(|namespace| {
+            MarkUsed {
+                payer,
+                authority,
+                namespace,
+                sequence,
+            }
+            .instruction()
+        })
+        .collect()
+}
+
+/// Build one `CreateBitmap` instruction per distinct bucket in `sequences`.
+///
+/// Unlike prefunding a contiguous range, this only creates the buckets a
+/// sparse sequence set actually touches. Instructions are ordered by bucket.
+pub fn minimal_creates(
+    payer: &Pubkey,
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequences: &[u64],
+) -> Vec<Instruction> {
+    let buckets: std::collections::BTreeSet<u64> = sequences
+        .iter()
+        .map(|&sequence| bucket_of(sequence))
+        .collect();
+
+    buckets
+        .into_iter()
+        .map(|bucket| {
+            CreateBitmap {
+                payer,
+                authority,
+                namespace,
+                sequence: bucket * BITS_PER_BUCKET,
+            }
+            .instruction()
+        })
+        .collect()
+}
+
+/// Resumable prefund of a contiguous bucket range, for jobs spanning many
+/// sessions.
+///
+/// [`next_batch`](Self::next_batch) yields CreateBitmap instructions for the
+/// next unconfirmed buckets; [`confirm`](Self::confirm) advances the
+/// checkpoint once they landed. Persist [`to_bytes`](Self::to_bytes) after
+/// each confirmation and restore with [`from_bytes`](Self::from_bytes) after
+/// a restart. A batch that was sent but not confirmed is yielded again on
+/// resume, which is harmless since CreateBitmap is idempotent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefundJob {
+    payer: Pubkey,
+    authority: Pubkey,
+    namespace: Vec<u8>,
+    /// First bucket not yet confirmed.
+    next_bucket: u64,
+    /// Exclusive end of the bucket range.
+    end_bucket: u64,
+    /// Buckets handed out by the last `next_batch` and not yet confirmed.
+    in_flight: u64,
+}
+
+impl PrefundJob {
+    /// Plan a prefund of `buckets` (bucket indices, not sequences).
+    pub fn new(
+        payer: &Pubkey,
+        authority: &Pubkey,
+        namespace: &[u8],
+        buckets: std::ops::Range<u64>,
+    ) -> Self {
+        Self {
+            payer: *payer,
+            authority: *authority,
+            namespace: namespace.to_vec(),
+            next_bucket: buckets.start,
+            end_bucket: buckets.end.max(buckets.start),
+            in_flight: 0,
+        }
+    }
+
+    /// CreateBitmap instructions for up to `max` buckets after the checkpoint.
+    ///
+    /// Calling again before [`confirm`](Self::confirm) yields the same buckets.
+    pub fn next_batch(&mut self, max: usize) -> Vec<Instruction> {
+        let remaining = self.end_bucket - self.next_bucket;
+        self.in_flight = remaining.min(max as u64);
+
+        (self.next_bucket..self.next_bucket + self.in_flight)
+            .map(|bucket| {
+                CreateBitmap {
+                    payer: &self.payer,
+                    authority: &self.authority,
+                    namespace: &self.namespace,
+                    sequence: bucket * BITS_PER_BUCKET,
+                }
+                .instruction()
+            })
+            .collect()
+    }
+
+    /// Mark the last batch as landed and advance the checkpoint past it.
+    pub fn confirm(&mut self) {
+        self.next_bucket += self.in_flight;
+        self.in_flight = 0;
+    }
+
+    /// First bucket index not yet confirmed.
+    pub fn checkpoint(&self) -> u64 {
+        self.next_bucket
+    }
+
+    /// Whether every bucket in the range has been confirmed.
+    pub fn is_complete(&self) -> bool {
+        self.next_bucket >= self.end_bucket
+    }
+
+    /// Serialize the job and its checkpoint.
+    ///
+    /// Format: `[payer: 32][authority: 32][next_bucket: u64 LE]
+    /// [end_bucket: u64 LE][namespace_len: u16 LE][namespace]`. An
+    /// unconfirmed batch is not recorded.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(82 + self.namespace.len());
+        bytes.extend_from_slice(self.payer.as_ref());
+        bytes.extend_from_slice(self.authority.as_ref());
+        bytes.extend_from_slice(&self.next_bucket.to_le_bytes());
+        bytes.extend_from_slice(&self.end_bucket.to_le_bytes());
+        bytes.extend_from_slice(&(self.namespace.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.namespace);
+        bytes
+    }
+
+    /// Restore a job saved with [`to_bytes`](Self::to_bytes).
+    ///
+    /// Returns `None` if the bytes are truncated, have trailing data, or the
+    /// checkpoint lies past the end of the range.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (payer, rest) = bytes.split_first_chunk::<32>()?;
+        let (authority, rest) = rest.split_first_chunk::<32>()?;
+        let (next_bucket, rest) = rest.split_first_chunk::<8>()?;
+        let (end_bucket, rest) = rest.split_first_chunk::<8>()?;
+        let (namespace_len, namespace) = rest.split_first_chunk::<2>()?;
+
+        let next_bucket = u64::from_le_bytes(*next_bucket);
+        let end_bucket = u64::from_le_bytes(*end_bucket);
+        if namespace.len() != u16::from_le_bytes(*namespace_len) as usize
+            || next_bucket > end_bucket
+        {
+            return None;
+        }
+
+        Some(Self {
+            payer: Pubkey::new_from_array(*payer),
+            authority: Pubkey::new_from_array(*authority),
+            namespace: namespace.to_vec(),
+            next_bucket,
+            end_bucket,
+            in_flight: 0,
+        })
+    }
+}
+
+/// Advisory finding from [`check_namespace_hygiene`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hygiene {
+    /// The namespace is empty.
+    Empty,
+    /// The namespace starts with `0x00` bytes.
+    LeadingZeroBytes,
+    /// The namespace ends with `0x00` bytes (e.g. a zero-padded fixed buffer).
+    TrailingZeroBytes,
+    /// The namespace is UTF-8 with leading or trailing whitespace.
+    SurroundingWhitespace,
+    /// The namespace is not valid UTF-8.
+    NonUtf8,
+    /// The namespace is within `NEAR_MAX_NAMESPACE_MARGIN` bytes of the limit.
+    NearMaxLength,
+}
+
+/// Length margin below `MAX_NAMESPACE_LEN` that triggers [`Hygiene::NearMaxLength`].
+pub const NEAR_MAX_NAMESPACE_MARGIN: usize = 8;
+
+/// Flag namespace patterns that often indicate an integration bug.
+///
+/// Padded or trimmed variants of a namespace derive different PDAs, so a
+/// namespace built inconsistently in two places silently splits the replay
+/// space. This check is advisory: binary namespaces (e.g. `chain_id ||
+/// emitter_address`) legitimately contain zero bytes and non-UTF-8 content.
+pub fn check_namespace_hygiene(namespace: &[u8]) -> Vec<Hygiene> {
+    let mut findings = Vec::new();
+
+    if namespace.is_empty() {
+        findings.push(Hygiene::Empty);
+        return findings;
+    }
+    if namespace.first() == Some(&0) {
+        findings.push(Hygiene::LeadingZeroBytes);
+    }
+    if namespace.last() == Some(&0) {
+        findings.push(Hygiene::TrailingZeroBytes);
+    }
+    match std::str::from_utf8(namespace) {
+        Ok(text) if text.trim() != text => findings.push(Hygiene::SurroundingWhitespace),
+        Ok(_) => {}
+        Err(_) => findings.push(Hygiene::NonUtf8),
+    }
+    if namespace.len() + NEAR_MAX_NAMESPACE_MARGIN > MAX_NAMESPACE_LEN {
+        findings.push(Hygiene::NearMaxLength);
+    }
+
+    findings
+}
+
+/// Convention for packing several small logical namespaces into one physical
+/// namespace's sequence space.
+///
+/// Logical namespace `ns_id` owns sequences `ns_id * stride .. (ns_id + 1) *
+/// stride`. With a stride below `BITS_PER_BUCKET`, several logical namespaces
+/// share one bucket account and its rent, which suits many namespaces that
+/// each consume only a handful of sequences.
+///
+/// The tradeoff is isolation: all logical namespaces share one authority and
+/// one physical namespace, a logical namespace can never grow past `stride`
+/// sequences, and the stride can't change once anything is marked (existing
+/// sequences would be reinterpreted). Pick it with headroom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequencePacking {
+    /// Sequences reserved per logical namespace. Must be non-zero.
+    pub stride: u64,
+}
+
+impl SequencePacking {
+    /// Physical sequence for `local_sequence` of logical namespace `ns_id`.
+    ///
+    /// Returns `None` if `local_sequence` doesn't fit in the stride (it would
+    /// collide with the next logical namespace) or the result overflows.
+    pub fn pack_sequence(&self, ns_id: u64, local_sequence: u64) -> Option<u64> {
+        if local_sequence >= self.stride {
+            return None;
+        }
+        ns_id.checked_mul(self.stride)?.checked_add(local_sequence)
+    }
+
+    /// Split a physical sequence back into `(ns_id, local_sequence)`.
+    pub fn unpack_sequence(&self, sequence: u64) -> (u64, u64) {
+        (sequence / self.stride, sequence % self.stride)
+    }
+}
+
+/// Count the distinct account keys a transaction built from `instructions`
+/// will reference, including program IDs.
+///
+/// Payer, authority, and system program repeat across instructions while
+/// PDAs differ per bucket. Use this to stay under the per-transaction
+/// account limits before submitting a batch.
+pub fn count_unique_accounts(instructions: &[Instruction]) -> usize {
+    let mut keys = std::collections::HashSet::new();
+    for ix in instructions {
+        keys.insert(ix.program_id);
+        keys.extend(ix.accounts.iter().map(|meta| meta.pubkey));
+    }
+    keys.len()
+}
+
+/// Default cap on distinct accounts per transaction used by batch builders.
+///
+/// Conservative relative to the runtime's limits so that compute-budget
+/// instructions and extra signers still fit. Tune per RPC provider with
+/// [`chunk_instructions`].
+pub const DEFAULT_MAX_ACCOUNTS_PER_TX: usize = 32;
+
+/// Split instructions into transaction-sized chunks, preserving order.
+///
+/// Each chunk, with `payer` as fee payer, references at most
+/// `max_accounts_per_tx` distinct accounts and serializes within
+/// `PACKET_DATA_SIZE`. An instruction that exceeds either limit on its own is
+/// still emitted as a single-instruction chunk.
+pub fn chunk_instructions(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    max_accounts_per_tx: usize,
+) -> Vec<Vec<Instruction>> {
+    let fits = |chunk: &[Instruction]| {
+        let message = Message::new(chunk, Some(payer));
+        // Signature count (short_vec, 1 byte below 128) + signatures + message
+        let tx_size =
+            1 + message.header.num_required_signatures as usize * 64 + message.serialize().len();
+        message.account_keys.len() <= max_accounts_per_tx && tx_size <= PACKET_DATA_SIZE
+    };
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<Instruction> = Vec::new();
+    for ix in instructions {
+        current.push(ix.clone());
+        if current.len() > 1 && !fits(&current) {
+            let overflow = current.pop().expect("just pushed");
+            chunks.push(std::mem::replace(&mut current, vec![overflow]));
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Group instructions so that no two in a group share a writable account.
+///
+/// Transactions write-locking the same account are serialized by the runtime,
+/// so instructions within a group can be sent as concurrent transactions
+/// while groups are sent one after another. Groups preserve input order and
+/// are filled greedily (first group without a conflict).
+///
+/// The payer is writable, so CreateBitmap instructions sharing one payer all
+/// conflict and each lands in its own group. To parallelize prefunding,
+/// spread the instructions across several payers (and use each as its
+/// transaction's fee payer, which is write-locked too).
+pub fn parallelizable_groups(instructions: &[Instruction]) -> Vec<Vec<Instruction>> {
+    let mut groups: Vec<(std::collections::HashSet<Pubkey>, Vec<Instruction>)> = Vec::new();
+    for ix in instructions {
+        let writable: std::collections::HashSet<Pubkey> = ix
+            .accounts
+            .iter()
+            .filter(|meta| meta.is_writable)
+            .map(|meta| meta.pubkey)
+            .collect();
+
+        match groups
+            .iter_mut()
+            .find(|(locked, _)| locked.is_disjoint(&writable))
+        {
+            Some((locked, group)) => {
+                locked.extend(writable);
+                group.push(ix.clone());
+            }
+            None => groups.push((writable, vec![ix.clone()])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Check whether `tx` contains a top-level MarkUsed instruction for
+/// `(authority, namespace, sequence)` against `program_id`.
+///
+/// Matches the instruction data exactly, requires the authority account to
+/// be a signer, and requires the bitmap account to be the bucket PDA derived
+/// under `program_id`. Only inspects the transaction itself: marks made via
+/// CPI from another program are not visible, and whether the transaction
+/// landed must be checked separately.
+pub fn tx_marks_sequence(
+    tx: &Transaction,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> bool {
+    let expected_data = build_instruction_data(crate::instruction::MARK_USED, namespace, sequence);
+
+    let bucket_bytes = bucket_of(sequence).to_le_bytes();
+    let [ns_chunk_0, ns_chunk_1, ns_chunk_2] = split_namespace(namespace);
+    let seeds: [&[u8]; 5] = [
+        authority.as_ref(),
+        ns_chunk_0,
+        ns_chunk_1,
+        ns_chunk_2,
+        &bucket_bytes,
+    ];
+    let (expected_pda, _bump) = Pubkey::find_program_address(&seeds, program_id);
+
+    let message = &tx.message;
+    let key = |index: u8| message.account_keys.get(index as usize);
+
+    message.instructions.iter().any(|ix| {
+        let [_payer, authority_index, pda_index, ..] = ix.accounts[..] else {
+            return false;
+        };
+        key(ix.program_id_index) == Some(program_id)
+            && ix.data == expected_data
+            && key(authority_index) == Some(authority)
+            && message.is_signer(authority_index as usize)
+            && key(pda_index) == Some(&expected_pda)
+    })
+}
+
+// =============================================================================
+// Account decoding
+// =============================================================================
+
+/// Error returned when bitmap account data cannot be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Account data is not exactly `BITMAP_ACCOUNT_SIZE` bytes. This usually
+    /// means a legacy layout, corruption, or a foreign account at the PDA.
+    UnexpectedSize { got: usize, expected: usize },
+    /// The leading tag isn't the current [`BITMAP_TAG`]: a foreign account,
+    /// one from a pre-tag build, or a newer format version.
+    BadTag(crate::state::TagError),
+}
+
+/// Owned copy of a bitmap account's data, decoded off-chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedBitmap {
+    /// Bump seed stored after the tag.
+    pub bump: u8,
+    /// Bitmap of used sequences within the bucket.
+    pub bitmap: [u8; BITMAP_BYTES],
+}
+
+impl DecodedBitmap {
+    /// Decode raw account data. Fails if the size or tag doesn't match the
+    /// current layout.
+    pub fn try_decode(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.len() != BITMAP_ACCOUNT_SIZE {
+            return Err(DecodeError::UnexpectedSize {
+                got: data.len(),
+                expected: BITMAP_ACCOUNT_SIZE,
+            });
+        }
+
+        let account = crate::state::BitmapAccountRef::from_slice(data).ok_or(
+            DecodeError::UnexpectedSize {
+                got: data.len(),
+                expected: BITMAP_ACCOUNT_SIZE,
+            },
+        )?;
+        crate::state::check_tag(account.tag).map_err(DecodeError::BadTag)?;
+
+        Ok(Self {
+            bump: *account.bump,
+            bitmap: *account.bitmap,
+        })
+    }
+
+    /// Check if a sequence number is marked as used in this bucket.
+    pub fn is_used(&self, sequence: u64) -> bool {
+        let (byte_index, bit_offset) = bit_position(sequence);
+        self.bitmap[byte_index] & (1 << bit_offset) != 0
+    }
+
+    /// Number of used sequences in this bucket.
+    pub fn count_used(&self) -> u64 {
+        self.bitmap
+            .iter()
+            .map(|byte| byte.count_ones() as u64)
+            .sum()
+    }
+
+    /// Runs of used sequences as `(start, len)`, in ascending order.
+    ///
+    /// `bucket_index` supplies the base sequence, since the account itself
+    /// doesn't store it. Much more compact than the raw bitmap for buckets
+    /// that are mostly empty or mostly full.
+    pub fn to_rle(&self, bucket_index: u64) -> Vec<(u64, u64)> {
+        let base = bucket_index * BITS_PER_BUCKET;
+        let mut runs: Vec<(u64, u64)> = Vec::new();
+        for offset in 0..BITS_PER_BUCKET {
+            if !self.is_used(offset) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((start, len)) if *start + *len == base + offset => *len += 1,
+                _ => runs.push((base + offset, 1)),
+            }
+        }
+        runs
+    }
+
+    /// Render the bucket as a 32x32 grid of `.` (unused) and `#` (used),
+    /// for operator tooling.
+    ///
+    /// The first line labels the bucket's sequence range; each grid row is
+    /// prefixed with its first sequence, so row `r`, column `c` is sequence
+    /// `base + 32 * r + c`.
+    pub fn to_string_grid(&self, bucket_index: u64) -> String {
+        const WIDTH: u64 = 32;
+        let base = bucket_index * BITS_PER_BUCKET;
+        let last = base + (BITS_PER_BUCKET - 1);
+        let label_width = last.to_string().len();
+
+        let mut grid = format!("bucket {bucket_index}: sequences {base}..={last}\n");
+        for row in 0..BITS_PER_BUCKET / WIDTH {
+            let row_start = row * WIDTH;
+            grid.push_str(&format!("{:>label_width$} ", base + row_start));
+            for offset in row_start..row_start + WIDTH {
+                grid.push(if self.is_used(offset) { '#' } else { '.' });
+            }
+            grid.push('\n');
+        }
+        grid
+    }
+}
+
+/// Owned copy of a committed bucket's data, decoded off-chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedCommittedBitmap {
+    /// Bump seed stored after the tag.
+    pub bump: u8,
+    /// Hash chain over the bucket's marks, see [`next_accumulator`].
+    pub accumulator: [u8; ACCUMULATOR_BYTES],
+    /// Bitmap of used sequences within the bucket.
+    pub bitmap: [u8; BITMAP_BYTES],
+}
+
+impl DecodedCommittedBitmap {
+    /// Decode raw committed bucket data. Fails if the size or tag doesn't
+    /// match the current layout.
+    pub fn try_decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let size_error = DecodeError::UnexpectedSize {
+            got: data.len(),
+            expected: COMMITTED_ACCOUNT_SIZE,
+        };
+        if data.len() != COMMITTED_ACCOUNT_SIZE {
+            return Err(size_error);
+        }
+
+        let (tag, rest) = data.split_first_chunk().ok_or(size_error)?;
+        crate::state::check_committed_tag(tag).map_err(DecodeError::BadTag)?;
+        let (&bump, rest) = rest.split_first().ok_or(size_error)?;
+        let (accumulator, rest) = rest.split_first_chunk().ok_or(size_error)?;
+        let bitmap = rest.first_chunk().ok_or(size_error)?;
+
+        Ok(Self {
+            bump,
+            accumulator: *accumulator,
+            bitmap: *bitmap,
+        })
+    }
+
+    /// Check if a sequence number is marked as used in this bucket.
+    pub fn is_used(&self, sequence: u64) -> bool {
+        let (byte_index, bit_offset) = bit_position(sequence);
+        self.bitmap[byte_index] & (1 << bit_offset) != 0
+    }
+}
+
+/// A bitmap account that failed to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeDiagnostic {
+    /// Address of the offending account.
+    pub address: Pubkey,
+    /// Why decoding failed.
+    pub error: DecodeError,
+}
+
+/// Streaming decoder for fetched bitmap accounts.
+///
+/// Accounts that fail to decode are skipped and recorded as diagnostics
+/// rather than aborting the whole sync, so data-integrity issues surface
+/// in a single report at the end.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut decoder = BitmapDecoder::new();
+/// for (address, account) in fetched {
+///     if let Some(bitmap) = decoder.decode(&address, &account.data) {
+///         // ...
+///     }
+/// }
+/// for diagnostic in decoder.diagnostics() {
+///     eprintln!("{}: {:?}", diagnostic.address, diagnostic.error);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct BitmapDecoder {
+    diagnostics: Vec<DecodeDiagnostic>,
+}
+
+impl BitmapDecoder {
+    /// Create a decoder with an empty diagnostics report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode one account, recording a diagnostic if it is malformed.
+    pub fn decode(&mut self, address: &Pubkey, data: &[u8]) -> Option<DecodedBitmap> {
+        match DecodedBitmap::try_decode(data) {
+            Ok(bitmap) => Some(bitmap),
+            Err(error) => {
+                self.diagnostics.push(DecodeDiagnostic {
+                    address: *address,
+                    error,
+                });
+                None
+            }
+        }
+    }
+
+    /// Diagnostics collected so far.
+    pub fn diagnostics(&self) -> &[DecodeDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Consume the decoder, returning the collected diagnostics.
+    pub fn into_diagnostics(self) -> Vec<DecodeDiagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Whether `sequence` is marked in fetched bucket data, without copying it.
+///
+/// Composes with any RPC client: pass the data of the account at
+/// [`derive_bitmap_pda`] for the sequence. Returns `None` if the data isn't
+/// a current-layout bucket (wrong size or tag); a bucket that doesn't exist
+/// has no used sequences, so treat a missing account as `Some(false)`.
+pub fn is_used_from_account(account_data: &[u8], sequence: u64) -> Option<bool> {
+    if account_data.len() != BITMAP_ACCOUNT_SIZE {
+        return None;
+    }
+    let account = crate::state::BitmapAccountRef::from_slice(account_data)?;
+    crate::state::check_tag(account.tag).ok()?;
+    Some(account.is_used(sequence))
+}
+
+/// Whether a fetched account is an initialized bucket: owned by
+/// [`PROGRAM_ID`] and exactly [`BITMAP_ACCOUNT_SIZE`] bytes. `None` (account
+/// not found) is `false`.
+pub fn bitmap_exists(account: Option<&Account>) -> bool {
+    account.is_some_and(|account| {
+        account.owner == PROGRAM_ID && account.data.len() == BITMAP_ACCOUNT_SIZE
+    })
+}
+
+/// Local cache of fetched bucket bitmaps.
+///
+/// Verification tools checking many candidate sequences can answer `is_used`
+/// locally and only fetch buckets the cache hasn't seen. Buckets change as
+/// sequences are marked, so entries expire after an optional TTL and can be
+/// invalidated manually. A bucket that doesn't exist on-chain can be cached
+/// as an all-zero [`DecodedBitmap`].
+#[derive(Debug, Default)]
+pub struct UsedCache {
+    ttl: Option<std::time::Duration>,
+    buckets: std::collections::HashMap<(Pubkey, Vec<u8>, u64), (DecodedBitmap, std::time::Instant)>,
+}
+
+impl UsedCache {
+    /// Create a cache whose entries never expire.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a cache whose entries expire `ttl` after insertion.
+    pub fn with_ttl(ttl: std::time::Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            ..Self::default()
+        }
+    }
+
+    /// Record a fetched bucket.
+    pub fn insert(
+        &mut self,
+        authority: &Pubkey,
+        namespace: &[u8],
+        bucket_index: u64,
+        bitmap: DecodedBitmap,
+    ) {
+        self.buckets.insert(
+            (*authority, namespace.to_vec(), bucket_index),
+            (bitmap, std::time::Instant::now()),
+        );
+    }
+
+    /// Answer from the cache, or `None` if the bucket must be (re)fetched.
+    pub fn is_used(&self, authority: &Pubkey, namespace: &[u8], sequence: u64) -> Option<bool> {
+        let key = (*authority, namespace.to_vec(), bucket_of(sequence));
+        let (bitmap, inserted_at) = self.buckets.get(&key)?;
+        if self.ttl.is_some_and(|ttl| inserted_at.elapsed() >= ttl) {
+            return None;
+        }
+        Some(bitmap.is_used(sequence))
+    }
+
+    /// Bucket indices among `sequences` that are missing or expired, in order.
+    pub fn missing_buckets(
+        &self,
+        authority: &Pubkey,
+        namespace: &[u8],
+        sequences: &[u64],
+    ) -> Vec<u64> {
+        let buckets: std::collections::BTreeSet<u64> = sequences
+            .iter()
+            .filter(|sequence| self.is_used(authority, namespace, **sequence).is_none())
+            .map(|&sequence| bucket_of(sequence))
+            .collect();
+        buckets.into_iter().collect()
+    }
+
+    /// Drop a single cached bucket.
+    pub fn invalidate(&mut self, authority: &Pubkey, namespace: &[u8], bucket_index: u64) {
+        self.buckets
+            .remove(&(*authority, namespace.to_vec(), bucket_index));
+    }
+
+    /// Drop all cached buckets.
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+    }
+}
+
+/// Maximum accounts per `getMultipleAccounts` RPC call.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Aggregate consumption of a namespace's sequence space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConsumptionStats {
+    /// Bucket accounts that exist (program-owned and decodable).
+    pub buckets_created: u64,
+    /// Used sequences across all created buckets.
+    pub total_used: u64,
+    /// Highest used sequence, if any.
+    pub highest_used: Option<u64>,
+    /// `total_used / (buckets_created * BITS_PER_BUCKET)`, or 0 with no buckets.
+    pub density: f64,
+}
+
+/// Aggregate [`ConsumptionStats`] over buckets `0..=max_bucket`.
+///
+/// `fetch` is called with at most [`MAX_MULTIPLE_ACCOUNTS`] addresses at a
+/// time and must return one entry per address, `None` for missing accounts
+/// (e.g. a thin wrapper over `RpcClient::get_multiple_accounts`). Accounts
+/// not owned by the program or not decodable are not counted as buckets.
+pub fn consumption_stats<E>(
+    authority: &Pubkey,
+    namespace: &[u8],
+    max_bucket: u64,
+    mut fetch: impl FnMut(&[Pubkey]) -> Result<Vec<Option<Account>>, E>,
+) -> Result<ConsumptionStats, E> {
+    let mut buckets_created = 0u64;
+    let mut total_used = 0u64;
+    let mut highest_used = None;
+
+    let mut next = 0u64;
+    while next <= max_bucket {
+        let end = max_bucket.min(next.saturating_add(MAX_MULTIPLE_ACCOUNTS as u64 - 1));
+        let addresses: Vec<Pubkey> = (next..=end)
+            .map(|bucket_index| derive_bucket_pda(authority, namespace, bucket_index).0)
+            .collect();
+        let accounts = fetch(&addresses)?;
+
+        for (bucket_index, account) in (next..=end).zip(accounts) {
+            let Some(account) = account.filter(|account| account.owner == PROGRAM_ID) else {
+                continue;
+            };
+            let Ok(decoded) = DecodedBitmap::try_decode(&account.data) else {
+                continue;
+            };
+            buckets_created += 1;
+            total_used += decoded.count_used();
+            if let Some((start, len)) = decoded.to_rle(bucket_index).last() {
+                highest_used = Some(start + len - 1);
+            }
+        }
+
+        next = end + 1;
+        if end == u64::MAX {
+            break;
+        }
+    }
+
+    let density = if buckets_created == 0 {
+        0.0
+    } else {
+        total_used as f64 / (buckets_created as f64 * BITS_PER_BUCKET as f64)
+    };
+
+    Ok(ConsumptionStats {
+        buckets_created,
+        total_used,
+        highest_used,
+        density,
+    })
+}
+
+/// Lamports a bitmap PDA needs to be rent-exempt under `rent`.
+///
+/// What [`CreateBitmap`] and the lazy creation in [`MarkUsed`] take from the
+/// payer for a new bucket.
+pub fn rent_for_bitmap(rent: &Rent) -> u64 {
+    rent.minimum_balance(BITMAP_ACCOUNT_SIZE)
+}
+
+/// Extra lamports one account needs to stay rent-exempt when resized from
+/// `old_size` to `new_size` bytes (0 if it shrinks).
+///
+/// Uses the default rent parameters, which every cluster currently runs with.
+pub fn rent_delta_for_upgrade(old_size: usize, new_size: usize) -> u64 {
+    let rent = Rent::default();
+    rent.minimum_balance(new_size)
+        .saturating_sub(rent.minimum_balance(old_size))
+}
+
+/// Total [`rent_delta_for_upgrade`] across buckets with the given current
+/// sizes, for budgeting a layout migration.
+pub fn total_rent_delta_for_upgrade(
+    old_sizes: impl IntoIterator<Item = usize>,
+    new_size: usize,
+) -> u64 {
+    old_sizes
+        .into_iter()
+        .map(|old_size| rent_delta_for_upgrade(old_size, new_size))
+        .sum()
+}
+
+/// Mismatch between the client's compiled layout and a deployed bitmap account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BucketSizeMismatch {
+    /// The account is not owned by the expected program.
+    WrongOwner { owner: Pubkey, expected: Pubkey },
+    /// The deployed bitmap size differs from the compiled `BITMAP_BYTES`.
+    BitmapBytes { deployed: usize, compiled: usize },
+}
+
+impl std::fmt::Display for BucketSizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongOwner { owner, expected } => write!(
+                f,
+                "bitmap account is owned by {owner}, expected NoReplay program {expected}"
+            ),
+            Self::BitmapBytes { deployed, compiled } => write!(
+                f,
+                "deployed program uses {deployed}-byte bitmaps but this client was compiled \
+                 with BITMAP_BYTES = {compiled}; derived bucket PDAs will not match"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BucketSizeMismatch {}
+
+/// Check that a fetched bitmap account matches the client's compiled layout.
+///
+/// A client compiled with a different `BITMAP_BYTES` derives different bucket
+/// indices and therefore different PDAs, silently writing to the wrong
+/// accounts. Call this once at startup against any known bitmap account
+/// (fetched with whatever RPC client you already use) to fail fast instead.
+pub fn assert_bucket_size(
+    account: &Account,
+    program_id: &Pubkey,
+) -> Result<(), BucketSizeMismatch> {
+    if account.owner != *program_id {
+        return Err(BucketSizeMismatch::WrongOwner {
+            owner: account.owner,
+            expected: *program_id,
+        });
+    }
+
+    let deployed = account
+        .data
+        .len()
+        .saturating_sub(BITMAP_ACCOUNT_SIZE - BITMAP_BYTES);
+    if deployed != BITMAP_BYTES {
+        return Err(BucketSizeMismatch::BitmapBytes {
+            deployed,
+            compiled: BITMAP_BYTES,
+        });
+    }
+
+    Ok(())
+}
+
+/// Health of a bucket account as seen by [`detect_squatted_bucket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquatStatus {
+    /// Program-owned, correctly sized, and storing the canonical bump.
+    Healthy,
+    /// Owned by a program other than NoReplay (or the system program).
+    ForeignOwner,
+    /// Program-owned but storing a bump other than the canonical one.
+    NonCanonicalBump,
+    /// Program-owned but not `BITMAP_ACCOUNT_SIZE` bytes long.
+    Malformed,
+    /// Not created yet (missing, or a system-owned pre-funded account).
+    Absent,
+}
+
+/// Compare a fetched bucket account against its canonical derivation.
+///
+/// The program only ever stores the bump returned by `find_program_address`,
+/// so anything other than [`SquatStatus::Healthy`] or [`SquatStatus::Absent`]
+/// indicates squatting or corruption and is worth alerting on. Pass the
+/// account fetched (with whatever RPC client you already use) from the
+/// address returned by [`derive_bitmap_pda`], or `None` if it doesn't exist.
+pub fn detect_squatted_bucket(
+    account: Option<&Account>,
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> SquatStatus {
+    let Some(account) = account else {
+        return SquatStatus::Absent;
+    };
+
+    if account.owner == system_program::ID && account.data.is_empty() {
+        // Pre-funded but not created; MarkUsed will take it over
+        return SquatStatus::Absent;
+    }
+    if account.owner != PROGRAM_ID {
+        return SquatStatus::ForeignOwner;
+    }
+
+    let Ok(decoded) = DecodedBitmap::try_decode(&account.data) else {
+        return SquatStatus::Malformed;
+    };
+    let (_, canonical_bump) = derive_bitmap_pda(authority, namespace, sequence);
+    if decoded.bump != canonical_bump {
+        return SquatStatus::NonCanonicalBump;
+    }
+
+    SquatStatus::Healthy
+}
+
+/// Pre-flight report for prefunding a bucket range, from [`validate_prefund_plan`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrefundPlan {
+    /// Buckets to create: missing, or system-owned and only pre-funded.
+    pub to_create: Vec<Pubkey>,
+    /// Healthy buckets that already exist; skip these.
+    pub existing: Vec<Pubkey>,
+    /// Buckets that are neither, with what's wrong. Alert on these; creating
+    /// over them fails.
+    pub anomalies: Vec<(Pubkey, SquatStatus)>,
+}
+
+/// Classify every bucket in `buckets` before running a prefund.
+///
+/// `fetch` loads accounts for up to [`MAX_MULTIPLE_ACCOUNTS`] addresses at a
+/// time, returning `None` for missing ones, e.g. a wrapper around RPC
+/// `getMultipleAccounts`. Each account is classified with
+/// [`detect_squatted_bucket`]. Addresses keep bucket order within each list.
+pub fn validate_prefund_plan<E>(
+    authority: &Pubkey,
+    namespace: &[u8],
+    buckets: std::ops::Range<u64>,
+    mut fetch: impl FnMut(&[Pubkey]) -> Result<Vec<Option<Account>>, E>,
+) -> Result<PrefundPlan, E> {
+    let mut plan = PrefundPlan::default();
+
+    let bucket_indices: Vec<u64> = buckets.collect();
+    for chunk in bucket_indices.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let addresses: Vec<Pubkey> = chunk
+            .iter()
+            .map(|&bucket_index| derive_bucket_pda(authority, namespace, bucket_index).0)
+            .collect();
+        let accounts = fetch(&addresses)?;
+
+        for ((&bucket_index, address), account) in chunk.iter().zip(addresses).zip(accounts) {
+            let sequence = bucket_index * BITS_PER_BUCKET;
+            match detect_squatted_bucket(account.as_ref(), authority, namespace, sequence) {
+                SquatStatus::Absent => plan.to_create.push(address),
+                SquatStatus::Healthy => plan.existing.push(address),
+                anomaly => plan.anomalies.push((address, anomaly)),
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+// =============================================================================
+// Error classification
+// =============================================================================
+
+/// How a submitter should react to a failed transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Transient failure; resubmitting (with a fresh blockhash) may succeed.
+    Retryable,
+    /// The sequence was already marked. Retrying will never succeed.
+    PermanentReplay,
+    /// Any other permanent failure (insufficient funds, bad accounts, ...).
+    PermanentOther,
+}
+
+/// Classify a transaction error returned when submitting NoReplay instructions.
+///
+/// Replay is detected by the program's `SequenceAlreadyUsed` custom error. If
+/// the transaction contains instructions from other programs, check the
+/// instruction index of the error before trusting `PermanentReplay`.
+///
+/// Transport-level failures (timeouts, dropped connections) never produce a
+/// `TransactionError` and should be treated as retryable by the caller.
+pub fn classify_send_error(err: &TransactionError) -> ErrorClass {
+    match err {
+        TransactionError::InstructionError(_, InstructionError::Custom(code))
+            if *code == NoReplayError::SequenceAlreadyUsed as u32 =>
+        {
+            ErrorClass::PermanentReplay
+        }
+        TransactionError::BlockhashNotFound
+        | TransactionError::AccountInUse
+        | TransactionError::ClusterMaintenance
+        | TransactionError::WouldExceedMaxBlockCostLimit
+        | TransactionError::WouldExceedMaxAccountCostLimit
+        | TransactionError::WouldExceedMaxVoteCostLimit
+        | TransactionError::WouldExceedAccountDataBlockLimit => ErrorClass::Retryable,
+        _ => ErrorClass::PermanentOther,
+    }
+}
+
+impl NoReplayError {
+    /// Map a `ProgramError` returned by a NoReplay instruction (e.g. from
+    /// `invoke`) back to the program's error.
+    ///
+    /// Returns `None` for built-in errors and for custom codes the program
+    /// doesn't define.
+    pub fn from_program_error(e: &ProgramError) -> Option<Self> {
+        let ProgramError::Custom(code) = e else {
+            return None;
+        };
+        Some(match code {
+            0 => Self::MalformedBitmapAccount,
+            1 => Self::BucketIndexTooLarge,
+            2 => Self::BucketNotFullyUsed,
+            3 => Self::SequenceAlreadyUsed,
+            4 => Self::NamespaceTooLong,
+            5 => Self::InvalidBucket,
+            6 => Self::UnsupportedFormatVersion,
+            7 => Self::AccountSizeMismatch,
+            8 => Self::AuthorityMismatch,
+            9 => Self::SequenceTooOld,
+            10 => Self::BitmapPdaMismatch,
+            11 => Self::ReservedNamespace,
+            _ => return None,
+        })
+    }
+}
+
+// Re-export useful constants for clients
+pub use crate::delegation::DELEGATION_DOMAIN;
+pub use crate::error::NoReplayError;
+pub use crate::instruction::{
+    CHECK_USED, CLOSE_BITMAP, CREATE_BITMAP, INIT_CONFIG, MARK_ABOVE, MARK_USED, MARK_USED_BATCH,
+    MARK_USED_COMMITTED, MARK_USED_DELEGATED, MARK_USED_DOMAIN, MARK_USED_IDEMPOTENT,
+    MARK_USED_RANGE, MARK_USED_U128, MARK_WINDOWED, MAX_MULTI_BUCKET_ACCOUNTS, MIGRATE_BUCKET,
+    MULTI_BUCKET_ENTRY_SIZE, MULTI_BUCKET_MARK, RANGE_ANY_USED, RETURN_STATUS_FLAG, SET_AUTHORITY,
+    VERIFY_BUCKET,
+};
+use crate::pda::split_namespace;
+pub use crate::pda::{
+    is_reserved_namespace, DerivePdaError, COMMITTED_SEED_TAG, CONFIG_SEED_TAG, DOMAIN_SEED_TAG,
+    RESERVED_SEED_TAGS, U128_SEED_TAG, WATERMARK_SEED_TAG, WINDOW_SEED_TAG,
+};
+pub use crate::state::{
+    bit_of, bit_position, bucket_of, bucket_of_u128, next_accumulator, BucketStatus,
+    ACCUMULATOR_BYTES, BITMAP_ACCOUNT_SIZE, BITMAP_BYTES, BITMAP_FORMAT_VERSION, BITMAP_LAYOUT,
+    BITMAP_TAG, BITMAP_TAG_LEN, BITS_PER_BUCKET, COMMITTED_ACCOUNT_SIZE, COMMITTED_LAYOUT,
+    COMMITTED_TAG, CONFIG_ACCOUNT_SIZE, CONFIG_LAYOUT, CONFIG_TAG, LEGACY_BITMAP_ACCOUNT_SIZE,
+    MAX_BUCKET_INDEX, WATERMARK_ACCOUNT_SIZE, WATERMARK_LAYOUT, WATERMARK_TAG, WINDOW_ACCOUNT_SIZE,
+    WINDOW_BITS, WINDOW_LAYOUT, WINDOW_TAG,
+};
+
+/// `memcmp` filter selecting bitmap accounts of the current format in
+/// `getProgramAccounts`: compare [`BITMAP_TAG`] at this offset.
+///
+/// ```ignore
+/// RpcFilterType::Memcmp(Memcmp::new_raw_bytes(BITMAP_MEMCMP_OFFSET, BITMAP_TAG.to_vec()))
+/// ```
+pub const BITMAP_MEMCMP_OFFSET: usize = 0;
+pub use crate::{MAX_NAMESPACE_LEN, MIN_NAMESPACE_LEN};
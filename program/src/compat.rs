@@ -0,0 +1,18 @@
+//! Conversions between the `solana_pubkey` 3.x `Pubkey` used by newer
+//! crates (mollusk, solana-instruction, solana-account) and the
+//! `solana_sdk` `Pubkey` used by the [`client`](crate::client) module.
+//!
+//! Both are 32 raw bytes in the same order; these helpers just save every
+//! integrator from writing the byte copy themselves.
+
+/// Convert a `solana_pubkey::Pubkey` to a `solana_sdk::pubkey::Pubkey`.
+#[inline]
+pub fn to_sdk(pubkey: &solana_pubkey::Pubkey) -> solana_sdk::pubkey::Pubkey {
+    solana_sdk::pubkey::Pubkey::new_from_array(pubkey.to_bytes())
+}
+
+/// Convert a `solana_sdk::pubkey::Pubkey` to a `solana_pubkey::Pubkey`.
+#[inline]
+pub fn from_sdk(pubkey: &solana_sdk::pubkey::Pubkey) -> solana_pubkey::Pubkey {
+    solana_pubkey::Pubkey::new_from_array(pubkey.to_bytes())
+}
@@ -0,0 +1,162 @@
+//! Ed25519 authorization checks for delegated marking.
+//!
+//! The authority signs [`DELEGATION_DOMAIN`] `|| program_id || authority ||
+//! namespace_len (u16 LE) || namespace || sequence (u64 LE)` off-chain. The relayer submits
+//! an ed25519 precompile instruction carrying that signature immediately
+//! before MarkUsedDelegated. The runtime verifies the signature before the
+//! program runs; this module only checks, via the instructions sysvar, that
+//! the verified public key and message are the ones this instruction needs.
+
+use pinocchio::error::ProgramError;
+
+/// Domain separator prefixed to every delegated authorization message, so the
+/// signature can't be lifted from (or into) any other signing context.
+pub const DELEGATION_DOMAIN: &[u8] = b"noreplay:mark_used_delegated:v2";
+
+/// Ed25519 precompile program ID (`Ed25519SigVerify111111111111111111111111111`).
+pub const ED25519_PROGRAM_ID: [u8; 32] = [
+    3, 125, 70, 214, 124, 147, 251, 190, 18, 249, 66, 143, 131, 141, 64, 255, 5, 112, 116, 73, 39,
+    244, 138, 100, 252, 202, 112, 68, 128, 0, 0, 0,
+];
+
+/// Instructions sysvar ID (`Sysvar1nstructions1111111111111111111111111`).
+pub const INSTRUCTIONS_SYSVAR_ID: [u8; 32] = [
+    6, 167, 213, 23, 24, 123, 209, 102, 53, 218, 212, 4, 85, 253, 194, 192, 193, 36, 198, 143, 33,
+    86, 117, 165, 219, 186, 203, 95, 8, 0, 0, 0,
+];
+
+/// Ed25519 instruction header: `[num_signatures: u8][padding: u8]`.
+const ED25519_HEADER_LEN: usize = 2;
+
+/// Ed25519 per-signature offsets: seven u16 LE fields.
+const ED25519_OFFSETS_LEN: usize = 14;
+
+/// Instruction index meaning "this instruction" in ed25519 offsets.
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+#[inline]
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Index of the currently executing instruction (last two bytes of the sysvar).
+fn current_index(sysvar: &[u8]) -> Option<u16> {
+    read_u16(sysvar, sysvar.len().checked_sub(2)?)
+}
+
+/// Load `(program_id, data)` of the instruction at `index` from the
+/// serialized instructions sysvar.
+///
+/// Layout: `[num: u16][offsets: u16; num]` then per instruction
+/// `[num_accounts: u16][(flags: u8, pubkey: 32); num_accounts]
+/// [program_id: 32][data_len: u16][data]`.
+fn load_instruction(sysvar: &[u8], index: u16) -> Option<(&[u8], &[u8])> {
+    let num_instructions = read_u16(sysvar, 0)?;
+    if index >= num_instructions {
+        return None;
+    }
+
+    let mut cursor = read_u16(sysvar, 2 + 2 * index as usize)? as usize;
+    let num_accounts = read_u16(sysvar, cursor)? as usize;
+    cursor += 2 + num_accounts * 33;
+
+    let program_id = sysvar.get(cursor..cursor + 32)?;
+    cursor += 32;
+
+    let data_len = read_u16(sysvar, cursor)? as usize;
+    cursor += 2;
+    let data = sysvar.get(cursor..cursor + data_len)?;
+
+    Some((program_id, data))
+}
+
+/// Check that `message` is exactly the authorization for
+/// `(authority, namespace, sequence)`.
+fn is_authorization(
+    message: &[u8],
+    program_id: &[u8],
+    authority: &[u8],
+    namespace: &[u8],
+    sequence: u64,
+) -> bool {
+    let Some(rest) = message.strip_prefix(DELEGATION_DOMAIN) else {
+        return false;
+    };
+    let Some(rest) = rest.strip_prefix(program_id) else {
+        return false;
+    };
+    let Some(rest) = rest.strip_prefix(authority) else {
+        return false;
+    };
+    let Some(rest) = rest.strip_prefix(&(namespace.len() as u16).to_le_bytes()[..]) else {
+        return false;
+    };
+    let Some(rest) = rest.strip_prefix(namespace) else {
+        return false;
+    };
+    rest == sequence.to_le_bytes()
+}
+
+/// Verify that the instruction preceding the current one is an ed25519
+/// precompile instruction in which `signer` signed the authorization for
+/// `(authority, namespace, sequence)`.
+///
+/// `authority` is the one in the bucket's seeds. It is part of the message,
+/// so a `signer` holding the marking rights of several authorities (e.g. as
+/// their config's current authority) authorizes a mark for one of them only.
+///
+/// Only a single signature whose public key, signature and message all live
+/// in the precompile instruction's own data is accepted. Offsets pointing into
+/// other instructions would let the verified bytes differ from those read here.
+pub fn verify_delegation(
+    sysvar: &[u8],
+    program_id: &[u8],
+    signer: &[u8],
+    authority: &[u8],
+    namespace: &[u8],
+    sequence: u64,
+) -> Result<(), ProgramError> {
+    let current = current_index(sysvar).ok_or(ProgramError::InvalidAccountData)?;
+    let previous = current
+        .checked_sub(1)
+        .ok_or(ProgramError::MissingRequiredSignature)?;
+    let (ed25519_program, data) =
+        load_instruction(sysvar, previous).ok_or(ProgramError::InvalidAccountData)?;
+
+    if ed25519_program != ED25519_PROGRAM_ID {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data.len() < ED25519_HEADER_LEN + ED25519_OFFSETS_LEN || data[0] != 1 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let field = |i: usize| read_u16(data, ED25519_HEADER_LEN + 2 * i).unwrap();
+    let public_key_offset = field(2) as usize;
+    let message_offset = field(4) as usize;
+    let message_size = field(5) as usize;
+
+    // signature, public key and message instruction indices
+    if field(1) != CURRENT_INSTRUCTION
+        || field(3) != CURRENT_INSTRUCTION
+        || field(6) != CURRENT_INSTRUCTION
+    {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+
+    if public_key != signer
+        || !is_authorization(message, program_id, authority, namespace, sequence)
+    {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    Ok(())
+}
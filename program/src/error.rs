@@ -0,0 +1,66 @@
+use pinocchio::error::ProgramError;
+
+use crate::state::TagError;
+
+/// Program-specific errors, surfaced as `ProgramError::Custom(code)`.
+///
+/// Codes are stable: never renumber or reuse a variant's value.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoReplayError {
+    /// A program-owned bitmap account is shorter than `BITMAP_ACCOUNT_SIZE`
+    /// or doesn't start with `BITMAP_MAGIC`. Accounts created by this
+    /// program are always well-formed, so this indicates corruption or an
+    /// account from a pre-tag build rather than a creation failure.
+    MalformedBitmapAccount = 0,
+    /// The sequence's bucket index exceeds `MAX_BUCKET_INDEX` (only with the
+    /// `max-bucket` feature).
+    BucketIndexTooLarge = 1,
+    /// CloseBitmap on a bucket with unused sequences. Closing it would let
+    /// those sequences be marked again in a recreated bucket.
+    BucketNotFullyUsed = 2,
+    /// The sequence was already marked: a replay. Also returned when any
+    /// sequence of a batch or range was already marked.
+    SequenceAlreadyUsed = 3,
+    /// `namespace_len` in the instruction data exceeds `MAX_NAMESPACE_LEN`.
+    NamespaceTooLong = 4,
+    /// A batch or range spans more than the single bucket it was sent for.
+    InvalidBucket = 5,
+    /// The bitmap account's tag carries a format version this build can't
+    /// read.
+    UnsupportedFormatVersion = 6,
+    /// An existing program-owned bitmap account is larger than
+    /// `BITMAP_ACCOUNT_SIZE`. Only a botched layout migration produces one,
+    /// and its extra bytes would otherwise be silently ignored. Also returned
+    /// by MigrateBucket for a size it has no migration from.
+    AccountSizeMismatch = 7,
+    /// A mark passing a namespace config was signed by a key other than the
+    /// config's `current_authority`, or SetAuthority was.
+    AuthorityMismatch = 8,
+    /// MarkWindowed for a sequence that has slid out of the namespace's
+    /// window. It may or may not have been used; it can't be marked.
+    SequenceTooOld = 9,
+    /// The passed bitmap account isn't the PDA derived from the authority,
+    /// namespace and sequence. A failure of the derivation itself is still
+    /// `ProgramError::InvalidSeeds`.
+    BitmapPdaMismatch = 10,
+    /// A namespace longer than two seed chunks begins with a seed tag, so its
+    /// buckets could share an address with a domained bucket or another
+    /// account kind. See `pda::is_reserved_namespace`.
+    ReservedNamespace = 11,
+}
+
+impl From<NoReplayError> for ProgramError {
+    fn from(e: NoReplayError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl From<TagError> for NoReplayError {
+    fn from(e: TagError) -> Self {
+        match e {
+            TagError::NotABitmap => NoReplayError::MalformedBitmapAccount,
+            TagError::UnsupportedVersion(_) => NoReplayError::UnsupportedFormatVersion,
+        }
+    }
+}
@@ -2,11 +2,120 @@
 
 use pinocchio::{error::ProgramError, AccountView};
 
+use crate::delegation::INSTRUCTIONS_SYSVAR_ID;
+use crate::error::NoReplayError;
+use crate::pda::is_reserved_namespace;
+use crate::state::{bit_of, bucket_of, BITS_PER_BUCKET};
 use crate::MAX_NAMESPACE_LEN;
 
 /// Instruction discriminators.
-pub const CREATE_BITMAP: u8 = 0;
-pub const MARK_USED: u8 = 1;
+///
+/// New instructions claim their byte here, so two features can't both take
+/// the same value: duplicate discriminants don't compile, and dispatch must
+/// handle every variant.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Discriminator {
+    CreateBitmap = 0,
+    MarkUsed = 1,
+    CheckUsed = 2,
+    MarkUsedBatch = 3,
+    CloseBitmap = 4,
+    MarkAbove = 5,
+    MarkUsedIdempotent = 6,
+    MarkUsedDelegated = 7,
+    MultiBucketMark = 8,
+    VerifyBucket = 9,
+    MarkUsedRange = 10,
+    RangeAnyUsed = 11,
+    MarkUsedDomain = 12,
+    MarkUsedU128 = 13,
+    InitConfig = 14,
+    SetAuthority = 15,
+    MarkWindowed = 16,
+    MigrateBucket = 17,
+    MarkUsedCommitted = 18,
+}
+
+impl Discriminator {
+    /// Every defined discriminator, in ascending order.
+    pub const ALL: [Self; 19] = [
+        Self::CreateBitmap,
+        Self::MarkUsed,
+        Self::CheckUsed,
+        Self::MarkUsedBatch,
+        Self::CloseBitmap,
+        Self::MarkAbove,
+        Self::MarkUsedIdempotent,
+        Self::MarkUsedDelegated,
+        Self::MultiBucketMark,
+        Self::VerifyBucket,
+        Self::MarkUsedRange,
+        Self::RangeAnyUsed,
+        Self::MarkUsedDomain,
+        Self::MarkUsedU128,
+        Self::InitConfig,
+        Self::SetAuthority,
+        Self::MarkWindowed,
+        Self::MigrateBucket,
+        Self::MarkUsedCommitted,
+    ];
+}
+
+impl TryFrom<u8> for Discriminator {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            CREATE_BITMAP => Ok(Self::CreateBitmap),
+            MARK_USED => Ok(Self::MarkUsed),
+            CHECK_USED => Ok(Self::CheckUsed),
+            MARK_USED_BATCH => Ok(Self::MarkUsedBatch),
+            CLOSE_BITMAP => Ok(Self::CloseBitmap),
+            MARK_ABOVE => Ok(Self::MarkAbove),
+            MARK_USED_IDEMPOTENT => Ok(Self::MarkUsedIdempotent),
+            MARK_USED_DELEGATED => Ok(Self::MarkUsedDelegated),
+            MULTI_BUCKET_MARK => Ok(Self::MultiBucketMark),
+            VERIFY_BUCKET => Ok(Self::VerifyBucket),
+            MARK_USED_RANGE => Ok(Self::MarkUsedRange),
+            RANGE_ANY_USED => Ok(Self::RangeAnyUsed),
+            MARK_USED_DOMAIN => Ok(Self::MarkUsedDomain),
+            MARK_USED_U128 => Ok(Self::MarkUsedU128),
+            INIT_CONFIG => Ok(Self::InitConfig),
+            SET_AUTHORITY => Ok(Self::SetAuthority),
+            MARK_WINDOWED => Ok(Self::MarkWindowed),
+            MIGRATE_BUCKET => Ok(Self::MigrateBucket),
+            MARK_USED_COMMITTED => Ok(Self::MarkUsedCommitted),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+pub const CREATE_BITMAP: u8 = Discriminator::CreateBitmap as u8;
+pub const MARK_USED: u8 = Discriminator::MarkUsed as u8;
+pub const CHECK_USED: u8 = Discriminator::CheckUsed as u8;
+pub const MARK_USED_BATCH: u8 = Discriminator::MarkUsedBatch as u8;
+pub const CLOSE_BITMAP: u8 = Discriminator::CloseBitmap as u8;
+pub const MARK_ABOVE: u8 = Discriminator::MarkAbove as u8;
+pub const MARK_USED_IDEMPOTENT: u8 = Discriminator::MarkUsedIdempotent as u8;
+pub const MARK_USED_DELEGATED: u8 = Discriminator::MarkUsedDelegated as u8;
+pub const MULTI_BUCKET_MARK: u8 = Discriminator::MultiBucketMark as u8;
+pub const VERIFY_BUCKET: u8 = Discriminator::VerifyBucket as u8;
+pub const MARK_USED_RANGE: u8 = Discriminator::MarkUsedRange as u8;
+pub const RANGE_ANY_USED: u8 = Discriminator::RangeAnyUsed as u8;
+pub const MARK_USED_DOMAIN: u8 = Discriminator::MarkUsedDomain as u8;
+pub const MARK_USED_U128: u8 = Discriminator::MarkUsedU128 as u8;
+pub const INIT_CONFIG: u8 = Discriminator::InitConfig as u8;
+pub const SET_AUTHORITY: u8 = Discriminator::SetAuthority as u8;
+pub const MARK_WINDOWED: u8 = Discriminator::MarkWindowed as u8;
+pub const MIGRATE_BUCKET: u8 = Discriminator::MigrateBucket as u8;
+pub const MARK_USED_COMMITTED: u8 = Discriminator::MarkUsedCommitted as u8;
+
+/// Maximum bitmap PDAs passed to a single MultiBucketMark instruction.
+///
+/// Keeps the instruction (plus payer, authority and system program) well
+/// inside the per-transaction account limit.
+pub const MAX_MULTI_BUCKET_ACCOUNTS: usize = 16;
 
 // =============================================================================
 // CreateBitmap
@@ -18,18 +127,19 @@ pub const MARK_USED: u8 = 1;
 /// 0. `[writable, signer]` payer - Pays for PDA creation
 /// 1. `[]` authority - Used for PDA derivation (does NOT need to sign)
 /// 2. `[writable]` bitmap_pda - PDA to create
-/// 3. `[]` system_program - System program (implicit, not stored)
+/// 3. `[]` system_program - System program
 pub struct CreateBitmapAccounts<'a> {
     pub payer: &'a AccountView,
     pub authority: &'a AccountView,
     pub bitmap_pda: &'a AccountView,
+    pub system_program: &'a AccountView,
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for CreateBitmapAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [payer, authority, bitmap_pda, _system_program, ..] = accounts else {
+        let [payer, authority, bitmap_pda, system_program, ..] = accounts else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
@@ -44,13 +154,39 @@ impl<'a> TryFrom<&'a [AccountView]> for CreateBitmapAccounts<'a> {
             payer,
             authority,
             bitmap_pda,
+            system_program,
         })
     }
 }
 
+/// Validate the namespace of `data` (starting at its `namespace_len`
+/// prefix) before any slicing.
+///
+/// Over [`MAX_NAMESPACE_LEN`] fails with `NamespaceTooLong`, and a reserved
+/// namespace (see [`is_reserved_namespace`]) with `ReservedNamespace`. With
+/// the `strict-namespace` feature an empty namespace, usually one that failed
+/// to populate, fails with `InvalidInstructionData`. Truncated data is left
+/// to the caller's length check.
+#[inline(always)]
+fn check_namespace(data: &[u8], namespace_len: usize) -> Result<(), ProgramError> {
+    if namespace_len > MAX_NAMESPACE_LEN {
+        return Err(NoReplayError::NamespaceTooLong.into());
+    }
+    if cfg!(feature = "strict-namespace") && namespace_len == 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if data
+        .get(2..2 + namespace_len)
+        .is_some_and(is_reserved_namespace)
+    {
+        return Err(NoReplayError::ReservedNamespace.into());
+    }
+    Ok(())
+}
+
 /// Data for CreateBitmap and MarkUsed instructions.
 ///
-/// Format: `[namespace_len: u16 LE][namespace: 0-64 bytes][sequence: u64 LE]`
+/// Format: `[namespace_len: u16 LE][namespace: 0-96 bytes][sequence: u64 LE]`
 pub struct InstructionData<'a> {
     pub namespace: &'a [u8],
     pub sequence: u64,
@@ -67,9 +203,7 @@ impl<'a> TryFrom<&'a [u8]> for InstructionData<'a> {
 
         let namespace_len = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
 
-        if namespace_len > MAX_NAMESPACE_LEN {
-            return Err(ProgramError::InvalidInstructionData);
-        }
+        check_namespace(data, namespace_len)?;
 
         if data.len() != 2 + namespace_len + 8 {
             return Err(ProgramError::InvalidInstructionData);
@@ -113,38 +247,87 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for CreateBitmap<'a> {
 ///
 /// # Accounts
 /// 0. `[writable, signer]` payer - Pays for PDA creation if needed
-/// 1. `[signer]` authority - Owner of the sequence space (included in PDA seeds)
+/// 1. `[signer]` authority - Owner of the sequence space (included in PDA seeds);
+///    signs unless an existing config is passed
 /// 2. `[writable]` bitmap_pda - PDA storing the bitmap for this bucket
-/// 3. `[]` system_program - System program (implicit, not stored)
+/// 3. `[]` system_program - System program (optional if the bucket already
+///    exists and no config follows)
+/// 4. `[]` config - Optional namespace config PDA, see [`ConfigAuthorityAccounts`]
+/// 5. `[signer]` current_authority - Optional, see [`ConfigAuthorityAccounts`]
 pub struct MarkUsedAccounts<'a> {
     pub payer: &'a AccountView,
     pub authority: &'a AccountView,
     pub bitmap_pda: &'a AccountView,
+    /// Only needed to create the bucket; checked at creation time to be the
+    /// system program (`IncorrectProgramId` otherwise).
+    pub system_program: Option<&'a AccountView>,
+    pub config: Option<ConfigAuthorityAccounts<'a>>,
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for MarkUsedAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [payer, authority, bitmap_pda, _system_program, ..] = accounts else {
+        let [payer, authority, bitmap_pda, rest @ ..] = accounts else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
+        let (system_program, rest) = match rest.split_first() {
+            Some((system_program, rest)) => (Some(system_program), rest),
+            None => (None, rest),
+        };
 
         // Payer must sign
         if !payer.is_signer() {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        // Authority MUST sign to prevent DOS attacks where adversaries
-        // mark sequences as used for other users
-        if !authority.is_signer() {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+        // The authority, or the current authority of a passed config, must
+        // sign; the processor checks which so adversaries can't mark
+        // sequences as used for other users
 
         Ok(Self {
             payer,
             authority,
             bitmap_pda,
+            system_program,
+            config: ConfigAuthorityAccounts::from_trailing(rest),
+        })
+    }
+}
+
+/// Optional trailing byte on MarkUsed data asking the program to write a
+/// one-byte status (`1` = newly marked) via `set_return_data` on success.
+///
+/// Any other trailing value is rejected, leaving room for future flags.
+pub const RETURN_STATUS_FLAG: u8 = 1;
+
+/// Optional trailing accounts handing the right to mark in
+/// `(authority, namespace)` to a namespace config (see [`InitConfig`]).
+///
+/// When passed, the config is checked at its derived address. Once it
+/// exists, only its current authority may mark; until then the authority
+/// must sign, as it must when no config is passed.
+///
+/// # Accounts (after the instruction's fixed accounts)
+/// - `[]` config - Namespace config PDA for `(authority, namespace)`
+/// - `[signer]` current_authority - Key recorded in the config; only needed
+///   once the config exists
+#[derive(Clone, Copy)]
+pub struct ConfigAuthorityAccounts<'a> {
+    pub config: &'a AccountView,
+    pub current_authority: Option<&'a AccountView>,
+}
+
+impl<'a> ConfigAuthorityAccounts<'a> {
+    /// Take the config and current authority from the accounts following an
+    /// instruction's fixed ones, if any were passed.
+    fn from_trailing(accounts: &'a [AccountView]) -> Option<Self> {
+        let [config, rest @ ..] = accounts else {
+            return None;
+        };
+        Some(Self {
+            config,
+            current_authority: rest.first(),
         })
     }
 }
@@ -153,11 +336,61 @@ impl<'a> TryFrom<&'a [AccountView]> for MarkUsedAccounts<'a> {
 pub struct MarkUsed<'a> {
     pub accounts: MarkUsedAccounts<'a>,
     pub data: InstructionData<'a>,
+    /// Caller appended [`RETURN_STATUS_FLAG`]; absent in the minimal layout.
+    pub return_status: bool,
 }
 
 impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsed<'a> {
     type Error = ProgramError;
 
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let (data, return_status) = split_return_status_flag(data)?;
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+            return_status,
+        })
+    }
+}
+
+/// Strip the optional [`RETURN_STATUS_FLAG`] byte after the sequence.
+///
+/// Data of any other length is returned untouched for
+/// [`InstructionData`] to validate.
+fn split_return_status_flag(data: &[u8]) -> Result<(&[u8], bool), ProgramError> {
+    let Some(len_bytes) = data.get(0..2) else {
+        return Ok((data, false));
+    };
+    let namespace_len = u16::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+
+    match data.split_last() {
+        Some((&flag, rest)) if data.len() == 2 + namespace_len + 8 + 1 => {
+            if flag != RETURN_STATUS_FLAG {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            Ok((rest, true))
+        }
+        _ => Ok((data, false)),
+    }
+}
+
+// =============================================================================
+// MarkUsedIdempotent
+// =============================================================================
+
+/// MarkUsedIdempotent instruction - ensures a sequence number is marked as used.
+///
+/// Same accounts and data as MarkUsed, but succeeds when the sequence was
+/// already marked, returning `0` (`1` if newly marked) as return data. Suits
+/// reconciliation jobs and retries that may re-run.
+pub struct MarkUsedIdempotent<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsedIdempotent<'a> {
+    type Error = ProgramError;
+
     fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
         Ok(Self {
             accounts: MarkUsedAccounts::try_from(accounts)?,
@@ -165,3 +398,980 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsed<'a> {
         })
     }
 }
+
+// =============================================================================
+// MarkUsedDelegated
+// =============================================================================
+
+/// Accounts for MarkUsedDelegated instruction.
+///
+/// # Accounts
+/// 0. `[writable, signer]` payer - Relayer paying for PDA creation if needed
+/// 1. `[]` authority - Signed the authorization off-chain (does NOT sign the tx)
+/// 2. `[writable]` bitmap_pda - PDA storing the bitmap for this bucket
+/// 3. `[]` system_program - System program
+/// 4. `[]` instructions_sysvar - Used to inspect the preceding ed25519 instruction
+/// 5. `[]` config - Optional namespace config PDA; once it exists, its
+///    current authority signs the authorization instead of the authority
+pub struct MarkUsedDelegatedAccounts<'a> {
+    pub mark: MarkUsedAccounts<'a>,
+    pub instructions_sysvar: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for MarkUsedDelegatedAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [payer, authority, bitmap_pda, system_program, instructions_sysvar, rest @ ..] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // Payer must sign
+        if !payer.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // Authority's signature is checked via the ed25519 instruction instead
+
+        if instructions_sysvar.address().as_ref() != INSTRUCTIONS_SYSVAR_ID {
+            return Err(ProgramError::UnsupportedSysvar);
+        }
+
+        Ok(Self {
+            mark: MarkUsedAccounts {
+                payer,
+                authority,
+                bitmap_pda,
+                system_program: Some(system_program),
+                // Nobody signs for the authority in the transaction
+                config: rest.first().map(|config| ConfigAuthorityAccounts {
+                    config,
+                    current_authority: None,
+                }),
+            },
+            instructions_sysvar,
+        })
+    }
+}
+
+/// MarkUsedDelegated instruction - marks a sequence number as used on the
+/// authority's behalf, authorized by an ed25519 signature instead of a live
+/// transaction signature.
+///
+/// Same data as MarkUsed. Fails if the sequence was already marked.
+pub struct MarkUsedDelegated<'a> {
+    pub accounts: MarkUsedDelegatedAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsedDelegated<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: MarkUsedDelegatedAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MultiBucketMark
+// =============================================================================
+
+/// Accounts for MultiBucketMark instruction.
+///
+/// # Accounts
+/// 0. `[writable, signer]` payer - Pays for PDA creation if needed
+/// 1. `[signer]` authority - Owner of the sequence space (included in PDA seeds);
+///    signs unless an existing config is passed
+/// 2. `[]` system_program - System program
+/// 3. `[writable]` bitmap_pdas - 1 to `MAX_MULTI_BUCKET_ACCOUNTS` bucket PDAs,
+///    as many as the highest `pda_index` in the data needs
+/// 4. `[]` config - Optional, after the bitmap PDAs; see [`ConfigAuthorityAccounts`]
+/// 5. `[signer]` current_authority - Optional; see [`ConfigAuthorityAccounts`]
+pub struct MultiBucketMarkAccounts<'a> {
+    pub payer: &'a AccountView,
+    pub authority: &'a AccountView,
+    pub system_program: &'a AccountView,
+    pub bitmap_pdas: &'a [AccountView],
+    pub config: Option<ConfigAuthorityAccounts<'a>>,
+}
+
+impl<'a> MultiBucketMarkAccounts<'a> {
+    /// Parse the accounts for `bucket_count` bitmap PDAs.
+    fn parse(accounts: &'a [AccountView], bucket_count: usize) -> Result<Self, ProgramError> {
+        let [payer, authority, system_program, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if bucket_count > MAX_MULTI_BUCKET_ACCOUNTS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if rest.len() < bucket_count {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (bitmap_pdas, rest) = rest.split_at(bucket_count);
+
+        // Payer must sign
+        if !payer.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // Authority or config authority: checked by the processor, as for
+        // MarkUsed
+
+        Ok(Self {
+            payer,
+            authority,
+            system_program,
+            bitmap_pdas,
+            config: ConfigAuthorityAccounts::from_trailing(rest),
+        })
+    }
+}
+
+/// Size of one `(pda_index: u8, sequence: u64 LE)` entry.
+pub const MULTI_BUCKET_ENTRY_SIZE: usize = 9;
+
+/// Data for MultiBucketMark instruction.
+///
+/// Format: `[namespace_len: u16 LE][namespace: 0-96 bytes][count: u8]
+/// [(pda_index: u8, sequence: u64 LE); count]`, where `pda_index` selects
+/// the bitmap PDA (relative to the first one) holding the sequence's bucket.
+pub struct MultiBucketMarkData<'a> {
+    pub namespace: &'a [u8],
+    entries: &'a [u8],
+}
+
+impl<'a> MultiBucketMarkData<'a> {
+    /// Number of bitmap PDAs the entries refer to: one past the highest
+    /// `pda_index`.
+    pub fn bucket_count(&self) -> usize {
+        self.entries()
+            .map(|(pda_index, _)| pda_index + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Iterate `(pda_index, sequence)` entries in order.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, u64)> + 'a {
+        self.entries
+            .chunks_exact(MULTI_BUCKET_ENTRY_SIZE)
+            .map(|entry| {
+                (
+                    entry[0] as usize,
+                    u64::from_le_bytes(entry[1..].try_into().unwrap()),
+                )
+            })
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for MultiBucketMarkData<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // Minimum: 2 (namespace_len) + 0 (empty namespace) + 1 (count) = 3 bytes
+        if data.len() < 3 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace_len = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
+
+        check_namespace(data, namespace_len)?;
+
+        let Some(&count) = data.get(2 + namespace_len) else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+
+        let entries = &data[2 + namespace_len + 1..];
+        if count == 0 || entries.len() != count as usize * MULTI_BUCKET_ENTRY_SIZE {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            namespace: &data[2..2 + namespace_len],
+            entries,
+        })
+    }
+}
+
+/// MultiBucketMark instruction - marks sequences spanning several buckets.
+///
+/// Every entry is marked in the PDA it names, which must be the bucket for
+/// that sequence. Fails (atomically) if any sequence was already marked.
+pub struct MultiBucketMark<'a> {
+    pub accounts: MultiBucketMarkAccounts<'a>,
+    pub data: MultiBucketMarkData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MultiBucketMark<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let data = MultiBucketMarkData::try_from(data)?;
+        Ok(Self {
+            accounts: MultiBucketMarkAccounts::parse(accounts, data.bucket_count())?,
+            data,
+        })
+    }
+}
+
+// =============================================================================
+// VerifyBucket
+// =============================================================================
+
+/// Accounts for VerifyBucket instruction.
+///
+/// # Accounts
+/// 0. `[]` authority - Used for PDA derivation (does NOT need to sign)
+/// 1. `[]` bitmap_pda - Bucket account to check
+pub struct VerifyBucketAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub bitmap_pda: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for VerifyBucketAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, bitmap_pda, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        Ok(Self {
+            authority,
+            bitmap_pda,
+        })
+    }
+}
+
+/// VerifyBucket instruction - read-only health check of a bucket account.
+///
+/// Uses the same data as MarkUsed; `sequence` selects the bucket. Writes a
+/// [`BucketStatus`](crate::state::BucketStatus) byte as return data.
+pub struct VerifyBucket<'a> {
+    pub accounts: VerifyBucketAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for VerifyBucket<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: VerifyBucketAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// CheckUsed
+// =============================================================================
+
+/// Accounts for CheckUsed instruction.
+///
+/// # Accounts
+/// 0. `[]` authority - Used for PDA derivation (does NOT need to sign)
+/// 1. `[]` bitmap_pda - Bucket account to read (may not exist yet)
+pub struct CheckUsedAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub bitmap_pda: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for CheckUsedAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, bitmap_pda, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // Read-only and permissionless: no signer required
+
+        Ok(Self {
+            authority,
+            bitmap_pda,
+        })
+    }
+}
+
+/// CheckUsed instruction - reports whether a sequence is marked, via return data.
+///
+/// Same data as MarkUsed. Writes `1` if used, `0` otherwise (including when
+/// the bucket doesn't exist yet).
+pub struct CheckUsed<'a> {
+    pub accounts: CheckUsedAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for CheckUsed<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: CheckUsedAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MarkUsedBatch
+// =============================================================================
+
+/// Data for MarkUsedBatch instruction.
+///
+/// Format: `[namespace_len: u16 LE][namespace: 0-96 bytes][count: u16 LE]
+/// [sequence: u64 LE; count]`. All sequences must fall in the same bucket.
+pub struct MarkUsedBatchData<'a> {
+    pub namespace: &'a [u8],
+    sequences: &'a [u8],
+}
+
+impl<'a> MarkUsedBatchData<'a> {
+    /// Iterate the sequences in order.
+    pub fn sequences(&self) -> impl Iterator<Item = u64> + 'a {
+        self.sequences
+            .chunks_exact(8)
+            .map(|sequence| u64::from_le_bytes(sequence.try_into().unwrap()))
+    }
+
+    /// The first sequence, which selects the bucket.
+    pub fn first_sequence(&self) -> u64 {
+        u64::from_le_bytes(self.sequences[..8].try_into().unwrap())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for MarkUsedBatchData<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // Minimum: 2 (namespace_len) + 0 (empty namespace) + 2 (count) = 4 bytes
+        if data.len() < 4 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace_len = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
+
+        check_namespace(data, namespace_len)?;
+
+        let Some(count) = data.get(2 + namespace_len..2 + namespace_len + 2) else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+        let count = u16::from_le_bytes(count.try_into().unwrap()) as usize;
+
+        let sequences = &data[2 + namespace_len + 2..];
+        if count == 0 || sequences.len() != count * 8 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let batch = Self {
+            namespace: &data[2..2 + namespace_len],
+            sequences,
+        };
+
+        // One bitmap PDA is passed, so every sequence must live in its bucket
+        let bucket = bucket_of(batch.first_sequence());
+        if batch
+            .sequences()
+            .any(|sequence| bucket_of(sequence) != bucket)
+        {
+            return Err(NoReplayError::InvalidBucket.into());
+        }
+
+        Ok(batch)
+    }
+}
+
+/// MarkUsedBatch instruction - marks several sequences sharing one bucket.
+///
+/// Same accounts as MarkUsed. The PDA is derived and initialized once for
+/// the whole batch. Fails (atomically) if any sequence was already marked,
+/// including a sequence repeated within the batch.
+pub struct MarkUsedBatch<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub data: MarkUsedBatchData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsedBatch<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            data: MarkUsedBatchData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MarkUsedRange
+// =============================================================================
+
+/// Data for MarkUsedRange instruction.
+///
+/// Format: `[namespace_len: u16 LE][namespace: 0-96 bytes][start: u64 LE]
+/// [end: u64 LE]`. The range is inclusive and must lie within one bucket.
+pub struct MarkUsedRangeData<'a> {
+    pub namespace: &'a [u8],
+    pub start: u64,
+    pub end: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for MarkUsedRangeData<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // Minimum: 2 (namespace_len) + 0 (empty namespace) + 8 (start) + 8 (end) = 18 bytes
+        if data.len() < 18 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace_len = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
+
+        check_namespace(data, namespace_len)?;
+
+        if data.len() != 2 + namespace_len + 16 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace = &data[2..2 + namespace_len];
+        let bounds = &data[2 + namespace_len..];
+        let start = u64::from_le_bytes(bounds[..8].try_into().unwrap());
+        let end = u64::from_le_bytes(bounds[8..].try_into().unwrap());
+
+        if end < start {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        if bucket_of(start) != bucket_of(end) {
+            return Err(NoReplayError::InvalidBucket.into());
+        }
+
+        Ok(Self {
+            namespace,
+            start,
+            end,
+        })
+    }
+}
+
+/// MarkUsedRange instruction - marks the contiguous sequences `start..=end`.
+///
+/// Same accounts as MarkUsed. Fails (atomically) if any sequence in the range
+/// was already marked.
+pub struct MarkUsedRange<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub data: MarkUsedRangeData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsedRange<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            data: MarkUsedRangeData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// RangeAnyUsed
+// =============================================================================
+
+/// Data for RangeAnyUsed instruction.
+///
+/// Format: `[namespace_len: u16 LE][namespace: 0-96 bytes][start: u64 LE]
+/// [count: u64 LE]`. The run must be non-empty and lie within one bucket.
+pub struct RangeAnyUsedData<'a> {
+    pub namespace: &'a [u8],
+    pub start: u64,
+    pub count: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for RangeAnyUsedData<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // Minimum: 2 (namespace_len) + 0 (empty namespace) + 8 (start) + 8 (count) = 18 bytes
+        if data.len() < 18 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace_len = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
+
+        check_namespace(data, namespace_len)?;
+
+        if data.len() != 2 + namespace_len + 16 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace = &data[2..2 + namespace_len];
+        let fields = &data[2 + namespace_len..];
+        let start = u64::from_le_bytes(fields[..8].try_into().unwrap());
+        let count = u64::from_le_bytes(fields[8..].try_into().unwrap());
+
+        if count == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        if count > BITS_PER_BUCKET - bit_of(start) as u64 {
+            return Err(NoReplayError::InvalidBucket.into());
+        }
+
+        Ok(Self {
+            namespace,
+            start,
+            count,
+        })
+    }
+}
+
+/// RangeAnyUsed instruction - reports whether any sequence in a run is
+/// marked, via return data.
+///
+/// Same accounts as CheckUsed. Writes `1` if any sequence in
+/// `start..start + count` is used, `0` otherwise (including when the bucket
+/// doesn't exist yet).
+pub struct RangeAnyUsed<'a> {
+    pub accounts: CheckUsedAccounts<'a>,
+    pub data: RangeAnyUsedData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for RangeAnyUsed<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: CheckUsedAccounts::try_from(accounts)?,
+            data: RangeAnyUsedData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// CloseBitmap
+// =============================================================================
+
+/// Accounts for CloseBitmap instruction.
+///
+/// # Accounts
+/// 0. `[signer]` authority - Owner of the sequence space (included in PDA seeds);
+///    needn't sign when a config's current authority does
+/// 1. `[writable]` bitmap_pda - Fully used bucket to close
+/// 2. `[writable]` recipient - Receives the bucket's lamports
+/// 3. `[]` config - Optional namespace config ([`ConfigAuthorityAccounts`])
+/// 4. `[signer]` current_authority - Optional, the config's current authority
+pub struct CloseBitmapAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub bitmap_pda: &'a AccountView,
+    pub recipient: &'a AccountView,
+    pub config: Option<ConfigAuthorityAccounts<'a>>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for CloseBitmapAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, bitmap_pda, recipient, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // The lamports must leave the bucket
+        if recipient.address() == bitmap_pda.address() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // The authority, or a passed config's current authority, must sign so
+        // third parties can't close buckets; checked by the processor
+        Ok(Self {
+            authority,
+            bitmap_pda,
+            recipient,
+            config: ConfigAuthorityAccounts::from_trailing(rest),
+        })
+    }
+}
+
+/// CloseBitmap instruction - reclaims the rent of a fully used bucket.
+///
+/// Same data as MarkUsed; any sequence in the bucket selects it. Only a
+/// bucket with every sequence marked can be closed. A recreated bucket starts
+/// empty, so after closing, replay protection for the bucket's sequences must
+/// come from the caller (e.g. a monotonic lower bound on accepted sequences).
+pub struct CloseBitmap<'a> {
+    pub accounts: CloseBitmapAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for CloseBitmap<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: CloseBitmapAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MigrateBucket
+// =============================================================================
+
+/// MigrateBucket instruction - rewrites a pre-tag bucket
+/// (`LEGACY_BITMAP_ACCOUNT_SIZE` bytes) in the current tagged layout,
+/// keeping its marks.
+///
+/// Same accounts as CreateBitmap (permissionless: the payer funds the rent
+/// delta, and the marks are only moved, never changed) and same data as
+/// MarkUsed; any sequence in the bucket selects it.
+pub struct MigrateBucket<'a> {
+    pub accounts: CreateBitmapAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MigrateBucket<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: CreateBitmapAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MarkUsedDomain
+// =============================================================================
+
+/// MarkUsedDomain instruction - MarkUsed in a separate PDA space.
+///
+/// Lets one authority run several independent replay systems whose
+/// `(namespace, bucket)` pairs may overlap. Accounts are the same as MarkUsed.
+///
+/// Format: `[domain: u8][namespace_len: u16 LE][namespace][sequence: u64 LE]`
+pub struct MarkUsedDomain<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub domain: u8,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsedDomain<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let (&domain, data) = data
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            domain,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MarkAbove
+// =============================================================================
+
+/// MarkAbove instruction - raises a per-namespace high watermark.
+///
+/// Same accounts and data as MarkUsed, except account 2 is the watermark PDA
+/// for `(authority, namespace)` rather than a bitmap bucket. Fails unless the
+/// sequence is strictly greater than every sequence marked before it.
+pub struct MarkAbove<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkAbove<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MarkWindowed
+// =============================================================================
+
+/// MarkWindowed instruction - marks a sequence in a per-namespace sliding
+/// window.
+///
+/// Same accounts and data as MarkUsed, except account 2 is the window PDA
+/// for `(authority, namespace)`. Fails for a sequence already marked within
+/// the window, or one that has slid out of it.
+pub struct MarkWindowed<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkWindowed<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MarkUsedCommitted
+// =============================================================================
+
+/// MarkUsedCommitted instruction - MarkUsed that also commits to the
+/// marked message's hash.
+///
+/// Same accounts as MarkUsed, except account 2 is a committed bucket, a
+/// separate PDA space whose buckets carry an accumulator alongside the
+/// bitmap.
+///
+/// Format: `[message_hash: 32 bytes][namespace_len: u16 LE][namespace][sequence: u64 LE]`
+pub struct MarkUsedCommitted<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub message_hash: &'a [u8; 32],
+    pub data: InstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsedCommitted<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let (message_hash, data) = data
+            .split_first_chunk::<32>()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            message_hash,
+            data: InstructionData::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// MarkUsedU128
+// =============================================================================
+
+/// Data for MarkUsedU128 instruction.
+///
+/// Format: `[namespace_len: u16 LE][namespace: 0-96 bytes][sequence: u128 LE]`
+pub struct MarkUsedU128Data<'a> {
+    pub namespace: &'a [u8],
+    pub sequence: u128,
+}
+
+impl<'a> TryFrom<&'a [u8]> for MarkUsedU128Data<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // Minimum: 2 (namespace_len) + 0 (empty namespace) + 16 (sequence) = 18 bytes
+        if data.len() < 18 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace_len = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
+
+        check_namespace(data, namespace_len)?;
+
+        if data.len() != 2 + namespace_len + 16 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace = &data[2..2 + namespace_len];
+        let sequence = u128::from_le_bytes(data[2 + namespace_len..].try_into().unwrap());
+
+        Ok(Self {
+            namespace,
+            sequence,
+        })
+    }
+}
+
+/// MarkUsedU128 instruction - MarkUsed for sequences wider than u64.
+///
+/// Accounts are the same as MarkUsed; the bucket is derived with a 16-byte
+/// bucket seed, see [`crate::pda::BitmapPdaSeeds::new_u128`].
+pub struct MarkUsedU128<'a> {
+    pub accounts: MarkUsedAccounts<'a>,
+    pub data: MarkUsedU128Data<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MarkUsedU128<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: MarkUsedAccounts::try_from(accounts)?,
+            data: MarkUsedU128Data::try_from(data)?,
+        })
+    }
+}
+
+// =============================================================================
+// InitConfig / SetAuthority
+// =============================================================================
+
+/// Data for InitConfig and SetAuthority instructions.
+///
+/// Format: `[namespace_len: u16 LE][namespace: 0-96 bytes][new_authority: 32 bytes]`
+pub struct ConfigData<'a> {
+    pub namespace: &'a [u8],
+    pub new_authority: &'a [u8; 32],
+}
+
+impl<'a> TryFrom<&'a [u8]> for ConfigData<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // Minimum: 2 (namespace_len) + 0 (empty namespace) + 32 (new_authority) = 34 bytes
+        if data.len() < 34 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace_len = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
+
+        check_namespace(data, namespace_len)?;
+
+        if data.len() != 2 + namespace_len + 32 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let namespace = &data[2..2 + namespace_len];
+        let new_authority = data[2 + namespace_len..].try_into().unwrap();
+
+        Ok(Self {
+            namespace,
+            new_authority,
+        })
+    }
+}
+
+/// Accounts for InitConfig instruction.
+///
+/// # Accounts
+/// 0. `[writable, signer]` payer - Pays for the config PDA
+/// 1. `[signer]` authority - Seed authority of the namespace
+/// 2. `[writable]` config - Namespace config PDA to create
+/// 3. `[]` system_program - System program
+pub struct InitConfigAccounts<'a> {
+    pub payer: &'a AccountView,
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+    pub system_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for InitConfigAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [payer, authority, config, system_program, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // Payer must sign
+        if !payer.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // Only the seed authority may hand its namespace to another key
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self {
+            payer,
+            authority,
+            config,
+            system_program,
+        })
+    }
+}
+
+/// InitConfig instruction - creates the namespace config PDA for
+/// `(authority, namespace)`, recording `new_authority` as the key allowed to
+/// mark sequences for it.
+///
+/// Fails if the config already exists; use SetAuthority to change it.
+pub struct InitConfig<'a> {
+    pub accounts: InitConfigAccounts<'a>,
+    pub data: ConfigData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for InitConfig<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: InitConfigAccounts::try_from(accounts)?,
+            data: ConfigData::try_from(data)?,
+        })
+    }
+}
+
+/// Accounts for SetAuthority instruction.
+///
+/// # Accounts
+/// 0. `[signer]` current_authority - Key currently recorded in the config
+/// 1. `[]` authority - Seed authority of the namespace (does NOT need to sign)
+/// 2. `[writable]` config - Namespace config PDA
+pub struct SetAuthorityAccounts<'a> {
+    pub current_authority: &'a AccountView,
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SetAuthorityAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [current_authority, authority, config, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // Checked against the config by the processor
+        if !current_authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self {
+            current_authority,
+            authority,
+            config,
+        })
+    }
+}
+
+/// SetAuthority instruction - replaces the config's current authority with
+/// `new_authority`.
+pub struct SetAuthority<'a> {
+    pub accounts: SetAuthorityAccounts<'a>,
+    pub data: ConfigData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for SetAuthority<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: SetAuthorityAccounts::try_from(accounts)?,
+            data: ConfigData::try_from(data)?,
+        })
+    }
+}
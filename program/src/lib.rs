@@ -1,22 +1,88 @@
-use pinocchio::{default_panic_handler, no_allocator, program_entrypoint};
-
+pub mod delegation;
+pub mod error;
 pub mod instruction;
+#[cfg(any(feature = "logging", feature = "debug-panic"))]
+mod log;
 pub mod pda;
 pub mod processor;
 pub mod state;
 
 #[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "compat")]
+pub mod compat;
 
 // Re-exports for convenience
-pub use instruction::{CreateBitmap, InstructionData, MarkUsed, CREATE_BITMAP, MARK_USED};
-pub use pda::{derive_bitmap_pda, BitmapPdaSeeds, DerivePdaError};
-pub use state::{BitmapAccount, BITMAP_ACCOUNT_SIZE, BITMAP_BYTES, BITS_PER_BUCKET};
+pub use error::NoReplayError;
+pub use instruction::{
+    CheckUsed, CloseBitmap, ConfigData, CreateBitmap, Discriminator, InitConfig, InstructionData,
+    MarkAbove, MarkUsed, MarkUsedBatch, MarkUsedCommitted, MarkUsedDelegated, MarkUsedDomain,
+    MarkUsedIdempotent, MarkUsedRange, MarkUsedU128, MarkWindowed, MigrateBucket, MultiBucketMark,
+    RangeAnyUsed, SetAuthority, VerifyBucket, CHECK_USED, CLOSE_BITMAP, CREATE_BITMAP, INIT_CONFIG,
+    MARK_ABOVE, MARK_USED, MARK_USED_BATCH, MARK_USED_COMMITTED, MARK_USED_DELEGATED,
+    MARK_USED_DOMAIN, MARK_USED_IDEMPOTENT, MARK_USED_RANGE, MARK_USED_U128, MARK_WINDOWED,
+    MAX_MULTI_BUCKET_ACCOUNTS, MIGRATE_BUCKET, MULTI_BUCKET_MARK, RANGE_ANY_USED,
+    RETURN_STATUS_FLAG, SET_AUTHORITY, VERIFY_BUCKET,
+};
+pub use pda::{
+    derive_bitmap_pda, derive_bitmap_pda_domain, derive_bitmap_pda_u128, derive_committed_pda,
+    derive_config_pda, derive_watermark_pda, derive_window_pda, is_reserved_namespace,
+    BitmapPdaSeeds, DerivePdaError, NamespacePdaSeeds, COMMITTED_SEED_TAG, CONFIG_SEED_TAG,
+    DOMAIN_SEED_TAG, RESERVED_SEED_TAGS, U128_SEED_TAG, WATERMARK_SEED_TAG, WINDOW_SEED_TAG,
+};
+pub use state::{
+    bit_of, bit_position, bucket_of, bucket_of_u128, check_committed_tag, check_config_tag,
+    check_tag, check_watermark_tag, check_window_tag, next_accumulator, BitmapAccount,
+    BitmapAccountRef, BucketStatus, CommittedBitmapAccount, ConfigAccount, TagError,
+    WatermarkAccount, WindowAccount, WindowError, ACCUMULATOR_BYTES, BITMAP_ACCOUNT_SIZE,
+    BITMAP_BYTES, BITMAP_FORMAT_VERSION, BITMAP_LAYOUT, BITMAP_MAGIC, BITMAP_TAG, BITMAP_TAG_LEN,
+    BITS_PER_BUCKET, COMMITTED_ACCOUNT_SIZE, COMMITTED_FORMAT_VERSION, COMMITTED_LAYOUT,
+    COMMITTED_MAGIC, COMMITTED_TAG, CONFIG_ACCOUNT_SIZE, CONFIG_FORMAT_VERSION, CONFIG_LAYOUT,
+    CONFIG_MAGIC, CONFIG_TAG, LEGACY_BITMAP_ACCOUNT_SIZE, MAX_BUCKET_INDEX, WATERMARK_ACCOUNT_SIZE,
+    WATERMARK_FORMAT_VERSION, WATERMARK_LAYOUT, WATERMARK_MAGIC, WATERMARK_TAG,
+    WINDOW_ACCOUNT_SIZE, WINDOW_BITS, WINDOW_BYTES, WINDOW_FORMAT_VERSION, WINDOW_LAYOUT,
+    WINDOW_MAGIC, WINDOW_TAG,
+};
+
+/// Maximum namespace length (3 chunks * 32 bytes = 96 bytes)
+/// Seeds: [authority (32), ns_chunk_0, ns_chunk_1, ns_chunk_2, bucket_index (8)]
+pub const MAX_NAMESPACE_LEN: usize = 96;
+
+/// Minimum namespace length: 1 with the `strict-namespace` feature, which
+/// rejects empty namespaces, otherwise 0.
+pub const MIN_NAMESPACE_LEN: usize = if cfg!(feature = "strict-namespace") {
+    1
+} else {
+    0
+};
+
+#[cfg(not(feature = "no-entrypoint"))]
+pinocchio::program_entrypoint!(processor::process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+pinocchio::no_allocator!();
+#[cfg(not(any(feature = "debug-panic", feature = "no-entrypoint")))]
+pinocchio::default_panic_handler!();
 
-/// Maximum namespace length (2 chunks * 32 bytes = 64 bytes)
-/// Seeds: [authority (32), ns_chunk_0, ns_chunk_1, bucket_index (8)]
-pub const MAX_NAMESPACE_LEN: usize = 64;
+/// Panic hook that logs the panic message and location before aborting.
+///
+/// Only built with the `debug-panic` feature, to help diagnose unexpected
+/// panics in the parser or bit logic during development.
+#[cfg(all(
+    feature = "debug-panic",
+    not(feature = "no-entrypoint"),
+    target_os = "solana"
+))]
+#[no_mangle]
+fn custom_panic(info: &core::panic::PanicInfo<'_>) {
+    use crate::log::{sol_log, sol_log_64};
 
-program_entrypoint!(processor::process_instruction);
-no_allocator!();
-default_panic_handler!();
+    sol_log("** PANICKED **");
+    // Formatted messages would need an allocator; static ones are logged as-is
+    if let Some(message) = info.message().as_str() {
+        sol_log(message);
+    }
+    if let Some(location) = info.location() {
+        sol_log(location.file());
+        sol_log_64(location.line() as u64, location.column() as u64, 0, 0, 0);
+    }
+}
@@ -0,0 +1,46 @@
+//! Program log syscalls.
+//!
+//! pinocchio 0.10 has no logging module, only the raw `sol_log_*` syscall
+//! definitions, so these are thin safe wrappers around them. Off-chain (host
+//! tools driving `processor::process_instruction` with mock accounts) they
+//! log nothing.
+
+// Each wrapper is only called under some of the features that build this module
+#![allow(dead_code)]
+
+/// Log a UTF-8 message.
+#[inline]
+pub(crate) fn sol_log(message: &str) {
+    #[cfg(target_os = "solana")]
+    // SAFETY: the pointer and length describe `message`, which outlives the call
+    unsafe {
+        pinocchio::syscalls::sol_log_(message.as_ptr(), message.len() as u64)
+    };
+    #[cfg(not(target_os = "solana"))]
+    let _ = message;
+}
+
+/// Log five integers.
+#[inline]
+pub(crate) fn sol_log_64(arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) {
+    #[cfg(target_os = "solana")]
+    // SAFETY: the syscall only reads its integer arguments
+    unsafe {
+        pinocchio::syscalls::sol_log_64_(arg1, arg2, arg3, arg4, arg5)
+    };
+    #[cfg(not(target_os = "solana"))]
+    let _ = (arg1, arg2, arg3, arg4, arg5);
+}
+
+/// Log each slice as base64, as one `Program data:` line.
+#[inline]
+pub(crate) fn sol_log_data(data: &[&[u8]]) {
+    #[cfg(target_os = "solana")]
+    // SAFETY: `data` is passed as the syscall's array of (pointer, length)
+    // pairs, all of which outlive the call
+    unsafe {
+        pinocchio::syscalls::sol_log_data(data.as_ptr() as *const u8, data.len() as u64)
+    };
+    #[cfg(not(target_os = "solana"))]
+    let _ = data;
+}
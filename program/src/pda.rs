@@ -1,63 +1,270 @@
 use pinocchio::Address;
 
-use crate::state::BITS_PER_BUCKET;
+use crate::state::{bucket_of, bucket_of_u128};
 use crate::MAX_NAMESPACE_LEN;
 
 /// Size of each seed component for namespace chunking
 const SEED_CHUNK_SIZE: usize = 32;
 
+/// Tag seed for domain-separated buckets, passed twice before the domain byte.
+///
+/// PDA seeds are hashed as one concatenated byte string, so a short domain
+/// prefix could be reproduced by an undomained namespace starting with the
+/// same bytes. Undomained seeds with namespaces of up to two chunks are at
+/// most 32 + 64 + 8 = 104 bytes; the 65-byte tag-plus-domain prefix makes
+/// every domained seed string longer. Longer namespaces close that gap, so
+/// those beginning with a seed tag are reserved (see
+/// [`is_reserved_namespace`]).
+pub const DOMAIN_SEED_TAG: &[u8; SEED_CHUNK_SIZE] = b"solana-noreplay/domain-separated";
+
+/// Tag seed for watermark accounts, passed three times before the namespace.
+///
+/// Watermark seeds drop the bucket component, so without it a watermark
+/// namespace ending in 8 bytes would hash like a bitmap namespace plus a
+/// bucket index. The 96-byte prefix puts every watermark seed string above
+/// the 104-byte maximum for two-chunk undomained namespaces, longer ones
+/// can't begin with the tag, and its first 32 bytes differ from
+/// [`DOMAIN_SEED_TAG`], so watermarks can't collide with either bitmap space.
+pub const WATERMARK_SEED_TAG: &[u8; SEED_CHUNK_SIZE] = b"solana-noreplay/watermark-single";
+
+/// Tag seed for sliding-window accounts, used like [`WATERMARK_SEED_TAG`].
+pub const WINDOW_SEED_TAG: &[u8; SEED_CHUNK_SIZE] = b"solana-noreplay/windowed-replays";
+
+/// Tag seed for namespace config accounts, used like [`WATERMARK_SEED_TAG`].
+///
+/// Config and window seeds have the same shape as watermark seeds, so each
+/// tag's distinct 32 bytes keep the three apart, and from both bitmap spaces.
+pub const CONFIG_SEED_TAG: &[u8; SEED_CHUNK_SIZE] = b"solana-noreplay/namespace-config";
+
+/// Tag seed for committed buckets, passed three times before the namespace.
+///
+/// Committed buckets keep the bucket index, so their seed strings are at
+/// least 32 + 96 + 8 = 136 bytes, longer than any two-chunk undomained one,
+/// and the tag's first 32 bytes keep them apart from domained buckets and
+/// every per-namespace account.
+pub const COMMITTED_SEED_TAG: &[u8; SEED_CHUNK_SIZE] = b"solana-noreplay/committed-bucket";
+
+/// Tag seed for u128 buckets, used like [`COMMITTED_SEED_TAG`].
+///
+/// Without it, a u128 bucket of namespace `N` hashes like the u64 bucket of
+/// `N || x` whose index is the wide index's high 8 bytes.
+pub const U128_SEED_TAG: &[u8; SEED_CHUNK_SIZE] = b"solana-noreplay/u128-seq-buckets";
+
+/// Seed tags that follow the authority in every tagged PDA kind.
+pub const RESERVED_SEED_TAGS: [&[u8; SEED_CHUNK_SIZE]; 6] = [
+    DOMAIN_SEED_TAG,
+    WATERMARK_SEED_TAG,
+    WINDOW_SEED_TAG,
+    CONFIG_SEED_TAG,
+    COMMITTED_SEED_TAG,
+    U128_SEED_TAG,
+];
+
+/// Whether the program rejects `namespace` as reserved: longer than two seed
+/// chunks and beginning with one of [`RESERVED_SEED_TAGS`].
+///
+/// Every tagged PDA kind puts a tag right after the authority, so an
+/// undomained bitmap seed string can only reproduce one if its namespace
+/// begins with that tag. Up to two chunks, length alone rules this out (see
+/// [`DOMAIN_SEED_TAG`]); the third chunk would otherwise let anyone create a
+/// bucket at another account kind's address with CreateBitmap.
+pub fn is_reserved_namespace(namespace: &[u8]) -> bool {
+    namespace.len() > 2 * SEED_CHUNK_SIZE
+        && RESERVED_SEED_TAGS
+            .iter()
+            .any(|tag| namespace.starts_with(*tag))
+}
+
 /// Error returned when PDA derivation fails
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DerivePdaError {
     NamespaceTooLong,
+    /// See [`is_reserved_namespace`].
+    ReservedNamespace,
+}
+
+/// Reject namespaces the program would, before deriving.
+#[inline]
+fn check_namespace(namespace: &[u8]) -> Result<(), DerivePdaError> {
+    if namespace.len() > MAX_NAMESPACE_LEN {
+        return Err(DerivePdaError::NamespaceTooLong);
+    }
+    if is_reserved_namespace(namespace) {
+        return Err(DerivePdaError::ReservedNamespace);
+    }
+    Ok(())
+}
+
+/// Split a namespace into its three seed chunks (bytes 0..32, 32..64 and
+/// 64..96), any of which may be empty.
+#[inline]
+pub(crate) const fn split_namespace(namespace: &[u8]) -> [&[u8]; 3] {
+    let first = if namespace.len() < SEED_CHUNK_SIZE {
+        namespace.len()
+    } else {
+        SEED_CHUNK_SIZE
+    };
+    let (ns_chunk_0, rest) = namespace.split_at(first);
+    let second = if rest.len() < SEED_CHUNK_SIZE {
+        rest.len()
+    } else {
+        SEED_CHUNK_SIZE
+    };
+    let (ns_chunk_1, ns_chunk_2) = rest.split_at(second);
+    [ns_chunk_0, ns_chunk_1, ns_chunk_2]
 }
 
 /// Seed components for bitmap PDA derivation.
 ///
-/// Seeds are always: `[authority, ns_chunk_0, ns_chunk_1, bucket_index]`
-/// where ns_chunk_0 and ns_chunk_1 may be empty slices.
+/// Seeds are always:
+/// `[authority, tag, tag, domain, ns_chunk_0, ns_chunk_1, ns_chunk_2, bucket_index, bucket_index_hi]`
+/// where the namespace chunks may be empty slices, `tag, tag, domain` are
+/// all empty unless a domain is set (or all [`COMMITTED_SEED_TAG`] for a
+/// committed bucket, or all [`U128_SEED_TAG`] for a u128 one), and
+/// `bucket_index_hi` is empty unless the bucket comes from a u128 sequence. Empty seeds don't change the hash,
+/// so undomained u64 buckets of namespaces up to 64 bytes keep the original
+/// 4-seed addresses.
 ///
-/// Namespace is split into two chunks (max 32 bytes each) to avoid heap
+/// Namespace is split into three chunks (max 32 bytes each) to avoid heap
 /// allocation while staying within Solana's per-seed size limit.
 pub struct BitmapPdaSeeds<'a> {
-    pub ns_chunks: [&'a [u8]; 2],
+    pub ns_chunks: [&'a [u8]; 3],
     pub bucket_bytes: [u8; 8],
+    /// Partition of the PDA space, see [`DOMAIN_SEED_TAG`].
+    pub domain: Option<[u8; 1]>,
+    /// High 8 bytes of a u128 bucket index; with `bucket_bytes` they hash
+    /// as one 16-byte LE bucket seed. See [`BitmapPdaSeeds::new_u128`].
+    pub bucket_hi: Option<[u8; 8]>,
+    /// Committed bucket space, see [`BitmapPdaSeeds::new_committed`]. Takes
+    /// the place of a domain.
+    pub committed: bool,
 }
 
 impl<'a> BitmapPdaSeeds<'a> {
     /// Compute seed components from namespace and sequence.
     pub fn new(namespace: &'a [u8], sequence: u64) -> Self {
-        let mid = namespace.len().min(SEED_CHUNK_SIZE);
         Self {
-            ns_chunks: [&namespace[..mid], &namespace[mid..]],
-            bucket_bytes: (sequence / BITS_PER_BUCKET).to_le_bytes(),
+            ns_chunks: split_namespace(namespace),
+            bucket_bytes: bucket_of(sequence).to_le_bytes(),
+            domain: None,
+            bucket_hi: None,
+            committed: false,
+        }
+    }
+
+    /// Compute seed components for a u128 sequence, with a 16-byte bucket
+    /// seed in the PDA space of [`U128_SEED_TAG`].
+    ///
+    /// Always distinct from every u64 bucket, of this namespace or any
+    /// other, even when `sequence` fits in a u64.
+    pub fn new_u128(namespace: &'a [u8], sequence: u128) -> Self {
+        let bucket = bucket_of_u128(sequence);
+        Self {
+            bucket_bytes: (bucket as u64).to_le_bytes(),
+            bucket_hi: Some(((bucket >> 64) as u64).to_le_bytes()),
+            ..Self::new(namespace, 0)
+        }
+    }
+
+    /// Like [`BitmapPdaSeeds::new`], in the PDA space of committed buckets
+    /// (see [`COMMITTED_SEED_TAG`]), whose layout differs from a bitmap's.
+    pub fn new_committed(namespace: &'a [u8], sequence: u64) -> Self {
+        Self {
+            committed: true,
+            ..Self::new(namespace, sequence)
+        }
+    }
+
+    /// Like [`BitmapPdaSeeds::new`], in the PDA space of `domain`.
+    pub fn new_with_domain(domain: u8, namespace: &'a [u8], sequence: u64) -> Self {
+        Self {
+            domain: Some([domain]),
+            ..Self::new(namespace, sequence)
+        }
+    }
+
+    /// Const equivalent of [`BitmapPdaSeeds::new`] for namespaces whose length
+    /// is known at compile time. An oversized namespace fails to compile.
+    pub const fn from_array<const N: usize>(namespace: &'a [u8; N], sequence: u64) -> Self {
+        const {
+            assert!(
+                N <= MAX_NAMESPACE_LEN,
+                "namespace exceeds MAX_NAMESPACE_LEN"
+            )
+        };
+        Self {
+            ns_chunks: split_namespace(namespace.as_slice()),
+            bucket_bytes: bucket_of(sequence).to_le_bytes(),
+            domain: None,
+            bucket_hi: None,
+            committed: false,
+        }
+    }
+
+    /// Domain seeds `[tag, tag, domain]`, the committed or u128 tag three
+    /// times, or three empty slices for a plain bucket.
+    #[inline]
+    fn domain_seeds(&self) -> [&[u8]; 3] {
+        match (&self.domain, self.committed, &self.bucket_hi) {
+            (_, true, _) => [COMMITTED_SEED_TAG, COMMITTED_SEED_TAG, COMMITTED_SEED_TAG],
+            (_, false, Some(_)) => [U128_SEED_TAG, U128_SEED_TAG, U128_SEED_TAG],
+            (Some(domain), false, None) => [DOMAIN_SEED_TAG, DOMAIN_SEED_TAG, domain],
+            (None, false, None) => [&[], &[], &[]],
+        }
+    }
+
+    /// High bucket index seed, or an empty slice for u64 buckets.
+    #[inline]
+    fn bucket_hi_seed(&self) -> &[u8] {
+        match &self.bucket_hi {
+            Some(hi) => hi,
+            None => &[],
         }
     }
 
     /// Build the seeds array for PDA derivation (without bump).
-    pub fn as_seeds(&self, authority: &'a [u8]) -> [&[u8]; 4] {
+    pub fn as_seeds(&self, authority: &'a [u8]) -> [&[u8]; 9] {
+        let [tag_0, tag_1, domain] = self.domain_seeds();
         [
             authority,
+            tag_0,
+            tag_1,
+            domain,
             self.ns_chunks[0],
             self.ns_chunks[1],
+            self.ns_chunks[2],
             &self.bucket_bytes,
+            self.bucket_hi_seed(),
         ]
     }
 
     /// Build the seeds array with bump for verification or signing.
-    pub fn as_seeds_with_bump<'b>(&'b self, authority: &'b [u8], bump: &'b [u8]) -> [&'b [u8]; 5]
+    pub fn as_seeds_with_bump<'b>(&'b self, authority: &'b [u8], bump: &'b [u8]) -> [&'b [u8]; 10]
     where
         'a: 'b,
     {
+        let [tag_0, tag_1, domain] = self.domain_seeds();
         [
             authority,
+            tag_0,
+            tag_1,
+            domain,
             self.ns_chunks[0],
             self.ns_chunks[1],
+            self.ns_chunks[2],
             &self.bucket_bytes,
+            self.bucket_hi_seed(),
             bump,
         ]
     }
 
+    /// Full bucket index encoded in the seeds.
+    #[inline]
+    pub fn bucket_index_u128(&self) -> u128 {
+        let hi = self.bucket_hi.map_or(0, u64::from_le_bytes);
+        (hi as u128) << 64 | u64::from_le_bytes(self.bucket_bytes) as u128
+    }
+
     /// Derive the PDA address and bump.
     pub fn find_pda(&self, authority: &Address, program_id: &Address) -> (Address, u8) {
         let seeds = self.as_seeds(authority.as_ref());
@@ -67,17 +274,158 @@ impl<'a> BitmapPdaSeeds<'a> {
 
 /// Derive the bitmap PDA for a given authority, namespace, and sequence.
 ///
-/// Seeds are always: `[authority, ns_chunk_0, ns_chunk_1, bucket_index]`
-/// where ns_chunk_0 and ns_chunk_1 may be empty slices.
+/// Seeds are always: `[authority, ns_chunk_0, ns_chunk_1, ns_chunk_2, bucket_index]`
+/// where the namespace chunks may be empty slices.
 pub fn derive_bitmap_pda(
     authority: &Address,
     namespace: &[u8],
     sequence: u64,
     program_id: &Address,
 ) -> Result<(Address, u8), DerivePdaError> {
-    if namespace.len() > MAX_NAMESPACE_LEN {
-        return Err(DerivePdaError::NamespaceTooLong);
-    }
+    check_namespace(namespace)?;
 
     Ok(BitmapPdaSeeds::new(namespace, sequence).find_pda(authority, program_id))
 }
+
+/// Derive the bitmap PDA for `domain`, see [`BitmapPdaSeeds::new_with_domain`].
+pub fn derive_bitmap_pda_domain(
+    authority: &Address,
+    domain: u8,
+    namespace: &[u8],
+    sequence: u64,
+    program_id: &Address,
+) -> Result<(Address, u8), DerivePdaError> {
+    check_namespace(namespace)?;
+
+    Ok(
+        BitmapPdaSeeds::new_with_domain(domain, namespace, sequence)
+            .find_pda(authority, program_id),
+    )
+}
+
+/// Derive the committed bucket PDA, see [`BitmapPdaSeeds::new_committed`].
+pub fn derive_committed_pda(
+    authority: &Address,
+    namespace: &[u8],
+    sequence: u64,
+    program_id: &Address,
+) -> Result<(Address, u8), DerivePdaError> {
+    check_namespace(namespace)?;
+
+    Ok(BitmapPdaSeeds::new_committed(namespace, sequence).find_pda(authority, program_id))
+}
+
+/// Seed components for per-namespace (bucketless) PDA derivation.
+///
+/// Seeds are always: `[authority, tag, tag, tag, ns_chunk_0, ns_chunk_1, ns_chunk_2]`,
+/// one account per `(authority, namespace, tag)`; see [`WATERMARK_SEED_TAG`],
+/// [`WINDOW_SEED_TAG`] and [`CONFIG_SEED_TAG`].
+pub struct NamespacePdaSeeds<'a> {
+    pub tag: &'static [u8; SEED_CHUNK_SIZE],
+    pub ns_chunks: [&'a [u8]; 3],
+}
+
+impl<'a> NamespacePdaSeeds<'a> {
+    /// Compute seed components for the namespace's watermark account.
+    pub fn watermark(namespace: &'a [u8]) -> Self {
+        Self::new(WATERMARK_SEED_TAG, namespace)
+    }
+
+    /// Compute seed components for the namespace's sliding-window account.
+    pub fn window(namespace: &'a [u8]) -> Self {
+        Self::new(WINDOW_SEED_TAG, namespace)
+    }
+
+    /// Compute seed components for the namespace's config account.
+    pub fn config(namespace: &'a [u8]) -> Self {
+        Self::new(CONFIG_SEED_TAG, namespace)
+    }
+
+    fn new(tag: &'static [u8; SEED_CHUNK_SIZE], namespace: &'a [u8]) -> Self {
+        Self {
+            tag,
+            ns_chunks: split_namespace(namespace),
+        }
+    }
+
+    /// Build the seeds array for PDA derivation (without bump).
+    pub fn as_seeds(&self, authority: &'a [u8]) -> [&[u8]; 7] {
+        [
+            authority,
+            self.tag,
+            self.tag,
+            self.tag,
+            self.ns_chunks[0],
+            self.ns_chunks[1],
+            self.ns_chunks[2],
+        ]
+    }
+
+    /// Build the seeds array with bump for verification or signing.
+    pub fn as_seeds_with_bump<'b>(&'b self, authority: &'b [u8], bump: &'b [u8]) -> [&'b [u8]; 8]
+    where
+        'a: 'b,
+    {
+        [
+            authority,
+            self.tag,
+            self.tag,
+            self.tag,
+            self.ns_chunks[0],
+            self.ns_chunks[1],
+            self.ns_chunks[2],
+            bump,
+        ]
+    }
+
+    /// Derive the PDA address and bump.
+    pub fn find_pda(&self, authority: &Address, program_id: &Address) -> (Address, u8) {
+        let seeds = self.as_seeds(authority.as_ref());
+        Address::find_program_address(&seeds, program_id)
+    }
+}
+
+/// Derive the bitmap PDA for a u128 sequence, see [`BitmapPdaSeeds::new_u128`].
+pub fn derive_bitmap_pda_u128(
+    authority: &Address,
+    namespace: &[u8],
+    sequence: u128,
+    program_id: &Address,
+) -> Result<(Address, u8), DerivePdaError> {
+    check_namespace(namespace)?;
+
+    Ok(BitmapPdaSeeds::new_u128(namespace, sequence).find_pda(authority, program_id))
+}
+
+/// Derive the watermark PDA for a given authority and namespace.
+pub fn derive_watermark_pda(
+    authority: &Address,
+    namespace: &[u8],
+    program_id: &Address,
+) -> Result<(Address, u8), DerivePdaError> {
+    check_namespace(namespace)?;
+
+    Ok(NamespacePdaSeeds::watermark(namespace).find_pda(authority, program_id))
+}
+
+/// Derive the sliding-window PDA for a given authority and namespace.
+pub fn derive_window_pda(
+    authority: &Address,
+    namespace: &[u8],
+    program_id: &Address,
+) -> Result<(Address, u8), DerivePdaError> {
+    check_namespace(namespace)?;
+
+    Ok(NamespacePdaSeeds::window(namespace).find_pda(authority, program_id))
+}
+
+/// Derive the namespace config PDA for a given authority and namespace.
+pub fn derive_config_pda(
+    authority: &Address,
+    namespace: &[u8],
+    program_id: &Address,
+) -> Result<(Address, u8), DerivePdaError> {
+    check_namespace(namespace)?;
+
+    Ok(NamespacePdaSeeds::config(namespace).find_pda(authority, program_id))
+}
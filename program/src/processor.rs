@@ -1,13 +1,35 @@
 use pinocchio::{
-    cpi::{Seed, Signer},
+    cpi::{set_return_data, Seed, Signer},
     error::ProgramError,
     AccountView, Address, ProgramResult,
 };
 use pinocchio_system::instructions::{Allocate, Assign, CreateAccount, Transfer};
 
-use crate::instruction::{CreateBitmap, MarkUsed, CREATE_BITMAP, MARK_USED};
-use crate::pda::BitmapPdaSeeds;
-use crate::state::{BitmapAccount, BITMAP_ACCOUNT_SIZE};
+use crate::delegation::verify_delegation;
+use crate::error::NoReplayError;
+use crate::instruction::{
+    CheckUsed, CloseBitmap, ConfigAuthorityAccounts, CreateBitmap, Discriminator, InitConfig,
+    InstructionData, MarkAbove, MarkUsed, MarkUsedAccounts, MarkUsedBatch, MarkUsedCommitted,
+    MarkUsedDelegated, MarkUsedDomain, MarkUsedIdempotent, MarkUsedRange, MarkUsedU128,
+    MarkWindowed, MigrateBucket, MultiBucketMark, RangeAnyUsed, SetAuthority, VerifyBucket,
+};
+use crate::pda::{BitmapPdaSeeds, NamespacePdaSeeds};
+use crate::state::{
+    check_committed_tag, check_config_tag, check_tag, check_watermark_tag, check_window_tag,
+    BitmapAccount, BitmapAccountRef, BucketStatus, CommittedBitmapAccount, ConfigAccount,
+    RangeError, WatermarkAccount, WindowAccount, WindowError, BITMAP_ACCOUNT_SIZE, BITMAP_TAG_LEN,
+    BITS_PER_BUCKET, COMMITTED_ACCOUNT_SIZE, CONFIG_ACCOUNT_SIZE, LEGACY_BITMAP_ACCOUNT_SIZE,
+    WATERMARK_ACCOUNT_SIZE, WINDOW_ACCOUNT_SIZE,
+};
+
+/// Line logged on every rejected replay when built with the `logging`
+/// feature. Stable, so log scrapers can match on it.
+pub const REPLAY_LOG: &str = "noreplay: seq already used";
+
+/// Line logged when a passed bitmap account isn't the PDA derived from the
+/// seeds, when built with the `logging` feature. The expected and provided
+/// addresses follow as `Program data`, in that order.
+pub const PDA_MISMATCH_LOG: &str = "noreplay: bitmap pda mismatch";
 
 /// Process program instructions.
 pub fn process_instruction(
@@ -15,17 +37,84 @@ pub fn process_instruction(
     accounts: &[AccountView],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    match instruction_data.split_first() {
-        Some((&CREATE_BITMAP, data)) => {
+    let Some((&discriminator, data)) = instruction_data.split_first() else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+
+    match Discriminator::try_from(discriminator)? {
+        Discriminator::CreateBitmap => {
             CreateBitmap::try_from((data, accounts))?.process(program_id)
         }
-        Some((&MARK_USED, data)) => MarkUsed::try_from((data, accounts))?.process(program_id),
-        _ => Err(ProgramError::InvalidInstructionData),
+        Discriminator::MarkUsed => MarkUsed::try_from((data, accounts))?.process(program_id),
+        Discriminator::CheckUsed => CheckUsed::try_from((data, accounts))?.process(program_id),
+        Discriminator::MarkUsedBatch => {
+            MarkUsedBatch::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::CloseBitmap => CloseBitmap::try_from((data, accounts))?.process(program_id),
+        Discriminator::MarkAbove => MarkAbove::try_from((data, accounts))?.process(program_id),
+        Discriminator::MarkUsedIdempotent => {
+            MarkUsedIdempotent::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MarkUsedDelegated => {
+            MarkUsedDelegated::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MultiBucketMark => {
+            MultiBucketMark::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::VerifyBucket => {
+            VerifyBucket::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MarkUsedRange => {
+            MarkUsedRange::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::RangeAnyUsed => {
+            RangeAnyUsed::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MarkUsedDomain => {
+            MarkUsedDomain::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MarkUsedU128 => {
+            MarkUsedU128::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::InitConfig => InitConfig::try_from((data, accounts))?.process(program_id),
+        Discriminator::SetAuthority => {
+            SetAuthority::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MarkWindowed => {
+            MarkWindowed::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MigrateBucket => {
+            MigrateBucket::try_from((data, accounts))?.process(program_id)
+        }
+        Discriminator::MarkUsedCommitted => {
+            MarkUsedCommitted::try_from((data, accounts))?.process(program_id)
+        }
     }
 }
 
 /// Create and assign a PDA with the given space.
-/// Uses single CPI for new accounts, 3 CPIs for pre-funded accounts.
+/// Uses single CPI for new accounts, up to 3 CPIs for pre-funded accounts.
+///
+/// The system program's CreateAccount rejects any destination that already
+/// holds lamports (`AccountAlreadyInUse`), and pinocchio-system exposes no
+/// prefund-tolerant variant, so a PDA that received lamports before creation
+/// (by anyone, since transfers to it are permissionless) must go through
+/// Transfer, Allocate and Assign instead. Each step runs only if needed:
+/// Transfer if underfunded, Allocate if not yet `space` bytes, Assign if not
+/// yet owned by `owner`. Allocate also fails with `AccountAlreadyInUse` on an
+/// account that already has data, so skipping it is what lets an
+/// already-allocated PDA be created at all. The cost of each path is tracked
+/// by the `mark_used__prefunded_*` benches.
+///
+/// Lamports above the rent-exempt minimum already in the PDA are left there:
+/// overfunding is the funder's choice, and the excess is neither spent nor
+/// refunded. It can only be recovered by `CloseBitmap`.
+///
+/// The payer is a system account, so there is no in-program check on its
+/// remaining balance: the runtime fails the whole transaction with
+/// `InsufficientFundsForRent` if the payer would be left with a non-zero
+/// balance below its own rent-exempt minimum. Draining it to exactly zero is
+/// allowed.
 fn create_pda<'a>(
     payer: &'a AccountView,
     pda: &'a AccountView,
@@ -53,19 +142,23 @@ fn create_pda<'a>(
             .invoke()?;
         }
 
-        // Allocate space
-        Allocate {
-            account: pda,
-            space,
+        // Allocate space, unless the account already has it
+        if pda.data_len() as u64 != space {
+            Allocate {
+                account: pda,
+                space,
+            }
+            .invoke_signed(signers)?;
         }
-        .invoke_signed(signers)?;
 
-        // Assign to owner
-        Assign {
-            account: pda,
-            owner,
+        // Assign to owner, unless already assigned
+        if unsafe { pda.owner() } != owner {
+            Assign {
+                account: pda,
+                owner,
+            }
+            .invoke_signed(signers)?;
         }
-        .invoke_signed(signers)?;
     }
 
     Ok(())
@@ -77,36 +170,284 @@ fn build_signer<'a>(
     authority: &'a [u8],
     pda_seeds: &'a BitmapPdaSeeds<'a>,
     bump_seed: &'a [u8],
-) -> [Seed<'a>; 5] {
-    let seeds = pda_seeds.as_seeds_with_bump(authority, bump_seed);
-    [
-        Seed::from(seeds[0]),
-        Seed::from(seeds[1]),
-        Seed::from(seeds[2]),
-        Seed::from(seeds[3]),
-        Seed::from(seeds[4]),
-    ]
+) -> [Seed<'a>; 10] {
+    pda_seeds
+        .as_seeds_with_bump(authority, bump_seed)
+        .map(Seed::from)
+}
+
+/// Require the system program account for a creation path.
+///
+/// The runtime dispatches CPIs by program ID, not by the account passed, but
+/// an explicit check fails a spoofed account up front with a clear error.
+#[inline]
+fn check_system_program(system_program: Option<&AccountView>) -> ProgramResult {
+    let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if system_program.address() != &pinocchio_system::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Reject bitmap account data that is too small.
+#[inline]
+fn check_bitmap_size(len: usize) -> Result<(), ProgramError> {
+    if len < BITMAP_ACCOUNT_SIZE {
+        // Log actual vs expected size
+        #[cfg(feature = "debug-panic")]
+        crate::log::sol_log_64(len as u64, BITMAP_ACCOUNT_SIZE as u64, 0, 0, 0);
+        return Err(NoReplayError::MalformedBitmapAccount.into());
+    }
+    Ok(())
+}
+
+/// View account data as a bitmap, rejecting accounts that are too small or
+/// don't carry the current tag.
+#[inline]
+fn load_bitmap(account_data: &mut [u8]) -> Result<BitmapAccount<'_>, ProgramError> {
+    check_bitmap_size(account_data.len())?;
+    let bitmap =
+        BitmapAccount::from_slice(account_data).ok_or(NoReplayError::MalformedBitmapAccount)?;
+    check_tag(bitmap.tag).map_err(NoReplayError::from)?;
+    Ok(bitmap)
+}
+
+/// Read-only [`load_bitmap`].
+#[inline]
+fn load_bitmap_ref(account_data: &[u8]) -> Result<BitmapAccountRef<'_>, ProgramError> {
+    check_bitmap_size(account_data.len())?;
+    let bitmap =
+        BitmapAccount::from_slice_ref(account_data).ok_or(NoReplayError::MalformedBitmapAccount)?;
+    check_tag(bitmap.tag).map_err(NoReplayError::from)?;
+    Ok(bitmap)
+}
+
+/// Load a bucket for a read-only query. Returns `None` if it isn't
+/// program-owned yet (no sequence in it can be used). An existing bucket is
+/// verified against the seeds via its stored bump.
+fn load_bucket_for_query<'a>(
+    authority: &AccountView,
+    bitmap_pda: &'a AccountView,
+    pda_seeds: &BitmapPdaSeeds,
+    program_id: &Address,
+) -> Result<Option<BitmapAccountRef<'a>>, ProgramError> {
+    if unsafe { bitmap_pda.owner() } != program_id {
+        return Ok(None);
+    }
+
+    // SAFETY: Read-only access; nothing else in this instruction borrows
+    // the account.
+    let account_data = unsafe { bitmap_pda.borrow_unchecked() };
+    let bitmap = load_bitmap_ref(account_data)?;
+
+    let bump_slice = [*bitmap.bump];
+    let seeds = pda_seeds.as_seeds_with_bump(authority.address().as_ref(), &bump_slice);
+    let expected_pda = Address::create_program_address(&seeds, program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+
+    check_bitmap_address(bitmap_pda, &expected_pda)?;
+
+    Ok(Some(bitmap))
+}
+
+/// Reject a bitmap account whose address isn't `expected`, the PDA derived
+/// from the instruction's seeds.
+#[inline]
+fn check_bitmap_address(bitmap_pda: &AccountView, expected: &Address) -> ProgramResult {
+    if bitmap_pda.address() != expected {
+        #[cfg(feature = "logging")]
+        {
+            crate::log::sol_log(PDA_MISMATCH_LOG);
+            crate::log::sol_log_data(&[expected.as_ref(), bitmap_pda.address().as_ref()]);
+        }
+        return Err(NoReplayError::BitmapPdaMismatch.into());
+    }
+    Ok(())
+}
+
+/// Create a per-namespace PDA (watermark, window or config) at the canonical
+/// bump and return the bump. The caller writes the account's tag.
+fn create_namespace_pda(
+    payer: &AccountView,
+    authority: &Address,
+    pda: &AccountView,
+    system_program: Option<&AccountView>,
+    pda_seeds: &NamespacePdaSeeds,
+    space: usize,
+    program_id: &Address,
+) -> Result<u8, ProgramError> {
+    check_system_program(system_program)?;
+
+    // Canonical bump only, for the same reason as init_bitmap_pda
+    let (expected_pda, bump) = pda_seeds.find_pda(authority, program_id);
+    if pda.address() != &expected_pda {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let bump_seed = [bump];
+    let signer_seeds = pda_seeds
+        .as_seeds_with_bump(authority.as_ref(), &bump_seed)
+        .map(Seed::from);
+    let signers = [Signer::from(signer_seeds.as_ref())];
+
+    create_pda(payer, pda, program_id, space as u64, &signers)?;
+
+    Ok(bump)
+}
+
+/// Verify an existing per-namespace PDA against the seeds via its stored
+/// bump. Every account kind is program-owned, so ownership alone doesn't
+/// tell them apart.
+fn check_namespace_pda(
+    authority: &Address,
+    pda: &AccountView,
+    pda_seeds: &NamespacePdaSeeds,
+    bump: u8,
+    program_id: &Address,
+) -> ProgramResult {
+    let bump_slice = [bump];
+    let seeds = pda_seeds.as_seeds_with_bump(authority.as_ref(), &bump_slice);
+    let expected_pda = Address::create_program_address(&seeds, program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+    if pda.address() != &expected_pda {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+/// Load an existing namespace config, verified against the seeds via its
+/// stored bump.
+fn load_config<'a>(
+    authority: &AccountView,
+    config: &'a AccountView,
+    namespace: &[u8],
+    program_id: &Address,
+) -> Result<ConfigAccount<'a>, ProgramError> {
+    if unsafe { config.owner() } != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    // SAFETY: Nothing else in this instruction borrows the config account.
+    let account_data = unsafe { config.borrow_unchecked_mut() };
+    let account =
+        ConfigAccount::from_slice(account_data).ok_or(NoReplayError::MalformedBitmapAccount)?;
+    check_config_tag(account.tag).map_err(NoReplayError::from)?;
+
+    check_namespace_pda(
+        authority.address(),
+        config,
+        &NamespacePdaSeeds::config(namespace),
+        *account.bump,
+        program_id,
+    )?;
+
+    Ok(account)
+}
+
+/// The key recorded in the passed config for `(authority, namespace)`, or
+/// `None` if the config doesn't exist yet. An existing config is verified
+/// via its stored bump; only a missing one costs a derivation.
+fn config_authority(
+    authority: &AccountView,
+    config: &AccountView,
+    namespace: &[u8],
+    program_id: &Address,
+) -> Result<Option<[u8; 32]>, ProgramError> {
+    if unsafe { config.owner() } == program_id {
+        let config = load_config(authority, config, namespace, program_id)?;
+        return Ok(Some(*config.current_authority));
+    }
+
+    let (expected_pda, _) =
+        NamespacePdaSeeds::config(namespace).find_pda(authority.address(), program_id);
+    if config.address() != &expected_pda {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(None)
+}
+
+/// Require the signature of whoever may mark in `(authority, namespace)`:
+/// the current authority of a passed config once it exists, the authority
+/// otherwise.
+fn authorize_mark(
+    authority: &AccountView,
+    accounts: Option<&ConfigAuthorityAccounts>,
+    namespace: &[u8],
+    program_id: &Address,
+) -> ProgramResult {
+    let current_authority = match accounts {
+        Some(accounts) => config_authority(authority, accounts.config, namespace, program_id)?,
+        None => None,
+    };
+
+    match (
+        current_authority,
+        accounts.and_then(|a| a.current_authority),
+    ) {
+        (Some(current_authority), Some(signer)) if signer.is_signer() => {
+            if signer.address().as_ref() != &current_authority[..] {
+                return Err(NoReplayError::AuthorityMismatch.into());
+            }
+        }
+        (Some(_), _) => return Err(ProgramError::MissingRequiredSignature),
+        (None, _) => {
+            if !authority.is_signer() {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// [`authorize_mark`] for the accounts shared by the marking instructions.
+#[inline]
+fn authorize(accounts: &MarkUsedAccounts, namespace: &[u8], program_id: &Address) -> ProgramResult {
+    authorize_mark(
+        accounts.authority,
+        accounts.config.as_ref(),
+        namespace,
+        program_id,
+    )
 }
 
 /// Initialize a bitmap PDA if it doesn't exist yet, and verify the PDA is correct.
 /// Returns the bump seed (either from creation or from existing account).
+///
+/// The system program account is only needed on the create path, so callers
+/// marking into an existing bucket may omit it.
 fn init_bitmap_pda<'a>(
     payer: &'a AccountView,
     authority: &'a AccountView,
     bitmap_pda: &'a AccountView,
+    system_program: Option<&'a AccountView>,
     pda_seeds: &BitmapPdaSeeds,
     program_id: &Address,
 ) -> Result<u8, ProgramError> {
     let pda_owner = unsafe { bitmap_pda.owner() };
 
     if pda_owner != program_id {
-        // Need to create - derive PDA to get bump
-        let (expected_pda, bump) = pda_seeds.find_pda(authority.address(), program_id);
+        // Creation CPIs into the system program, which must be passed
+        check_system_program(system_program)?;
 
-        if bitmap_pda.address() != &expected_pda {
-            return Err(ProgramError::InvalidSeeds);
+        // Refuse to create astronomically distant buckets
+        #[cfg(feature = "max-bucket")]
+        if pda_seeds.bucket_index_u128() > crate::state::MAX_BUCKET_INDEX as u128 {
+            return Err(NoReplayError::BucketIndexTooLarge.into());
         }
 
+        // Need to create - derive PDA to get bump.
+        //
+        // This must be the canonical bump, so don't take a client-supplied
+        // one: create_program_address accepts any valid bump, and a bucket
+        // created at a non-canonical address would pass the stored-bump check
+        // below, giving one bucket range two accounts and letting every
+        // sequence in it be marked twice. Proving a supplied bump canonical
+        // means showing each higher bump is off-curve, which is the same
+        // iteration find_program_address already does.
+        let (expected_pda, bump) = pda_seeds.find_pda(authority.address(), program_id);
+
+        check_bitmap_address(bitmap_pda, &expected_pda)?;
+
         let bump_seed = [bump];
         let signer_seeds = build_signer(authority.address().as_ref(), pda_seeds, &bump_seed);
         let signers = [Signer::from(signer_seeds.as_ref())];
@@ -119,13 +460,11 @@ fn init_bitmap_pda<'a>(
             &signers,
         )?;
 
-        // Store bump in the account
+        // Store tag and bump in the account
         // SAFETY: We have exclusive write access to the PDA data after creation.
         // The account was just created by this program, so no other references exist.
         let account_data = unsafe { bitmap_pda.borrow_unchecked_mut() };
-        let bitmap =
-            BitmapAccount::from_slice(account_data).ok_or(ProgramError::AccountDataTooSmall)?;
-        *bitmap.bump = bump;
+        BitmapAccount::init(account_data, bump).ok_or(NoReplayError::MalformedBitmapAccount)?;
 
         Ok(bump)
     } else {
@@ -133,23 +472,76 @@ fn init_bitmap_pda<'a>(
         // SAFETY: We have exclusive write access to the PDA data after owner validation.
         // The owner check above confirms this is our program's account.
         let account_data = unsafe { bitmap_pda.borrow_unchecked_mut() };
-        let bitmap =
-            BitmapAccount::from_slice(account_data).ok_or(ProgramError::AccountDataTooSmall)?;
+        // Smaller accounts are rejected by load_bitmap; together the size must
+        // be exact, so a stale larger layout can't pass as a bucket
+        if account_data.len() > BITMAP_ACCOUNT_SIZE {
+            return Err(NoReplayError::AccountSizeMismatch.into());
+        }
+        let bitmap = load_bitmap(account_data)?;
         let bump = *bitmap.bump;
 
+        // Ownership alone is not enough: every bucket this program creates is
+        // program-owned, so without this check a caller could pass any other
+        // (e.g. empty) bucket and replay a sequence. create_program_address with
+        // the stored bump is the cheap way to bind the account to these seeds.
         let bump_slice = [bump];
         let seeds = pda_seeds.as_seeds_with_bump(authority.address().as_ref(), &bump_slice);
         let expected_pda = Address::create_program_address(&seeds, program_id)
             .map_err(|_| ProgramError::InvalidSeeds)?;
 
-        if bitmap_pda.address() != &expected_pda {
-            return Err(ProgramError::InvalidSeeds);
-        }
+        check_bitmap_address(bitmap_pda, &expected_pda)?;
 
         Ok(bump)
     }
 }
 
+/// Error for a rejected replay. With the `logging` feature, also logs a fixed
+/// line so operators can tell replays from other failures in the transaction
+/// logs.
+#[inline]
+fn sequence_already_used() -> ProgramError {
+    #[cfg(feature = "logging")]
+    crate::log::sol_log(REPLAY_LOG);
+    NoReplayError::SequenceAlreadyUsed.into()
+}
+
+/// Set the bit for `data.sequence`, creating the bitmap PDA if needed.
+/// Returns true if the sequence was already marked.
+fn mark_sequence(
+    accounts: &MarkUsedAccounts,
+    data: &InstructionData,
+    program_id: &Address,
+) -> Result<bool, ProgramError> {
+    let pda_seeds = BitmapPdaSeeds::new(data.namespace, data.sequence);
+    mark_sequence_with_seeds(accounts, &pda_seeds, data.sequence, program_id)
+}
+
+/// [`mark_sequence`] for a bucket derived from caller-built seeds.
+fn mark_sequence_with_seeds(
+    accounts: &MarkUsedAccounts,
+    pda_seeds: &BitmapPdaSeeds,
+    sequence: u64,
+    program_id: &Address,
+) -> Result<bool, ProgramError> {
+    // Initialize PDA if needed (also verifies PDA is correct)
+    init_bitmap_pda(
+        accounts.payer,
+        accounts.authority,
+        accounts.bitmap_pda,
+        accounts.system_program,
+        pda_seeds,
+        program_id,
+    )?;
+
+    // Get mutable access to bitmap data
+    // SAFETY: We have exclusive write access to the PDA data after creation/validation.
+    // The init_bitmap_pda call above ensures the account is valid and owned by us.
+    let account_data = unsafe { accounts.bitmap_pda.borrow_unchecked_mut() };
+    let mut bitmap = load_bitmap(account_data)?;
+
+    Ok(bitmap.mark_used(sequence))
+}
+
 // =============================================================================
 // Instruction Implementations
 // =============================================================================
@@ -167,6 +559,7 @@ impl CreateBitmap<'_> {
             self.accounts.payer,
             self.accounts.authority,
             self.accounts.bitmap_pda,
+            Some(self.accounts.system_program),
             &pda_seeds,
             program_id,
         )?;
@@ -180,29 +573,690 @@ impl MarkUsed<'_> {
     ///
     /// Marks a sequence number as used for replay protection. Fails if the
     /// sequence was already marked (replay detected).
+    ///
+    /// If the caller asked for it, writes `1` (newly marked) as return data.
+    /// Callers using the minimal layout don't pay for the syscall.
+    ///
+    /// Once the namespace has a config, the signer must be its current
+    /// authority rather than the authority.
     pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        // Mark sequence as used, fail if already used (replay protection)
+        if mark_sequence(&self.accounts, &self.data, program_id)? {
+            return Err(sequence_already_used());
+        }
+
+        if self.return_status {
+            set_return_data(&[1]);
+        }
+
+        Ok(())
+    }
+}
+
+impl MarkUsedIdempotent<'_> {
+    /// Process MarkUsedIdempotent instruction.
+    ///
+    /// Marks a sequence number as used, succeeding whether or not it was
+    /// already marked. Still creates the bitmap PDA if it doesn't exist.
+    ///
+    /// Writes `1` as return data if this call marked the sequence, `0` if it
+    /// was already marked, so retrying callers can tell without parsing
+    /// errors.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let was_used = mark_sequence(&self.accounts, &self.data, program_id)?;
+
+        set_return_data(&[!was_used as u8]);
+
+        Ok(())
+    }
+}
+
+impl MarkUsedDelegated<'_> {
+    /// Process MarkUsedDelegated instruction.
+    ///
+    /// Like MarkUsed, but the authority's consent comes from an ed25519
+    /// precompile instruction immediately preceding this one, signed over
+    /// this exact `(program_id, namespace, sequence)`. If an existing config
+    /// is passed, its current authority must sign instead.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        let mark = &self.accounts.mark;
+        let current_authority = match &mark.config {
+            Some(config) => config_authority(
+                mark.authority,
+                config.config,
+                self.data.namespace,
+                program_id,
+            )?,
+            None => None,
+        };
+        let signer = match &current_authority {
+            Some(current_authority) => &current_authority[..],
+            None => mark.authority.address().as_ref(),
+        };
+
+        // SAFETY: The instructions sysvar is read-only and nothing else in
+        // this instruction borrows it.
+        let sysvar = unsafe { self.accounts.instructions_sysvar.borrow_unchecked() };
+        verify_delegation(
+            sysvar,
+            program_id.as_ref(),
+            signer,
+            mark.authority.address().as_ref(),
+            self.data.namespace,
+            self.data.sequence,
+        )?;
+
+        // Mark sequence as used, fail if already used (replay protection)
+        if mark_sequence(&self.accounts.mark, &self.data, program_id)? {
+            return Err(sequence_already_used());
+        }
+
+        Ok(())
+    }
+}
+
+impl MultiBucketMark<'_> {
+    /// Process MultiBucketMark instruction.
+    ///
+    /// Marks each `(pda_index, sequence)` entry in the named bitmap PDA,
+    /// verifying (and creating if needed) that PDA against the sequence's
+    /// bucket. Fails if any sequence was already marked (replay detected).
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize_mark(
+            self.accounts.authority,
+            self.accounts.config.as_ref(),
+            self.data.namespace,
+            program_id,
+        )?;
+
+        for (pda_index, sequence) in self.data.entries() {
+            let bitmap_pda = self
+                .accounts
+                .bitmap_pdas
+                .get(pda_index)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+            let accounts = MarkUsedAccounts {
+                payer: self.accounts.payer,
+                authority: self.accounts.authority,
+                bitmap_pda,
+                system_program: Some(self.accounts.system_program),
+                config: self.accounts.config,
+            };
+            let data = InstructionData {
+                namespace: self.data.namespace,
+                sequence,
+            };
+
+            if mark_sequence(&accounts, &data, program_id)? {
+                return Err(sequence_already_used());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl VerifyBucket<'_> {
+    /// Process VerifyBucket instruction.
+    ///
+    /// Checks ownership, size, and that the stored bump derives the account's
+    /// address, then returns the [`BucketStatus`] as one byte of return data.
+    /// Never modifies the account; an unhealthy bucket is not an error.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        let status = self.status(program_id);
+        set_return_data(&[status as u8]);
+
+        Ok(())
+    }
+
+    fn status(&self, program_id: &Address) -> BucketStatus {
+        let bitmap_pda = self.accounts.bitmap_pda;
+
+        if unsafe { bitmap_pda.owner() } != program_id {
+            return BucketStatus::NotOwned;
+        }
+
+        // SAFETY: Read-only access; nothing else in this instruction borrows
+        // the account.
+        let account_data = unsafe { bitmap_pda.borrow_unchecked() };
+        if account_data.len() != BITMAP_ACCOUNT_SIZE {
+            return BucketStatus::WrongSize;
+        }
+        let Some(bitmap) = BitmapAccount::from_slice_ref(account_data) else {
+            return BucketStatus::WrongSize;
+        };
+        if check_tag(bitmap.tag).is_err() {
+            return BucketStatus::WrongFormat;
+        }
+
         let pda_seeds = BitmapPdaSeeds::new(self.data.namespace, self.data.sequence);
+        let bump_slice = [*bitmap.bump];
+        let seeds =
+            pda_seeds.as_seeds_with_bump(self.accounts.authority.address().as_ref(), &bump_slice);
+        match Address::create_program_address(&seeds, program_id) {
+            Ok(expected) if bitmap_pda.address() == &expected => BucketStatus::Healthy,
+            _ => BucketStatus::WrongAddress,
+        }
+    }
+}
+
+impl CheckUsed<'_> {
+    /// Process CheckUsed instruction.
+    ///
+    /// Writes `1` as return data if the sequence is marked, `0` otherwise. A
+    /// bucket that isn't program-owned yet has no used sequences, so callers
+    /// can CPI into this defensively before the bucket exists. An existing
+    /// bucket is verified against the seeds via its stored bump, so a wrong
+    /// account can't report a false "used".
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        let used = load_bucket_for_query(
+            self.accounts.authority,
+            self.accounts.bitmap_pda,
+            &BitmapPdaSeeds::new(self.data.namespace, self.data.sequence),
+            program_id,
+        )?
+        .is_some_and(|bitmap| bitmap.is_used(self.data.sequence));
+
+        set_return_data(&[used as u8]);
+
+        Ok(())
+    }
+}
+
+impl MarkUsedBatch<'_> {
+    /// Process MarkUsedBatch instruction.
+    ///
+    /// Initializes the shared bucket once, then marks every sequence. Fails
+    /// if any sequence was already marked; the whole batch is rolled back.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let pda_seeds = BitmapPdaSeeds::new(self.data.namespace, self.data.first_sequence());
 
         // Initialize PDA if needed (also verifies PDA is correct)
         init_bitmap_pda(
             self.accounts.payer,
             self.accounts.authority,
             self.accounts.bitmap_pda,
+            self.accounts.system_program,
             &pda_seeds,
             program_id,
         )?;
 
-        // Get mutable access to bitmap data
         // SAFETY: We have exclusive write access to the PDA data after creation/validation.
         // The init_bitmap_pda call above ensures the account is valid and owned by us.
         let account_data = unsafe { self.accounts.bitmap_pda.borrow_unchecked_mut() };
-        let mut bitmap =
-            BitmapAccount::from_slice(account_data).ok_or(ProgramError::AccountDataTooSmall)?;
+        let mut bitmap = load_bitmap(account_data)?;
+
+        // Parsing guarantees every sequence maps to this PDA's bucket
+        for sequence in self.data.sequences() {
+            if bitmap.mark_used(sequence) {
+                return Err(sequence_already_used());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MarkUsedRange<'_> {
+    /// Process MarkUsedRange instruction.
+    ///
+    /// Marks every sequence in `start..=end` in a single bitmap pass. Fails
+    /// without marking anything if any of them was already marked.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let pda_seeds = BitmapPdaSeeds::new(self.data.namespace, self.data.start);
+
+        // Initialize PDA if needed (also verifies PDA is correct)
+        init_bitmap_pda(
+            self.accounts.payer,
+            self.accounts.authority,
+            self.accounts.bitmap_pda,
+            self.accounts.system_program,
+            &pda_seeds,
+            program_id,
+        )?;
+
+        // SAFETY: We have exclusive write access to the PDA data after creation/validation.
+        // The init_bitmap_pda call above ensures the account is valid and owned by us.
+        let account_data = unsafe { self.accounts.bitmap_pda.borrow_unchecked_mut() };
+        let mut bitmap = load_bitmap(account_data)?;
+
+        let count = self.data.end - self.data.start + 1;
+        match bitmap.mark_range(self.data.start, count) {
+            Ok(()) => Ok(()),
+            Err(RangeError::AlreadyUsed(_)) => Err(sequence_already_used()),
+            // Parsing already rejects ranges crossing a bucket boundary
+            Err(RangeError::CrossesBucket) => Err(NoReplayError::InvalidBucket.into()),
+        }
+    }
+}
+
+impl RangeAnyUsed<'_> {
+    /// Process RangeAnyUsed instruction.
+    ///
+    /// Writes `1` as return data if any sequence in the run is marked, `0`
+    /// otherwise. Like CheckUsed, a bucket that doesn't exist yet reports `0`.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        let used = match load_bucket_for_query(
+            self.accounts.authority,
+            self.accounts.bitmap_pda,
+            &BitmapPdaSeeds::new(self.data.namespace, self.data.start),
+            program_id,
+        )? {
+            // Parsing already rejects runs crossing a bucket boundary
+            Some(bitmap) => bitmap
+                .any_used(self.data.start, self.data.count)
+                .map_err(|_| ProgramError::from(NoReplayError::InvalidBucket))?,
+            None => false,
+        };
+
+        set_return_data(&[used as u8]);
+
+        Ok(())
+    }
+}
+
+impl CloseBitmap<'_> {
+    /// Process CloseBitmap instruction.
+    ///
+    /// Requires the same signature as a mark in the namespace, verifies the
+    /// bucket against the seeds via its stored bump, requires every sequence
+    /// in it to be marked, then zeroes the data and moves all lamports to the
+    /// recipient. The runtime reaps the emptied account, so
+    /// the bucket can later be recreated from scratch, with none of its
+    /// sequences marked.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize_mark(
+            self.accounts.authority,
+            self.accounts.config.as_ref(),
+            self.data.namespace,
+            program_id,
+        )?;
+
+        let bitmap_pda = self.accounts.bitmap_pda;
+
+        if unsafe { bitmap_pda.owner() } != program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        // SAFETY: We have exclusive write access to the PDA data after owner validation.
+        let account_data = unsafe { bitmap_pda.borrow_unchecked_mut() };
+        let bitmap = load_bitmap(account_data)?;
+
+        let pda_seeds = BitmapPdaSeeds::new(self.data.namespace, self.data.sequence);
+        let bump_slice = [*bitmap.bump];
+        let seeds =
+            pda_seeds.as_seeds_with_bump(self.accounts.authority.address().as_ref(), &bump_slice);
+        let expected_pda = Address::create_program_address(&seeds, program_id)
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+
+        check_bitmap_address(bitmap_pda, &expected_pda)?;
+
+        // Closing a bucket with an unused sequence would re-admit it later
+        if bitmap.count_used() as u64 != BITS_PER_BUCKET {
+            return Err(NoReplayError::BucketNotFullyUsed.into());
+        }
+
+        account_data.fill(0);
+
+        let recipient = self.accounts.recipient;
+        let lamports = recipient
+            .lamports()
+            .checked_add(bitmap_pda.lamports())
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        recipient.set_lamports(lamports);
+
+        // Zeroes lamports, data length and owner
+        bitmap_pda.close()
+    }
+}
+
+impl MarkUsedDomain<'_> {
+    /// Process MarkUsedDomain instruction.
+    ///
+    /// Same as MarkUsed, but the bucket is derived in the domain's PDA space.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let pda_seeds =
+            BitmapPdaSeeds::new_with_domain(self.domain, self.data.namespace, self.data.sequence);
 
         // Mark sequence as used, fail if already used (replay protection)
-        if bitmap.mark_used(self.data.sequence) {
+        if mark_sequence_with_seeds(&self.accounts, &pda_seeds, self.data.sequence, program_id)? {
+            return Err(sequence_already_used());
+        }
+
+        Ok(())
+    }
+}
+
+impl MarkAbove<'_> {
+    /// Process MarkAbove instruction.
+    ///
+    /// Raises the namespace's watermark to the sequence, creating the
+    /// watermark PDA if needed. Fails if the sequence is at or below the
+    /// watermark, so any replay or out-of-order sequence is rejected.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let pda_seeds = NamespacePdaSeeds::watermark(self.data.namespace);
+        let accounts = &self.accounts;
+        let watermark_pda = accounts.bitmap_pda;
+        let authority = accounts.authority.address();
+
+        if unsafe { watermark_pda.owner() } != program_id {
+            let bump = create_namespace_pda(
+                accounts.payer,
+                authority,
+                watermark_pda,
+                accounts.system_program,
+                &pda_seeds,
+                WATERMARK_ACCOUNT_SIZE,
+                program_id,
+            )?;
+
+            // SAFETY: The account was just created by this program, so no
+            // other references exist.
+            let account_data = unsafe { watermark_pda.borrow_unchecked_mut() };
+            WatermarkAccount::init(account_data, bump)
+                .ok_or(NoReplayError::MalformedBitmapAccount)?;
+        }
+
+        // SAFETY: We have exclusive write access to the PDA data after owner validation.
+        let account_data = unsafe { watermark_pda.borrow_unchecked_mut() };
+        let mut watermark = WatermarkAccount::from_slice(account_data)
+            .ok_or(NoReplayError::MalformedBitmapAccount)?;
+        check_watermark_tag(watermark.tag).map_err(NoReplayError::from)?;
+        check_namespace_pda(
+            authority,
+            watermark_pda,
+            &pda_seeds,
+            *watermark.bump,
+            program_id,
+        )?;
+
+        if watermark.mark_above(self.data.sequence) {
+            return Err(sequence_already_used());
+        }
+
+        Ok(())
+    }
+}
+
+impl MarkWindowed<'_> {
+    /// Process MarkWindowed instruction.
+    ///
+    /// Marks the sequence in the namespace's window, creating the window PDA
+    /// if needed. A new highest sequence slides the window forward.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let pda_seeds = NamespacePdaSeeds::window(self.data.namespace);
+        let accounts = &self.accounts;
+        let window_pda = accounts.bitmap_pda;
+        let authority = accounts.authority.address();
+
+        if unsafe { window_pda.owner() } != program_id {
+            let bump = create_namespace_pda(
+                accounts.payer,
+                authority,
+                window_pda,
+                accounts.system_program,
+                &pda_seeds,
+                WINDOW_ACCOUNT_SIZE,
+                program_id,
+            )?;
+
+            // SAFETY: The account was just created by this program, so no
+            // other references exist.
+            let account_data = unsafe { window_pda.borrow_unchecked_mut() };
+            WindowAccount::init(account_data, bump).ok_or(NoReplayError::MalformedBitmapAccount)?;
+        }
+
+        // SAFETY: We have exclusive write access to the PDA data after owner validation.
+        let account_data = unsafe { window_pda.borrow_unchecked_mut() };
+        let mut window =
+            WindowAccount::from_slice(account_data).ok_or(NoReplayError::MalformedBitmapAccount)?;
+        check_window_tag(window.tag).map_err(NoReplayError::from)?;
+        check_namespace_pda(authority, window_pda, &pda_seeds, *window.bump, program_id)?;
+
+        match window.mark(self.data.sequence) {
+            Ok(()) => Ok(()),
+            Err(WindowError::AlreadyUsed) => Err(sequence_already_used()),
+            Err(WindowError::TooOld) => Err(NoReplayError::SequenceTooOld.into()),
+        }
+    }
+}
+
+impl MarkUsedCommitted<'_> {
+    /// Process MarkUsedCommitted instruction.
+    ///
+    /// Same as MarkUsed in the committed bucket space, creating the bucket
+    /// if needed. A newly marked sequence also folds the message hash into
+    /// the bucket's accumulator; a replay changes nothing.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let pda_seeds = BitmapPdaSeeds::new_committed(self.data.namespace, self.data.sequence);
+        let accounts = &self.accounts;
+        let bucket_pda = accounts.bitmap_pda;
+        let authority = accounts.authority.address();
+
+        if unsafe { bucket_pda.owner() } != program_id {
+            check_system_program(accounts.system_program)?;
+
+            #[cfg(feature = "max-bucket")]
+            if pda_seeds.bucket_index_u128() > crate::state::MAX_BUCKET_INDEX as u128 {
+                return Err(NoReplayError::BucketIndexTooLarge.into());
+            }
+
+            // Canonical bump only, for the same reason as init_bitmap_pda
+            let (expected_pda, bump) = pda_seeds.find_pda(authority, program_id);
+            check_bitmap_address(bucket_pda, &expected_pda)?;
+
+            let bump_seed = [bump];
+            let signer_seeds = build_signer(authority.as_ref(), &pda_seeds, &bump_seed);
+            let signers = [Signer::from(signer_seeds.as_ref())];
+            create_pda(
+                accounts.payer,
+                bucket_pda,
+                program_id,
+                COMMITTED_ACCOUNT_SIZE as u64,
+                &signers,
+            )?;
+
+            // SAFETY: The account was just created by this program, so no
+            // other references exist.
+            let account_data = unsafe { bucket_pda.borrow_unchecked_mut() };
+            CommittedBitmapAccount::init(account_data, bump)
+                .ok_or(NoReplayError::MalformedBitmapAccount)?;
+        }
+
+        // SAFETY: We have exclusive write access to the PDA data after owner validation.
+        let account_data = unsafe { bucket_pda.borrow_unchecked_mut() };
+        if account_data.len() > COMMITTED_ACCOUNT_SIZE {
+            return Err(NoReplayError::AccountSizeMismatch.into());
+        }
+        let mut bucket = CommittedBitmapAccount::from_slice(account_data)
+            .ok_or(NoReplayError::MalformedBitmapAccount)?;
+        check_committed_tag(bucket.tag).map_err(NoReplayError::from)?;
+
+        // Every committed bucket is program-owned, so bind this one to the
+        // seeds via its stored bump, as init_bitmap_pda does
+        let bump_slice = [*bucket.bump];
+        let seeds = pda_seeds.as_seeds_with_bump(authority.as_ref(), &bump_slice);
+        let expected_pda = Address::create_program_address(&seeds, program_id)
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+        check_bitmap_address(bucket_pda, &expected_pda)?;
+
+        if bucket.commit(self.data.sequence, self.message_hash) {
+            return Err(sequence_already_used());
+        }
+
+        Ok(())
+    }
+}
+
+impl MigrateBucket<'_> {
+    /// Process MigrateBucket instruction.
+    ///
+    /// Verifies the legacy bucket against the seeds via its stored bump,
+    /// tops its lamports up to the new rent-exempt minimum from the payer,
+    /// grows it and rewrites it as `[tag][bump][bitmap]`. A bucket already in
+    /// the current layout fails with `AccountAlreadyInitialized`.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        let accounts = &self.accounts;
+        let bitmap_pda = accounts.bitmap_pda;
+
+        if unsafe { bitmap_pda.owner() } != program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        match bitmap_pda.data_len() {
+            LEGACY_BITMAP_ACCOUNT_SIZE => {}
+            BITMAP_ACCOUNT_SIZE => return Err(ProgramError::AccountAlreadyInitialized),
+            _ => return Err(NoReplayError::AccountSizeMismatch.into()),
+        }
+
+        // SAFETY: Read-only access; nothing else in this instruction borrows
+        // the account.
+        let bump = unsafe { bitmap_pda.borrow_unchecked() }[0];
+        let pda_seeds = BitmapPdaSeeds::new(self.data.namespace, self.data.sequence);
+        let bump_slice = [bump];
+        let seeds =
+            pda_seeds.as_seeds_with_bump(accounts.authority.address().as_ref(), &bump_slice);
+        let expected_pda = Address::create_program_address(&seeds, program_id)
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+        check_bitmap_address(bitmap_pda, &expected_pda)?;
+
+        check_system_program(Some(accounts.system_program))?;
+
+        // Same rent source as create_pda
+        let required_lamports = CreateAccount::with_minimum_balance(
+            accounts.payer,
+            bitmap_pda,
+            BITMAP_ACCOUNT_SIZE as u64,
+            program_id,
+            None,
+        )?
+        .lamports;
+        let current_lamports = bitmap_pda.lamports();
+        if current_lamports < required_lamports {
+            Transfer {
+                from: accounts.payer,
+                to: bitmap_pda,
+                lamports: required_lamports - current_lamports,
+            }
+            .invoke()?;
+        }
+
+        bitmap_pda.resize(BITMAP_ACCOUNT_SIZE)?;
+
+        // SAFETY: We have exclusive write access to the PDA data after owner
+        // validation; the read borrow above has ended.
+        let account_data = unsafe { bitmap_pda.borrow_unchecked_mut() };
+        // Shift the bitmap past the new tag, then write the tag and bump
+        account_data.copy_within(1..LEGACY_BITMAP_ACCOUNT_SIZE, BITMAP_TAG_LEN + 1);
+        BitmapAccount::init(account_data, bump).ok_or(NoReplayError::MalformedBitmapAccount)?;
+
+        Ok(())
+    }
+}
+
+impl MarkUsedU128<'_> {
+    /// Process MarkUsedU128 instruction.
+    ///
+    /// Same as MarkUsed for a u128 sequence, in the bucket derived by
+    /// [`BitmapPdaSeeds::new_u128`].
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        authorize(&self.accounts, self.data.namespace, program_id)?;
+
+        let pda_seeds = BitmapPdaSeeds::new_u128(self.data.namespace, self.data.sequence);
+
+        // Initialize PDA if needed (also verifies PDA is correct)
+        init_bitmap_pda(
+            self.accounts.payer,
+            self.accounts.authority,
+            self.accounts.bitmap_pda,
+            self.accounts.system_program,
+            &pda_seeds,
+            program_id,
+        )?;
+
+        // SAFETY: We have exclusive write access to the PDA data after creation/validation.
+        // The init_bitmap_pda call above ensures the account is valid and owned by us.
+        let account_data = unsafe { self.accounts.bitmap_pda.borrow_unchecked_mut() };
+        let mut bitmap = load_bitmap(account_data)?;
+
+        // The bit index only depends on the sequence modulo the bucket size
+        let bit_sequence = (self.data.sequence % BITS_PER_BUCKET as u128) as u64;
+        if bitmap.mark_used(bit_sequence) {
+            return Err(sequence_already_used());
+        }
+
+        Ok(())
+    }
+}
+
+impl InitConfig<'_> {
+    /// Process InitConfig instruction.
+    ///
+    /// Creates the namespace config PDA and records the new authority. Fails
+    /// with `AccountAlreadyInitialized` if the config exists.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        let pda_seeds = NamespacePdaSeeds::config(self.data.namespace);
+        let accounts = &self.accounts;
+        let authority = accounts.authority.address();
+
+        if unsafe { accounts.config.owner() } == program_id {
             return Err(ProgramError::AccountAlreadyInitialized);
         }
+        let bump = create_namespace_pda(
+            accounts.payer,
+            authority,
+            accounts.config,
+            Some(accounts.system_program),
+            &pda_seeds,
+            CONFIG_ACCOUNT_SIZE,
+            program_id,
+        )?;
+
+        // SAFETY: The account was just created by this program, so no
+        // other references exist.
+        let account_data = unsafe { accounts.config.borrow_unchecked_mut() };
+        ConfigAccount::init(account_data, bump, self.data.new_authority)
+            .ok_or(NoReplayError::MalformedBitmapAccount)?;
+
+        Ok(())
+    }
+}
+
+impl SetAuthority<'_> {
+    /// Process SetAuthority instruction.
+    ///
+    /// Replaces the config's current authority. Only the current authority
+    /// may do so; the seed authority has no say once it has handed over.
+    pub fn process(&self, program_id: &Address) -> ProgramResult {
+        let accounts = &self.accounts;
+        let config = load_config(
+            accounts.authority,
+            accounts.config,
+            self.data.namespace,
+            program_id,
+        )?;
+        if accounts.current_authority.address().as_ref() != &config.current_authority[..] {
+            return Err(NoReplayError::AuthorityMismatch.into());
+        }
+
+        *config.current_authority = *self.data.new_authority;
 
         Ok(())
     }
@@ -2,48 +2,880 @@
 pub const BITMAP_BYTES: usize = 128;
 /// Bits per bitmap bucket (derived from BITMAP_BYTES)
 pub const BITS_PER_BUCKET: u64 = (BITMAP_BYTES * 8) as u64;
-/// Total account size: [bump: u8][bitmap: 128 bytes] = 129 bytes
-pub const BITMAP_ACCOUNT_SIZE: usize = 1 + BITMAP_BYTES;
+/// Identifies NoReplay bitmap accounts; the first 7 bytes of [`BITMAP_TAG`].
+pub const BITMAP_MAGIC: [u8; 7] = *b"NOREPLY";
+/// Account format version, the last byte of [`BITMAP_TAG`]. Bump it when the
+/// layout after the tag changes, so old and new accounts can be told apart.
+pub const BITMAP_FORMAT_VERSION: u8 = 1;
+/// Size of the leading magic/version tag.
+pub const BITMAP_TAG_LEN: usize = 8;
+/// Tag at offset 0 of every bitmap account: [`BITMAP_MAGIC`] then
+/// [`BITMAP_FORMAT_VERSION`]. Off-chain indexers can `memcmp` on it to
+/// select bitmap accounts among everything the program owns.
+pub const BITMAP_TAG: [u8; BITMAP_TAG_LEN] = {
+    let mut tag = [0u8; BITMAP_TAG_LEN];
+    let mut i = 0;
+    while i < BITMAP_MAGIC.len() {
+        tag[i] = BITMAP_MAGIC[i];
+        i += 1;
+    }
+    tag[BITMAP_TAG_LEN - 1] = BITMAP_FORMAT_VERSION;
+    tag
+};
+/// Total account size: [tag: 8 bytes][bump: u8][bitmap: 128 bytes] = 137 bytes
+pub const BITMAP_ACCOUNT_SIZE: usize = BITMAP_TAG_LEN + 1 + BITMAP_BYTES;
+
+/// Size of a bucket created before the tag was introduced:
+/// [bump: u8][bitmap: 128 bytes] = 129 bytes. Same bucket indexing as the
+/// current layout, so MigrateBucket can rewrite it in place.
+pub const LEGACY_BITMAP_ACCOUNT_SIZE: usize = 1 + BITMAP_BYTES;
+
+/// Identifies NoReplay watermark accounts; the first 7 bytes of [`WATERMARK_TAG`].
+pub const WATERMARK_MAGIC: [u8; 7] = *b"NOREPWM";
+/// Watermark account format version, the last byte of [`WATERMARK_TAG`].
+pub const WATERMARK_FORMAT_VERSION: u8 = 1;
+/// Tag at offset 0 of every watermark account: [`WATERMARK_MAGIC`] then
+/// [`WATERMARK_FORMAT_VERSION`].
+pub const WATERMARK_TAG: [u8; BITMAP_TAG_LEN] = {
+    let mut tag = [0u8; BITMAP_TAG_LEN];
+    let mut i = 0;
+    while i < WATERMARK_MAGIC.len() {
+        tag[i] = WATERMARK_MAGIC[i];
+        i += 1;
+    }
+    tag[BITMAP_TAG_LEN - 1] = WATERMARK_FORMAT_VERSION;
+    tag
+};
+/// Total watermark account size:
+/// [tag: 8 bytes][bump: u8][is_set: u8][watermark: u64 LE] = 18 bytes
+pub const WATERMARK_ACCOUNT_SIZE: usize = BITMAP_TAG_LEN + 1 + 1 + 8;
+
+/// Identifies NoReplay sliding-window accounts; the first 7 bytes of [`WINDOW_TAG`].
+pub const WINDOW_MAGIC: [u8; 7] = *b"NOREPWN";
+/// Window account format version, the last byte of [`WINDOW_TAG`].
+pub const WINDOW_FORMAT_VERSION: u8 = 1;
+/// Tag at offset 0 of every window account: [`WINDOW_MAGIC`] then
+/// [`WINDOW_FORMAT_VERSION`].
+pub const WINDOW_TAG: [u8; BITMAP_TAG_LEN] = {
+    let mut tag = [0u8; BITMAP_TAG_LEN];
+    let mut i = 0;
+    while i < WINDOW_MAGIC.len() {
+        tag[i] = WINDOW_MAGIC[i];
+        i += 1;
+    }
+    tag[BITMAP_TAG_LEN - 1] = WINDOW_FORMAT_VERSION;
+    tag
+};
+/// Size of a window account's bitmap in bytes.
+pub const WINDOW_BYTES: usize = 128;
+/// Sequences tracked below and including the highest one marked.
+pub const WINDOW_BITS: u64 = (WINDOW_BYTES * 8) as u64;
+/// Total window account size:
+/// [tag: 8 bytes][bump: u8][is_set: u8][highest: u64 LE][window: 128 bytes] = 146 bytes
+pub const WINDOW_ACCOUNT_SIZE: usize = BITMAP_TAG_LEN + 1 + 1 + 8 + WINDOW_BYTES;
+
+/// Identifies NoReplay namespace config accounts; the first 7 bytes of [`CONFIG_TAG`].
+pub const CONFIG_MAGIC: [u8; 7] = *b"NOREPCF";
+/// Config account format version, the last byte of [`CONFIG_TAG`].
+pub const CONFIG_FORMAT_VERSION: u8 = 1;
+/// Tag at offset 0 of every namespace config account: [`CONFIG_MAGIC`] then
+/// [`CONFIG_FORMAT_VERSION`].
+pub const CONFIG_TAG: [u8; BITMAP_TAG_LEN] = {
+    let mut tag = [0u8; BITMAP_TAG_LEN];
+    let mut i = 0;
+    while i < CONFIG_MAGIC.len() {
+        tag[i] = CONFIG_MAGIC[i];
+        i += 1;
+    }
+    tag[BITMAP_TAG_LEN - 1] = CONFIG_FORMAT_VERSION;
+    tag
+};
+/// Total config account size:
+/// [tag: 8 bytes][bump: u8][current_authority: 32 bytes] = 41 bytes
+pub const CONFIG_ACCOUNT_SIZE: usize = BITMAP_TAG_LEN + 1 + 32;
+
+/// Identifies NoReplay committed buckets; the first 7 bytes of [`COMMITTED_TAG`].
+pub const COMMITTED_MAGIC: [u8; 7] = *b"NOREPCB";
+/// Committed bucket format version, the last byte of [`COMMITTED_TAG`].
+pub const COMMITTED_FORMAT_VERSION: u8 = 1;
+/// Tag at offset 0 of every committed bucket: [`COMMITTED_MAGIC`] then
+/// [`COMMITTED_FORMAT_VERSION`].
+pub const COMMITTED_TAG: [u8; BITMAP_TAG_LEN] = {
+    let mut tag = [0u8; BITMAP_TAG_LEN];
+    let mut i = 0;
+    while i < COMMITTED_MAGIC.len() {
+        tag[i] = COMMITTED_MAGIC[i];
+        i += 1;
+    }
+    tag[BITMAP_TAG_LEN - 1] = COMMITTED_FORMAT_VERSION;
+    tag
+};
+/// Size of a committed bucket's accumulator (a sha256 digest).
+pub const ACCUMULATOR_BYTES: usize = 32;
+/// Total committed bucket size:
+/// [tag: 8 bytes][bump: u8][accumulator: 32 bytes][bitmap: 128 bytes] = 169 bytes
+pub const COMMITTED_ACCOUNT_SIZE: usize = BITMAP_TAG_LEN + 1 + ACCUMULATOR_BYTES + BITMAP_BYTES;
+
+/// Highest bucket index a deployment built with the `max-bucket` feature will
+/// create. Bounds the sequence space to `(MAX_BUCKET_INDEX + 1) * BITS_PER_BUCKET`
+/// sequences; edit before building to suit the deployment.
+pub const MAX_BUCKET_INDEX: u64 = u32::MAX as u64;
+
+/// Bucket index containing `sequence`.
+#[inline]
+pub const fn bucket_of(sequence: u64) -> u64 {
+    sequence / BITS_PER_BUCKET
+}
+
+/// Bucket index containing a u128 `sequence`.
+#[inline]
+pub const fn bucket_of_u128(sequence: u128) -> u128 {
+    sequence / BITS_PER_BUCKET as u128
+}
+
+/// Bit index of `sequence` within its bucket.
+#[inline]
+pub const fn bit_of(sequence: u64) -> usize {
+    (sequence % BITS_PER_BUCKET) as usize
+}
+
+/// Position of `sequence`'s bit in its bucket's bitmap, as `(byte index,
+/// bit offset)`: the bit is `bitmap[byte] & (1 << offset)`.
+#[inline]
+pub const fn bit_position(sequence: u64) -> (usize, u8) {
+    bit_position_in::<BITMAP_BYTES>(sequence)
+}
+
+/// Account layout as `(field, offset, size)` for tooling and IDL generators.
+/// Keep in sync with [`BitmapAccount::from_slice`].
+pub const BITMAP_LAYOUT: &[(&str, usize, usize)] = &[
+    ("tag", 0, BITMAP_TAG_LEN),
+    ("bump", BITMAP_TAG_LEN, 1),
+    ("bitmap", BITMAP_TAG_LEN + 1, BITMAP_BYTES),
+];
+
+/// Watermark account layout, see [`BITMAP_LAYOUT`].
+/// Keep in sync with [`WatermarkAccount::from_slice`].
+pub const WATERMARK_LAYOUT: &[(&str, usize, usize)] = &[
+    ("tag", 0, BITMAP_TAG_LEN),
+    ("bump", BITMAP_TAG_LEN, 1),
+    ("is_set", BITMAP_TAG_LEN + 1, 1),
+    ("watermark", BITMAP_TAG_LEN + 2, 8),
+];
+
+/// Window account layout, see [`BITMAP_LAYOUT`].
+/// Keep in sync with [`WindowAccount::from_slice`].
+pub const WINDOW_LAYOUT: &[(&str, usize, usize)] = &[
+    ("tag", 0, BITMAP_TAG_LEN),
+    ("bump", BITMAP_TAG_LEN, 1),
+    ("is_set", BITMAP_TAG_LEN + 1, 1),
+    ("highest", BITMAP_TAG_LEN + 2, 8),
+    ("window", BITMAP_TAG_LEN + 10, WINDOW_BYTES),
+];
+
+/// Namespace config account layout, see [`BITMAP_LAYOUT`].
+/// Keep in sync with [`ConfigAccount::from_slice`].
+pub const CONFIG_LAYOUT: &[(&str, usize, usize)] = &[
+    ("tag", 0, BITMAP_TAG_LEN),
+    ("bump", BITMAP_TAG_LEN, 1),
+    ("current_authority", BITMAP_TAG_LEN + 1, 32),
+];
+
+/// Committed bucket layout, see [`BITMAP_LAYOUT`].
+/// Keep in sync with [`CommittedBitmapAccount::from_slice`].
+pub const COMMITTED_LAYOUT: &[(&str, usize, usize)] = &[
+    ("tag", 0, BITMAP_TAG_LEN),
+    ("bump", BITMAP_TAG_LEN, 1),
+    ("accumulator", BITMAP_TAG_LEN + 1, ACCUMULATOR_BYTES),
+    (
+        "bitmap",
+        BITMAP_TAG_LEN + 1 + ACCUMULATOR_BYTES,
+        BITMAP_BYTES,
+    ),
+];
+
+/// Result of a VerifyBucket health check, written as one byte of return data.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketStatus {
+    /// Program-owned, correctly sized, and the stored bump derives the address.
+    Healthy = 0,
+    /// Not owned by the program (not created yet, or a foreign account).
+    NotOwned = 1,
+    /// Program-owned but not exactly `BITMAP_ACCOUNT_SIZE` bytes (truncated,
+    /// or oversized like a bucket MarkUsed rejects with `AccountSizeMismatch`).
+    WrongSize = 2,
+    /// The stored bump does not derive this address from the given seeds.
+    WrongAddress = 3,
+    /// Correctly sized but without the current [`BITMAP_TAG`].
+    WrongFormat = 4,
+}
+
+impl TryFrom<u8> for BucketStatus {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Healthy),
+            1 => Ok(Self::NotOwned),
+            2 => Ok(Self::WrongSize),
+            3 => Ok(Self::WrongAddress),
+            4 => Ok(Self::WrongFormat),
+            other => Err(other),
+        }
+    }
+}
+
+/// Why an account's tag is not the current [`BITMAP_TAG`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagError {
+    /// The magic doesn't match: not a bitmap account (or never initialized).
+    NotABitmap,
+    /// A bitmap account of a format version this build can't read.
+    UnsupportedVersion(u8),
+}
+
+/// Check a stored tag against the current [`BITMAP_TAG`].
+#[inline]
+pub fn check_tag(tag: &[u8; BITMAP_TAG_LEN]) -> Result<(), TagError> {
+    check_tag_against(tag, &BITMAP_TAG)
+}
+
+/// Check a stored tag against the current [`WATERMARK_TAG`]. A mismatched
+/// magic is reported as [`TagError::NotABitmap`].
+#[inline]
+pub fn check_watermark_tag(tag: &[u8; BITMAP_TAG_LEN]) -> Result<(), TagError> {
+    check_tag_against(tag, &WATERMARK_TAG)
+}
+
+/// Check a stored tag against the current [`WINDOW_TAG`]. A mismatched
+/// magic is reported as [`TagError::NotABitmap`].
+#[inline]
+pub fn check_window_tag(tag: &[u8; BITMAP_TAG_LEN]) -> Result<(), TagError> {
+    check_tag_against(tag, &WINDOW_TAG)
+}
+
+/// Check a stored tag against the current [`CONFIG_TAG`]. A mismatched
+/// magic is reported as [`TagError::NotABitmap`].
+#[inline]
+pub fn check_config_tag(tag: &[u8; BITMAP_TAG_LEN]) -> Result<(), TagError> {
+    check_tag_against(tag, &CONFIG_TAG)
+}
+
+/// Check a stored tag against the current [`COMMITTED_TAG`]. A mismatched
+/// magic is reported as [`TagError::NotABitmap`].
+#[inline]
+pub fn check_committed_tag(tag: &[u8; BITMAP_TAG_LEN]) -> Result<(), TagError> {
+    check_tag_against(tag, &COMMITTED_TAG)
+}
+
+#[inline]
+fn check_tag_against(
+    tag: &[u8; BITMAP_TAG_LEN],
+    expected: &[u8; BITMAP_TAG_LEN],
+) -> Result<(), TagError> {
+    let (magic, version) = tag.split_at(BITMAP_TAG_LEN - 1);
+    if magic != &expected[..BITMAP_TAG_LEN - 1] {
+        return Err(TagError::NotABitmap);
+    }
+    match version[0] {
+        v if v == expected[BITMAP_TAG_LEN - 1] => Ok(()),
+        v => Err(TagError::UnsupportedVersion(v)),
+    }
+}
+
+/// Error returned by [`BitmapAccount::mark_range`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeError {
+    /// The range extends past the end of the bucket containing `start`.
+    CrossesBucket,
+    /// The range contains a sequence that was already used (the first one found).
+    AlreadyUsed(u64),
+}
+
+/// Bit index of `sequence` within a bucket of `BYTES * 8` bits.
+#[inline]
+const fn bit_in<const BYTES: usize>(sequence: u64) -> usize {
+    (sequence % (BYTES as u64 * 8)) as usize
+}
+
+/// [`bit_position`] within a bucket of `BYTES * 8` bits.
+#[inline]
+const fn bit_position_in<const BYTES: usize>(sequence: u64) -> (usize, u8) {
+    let bit_index = bit_in::<BYTES>(sequence);
+    (bit_index / 8, (bit_index % 8) as u8)
+}
+
+/// Whether the bit for `sequence` is set in `bitmap`.
+#[inline]
+fn is_bit_set<const BYTES: usize>(bitmap: &[u8; BYTES], sequence: u64) -> bool {
+    let (byte_index, bit_offset) = bit_position_in::<BYTES>(sequence);
+    bitmap[byte_index] & (1 << bit_offset) != 0
+}
+
+/// Global sequences of the set bits in `bitmap`, ascending, for the bucket
+/// starting at `base`.
+#[inline]
+fn iter_set_bits<const BYTES: usize>(
+    bitmap: &[u8; BYTES],
+    base: u64,
+) -> impl Iterator<Item = u64> + '_ {
+    bitmap
+        .iter()
+        .enumerate()
+        .flat_map(move |(byte_index, &byte)| {
+            (0..8usize)
+                .filter(move |bit| byte & (1 << bit) != 0)
+                .map(move |bit| base + (byte_index * 8 + bit) as u64)
+        })
+}
 
 /// Zero-copy wrapper for bitmap account data.
-/// Layout: [bump: u8][bitmap: 128 bytes]
-pub struct BitmapAccount<'a> {
+/// Layout: [tag: 8 bytes][bump: u8][bitmap: BYTES bytes]
+///
+/// `BYTES` is the bitmap size, so a bucket holds `BYTES * 8` sequences. The
+/// program only uses the default [`BITMAP_BYTES`]; other sizes are for
+/// off-chain tooling modelling alternative layouts. Wrapping doesn't check
+/// the tag; see [`check_tag`].
+pub struct BitmapAccount<'a, const BYTES: usize = BITMAP_BYTES> {
+    pub tag: &'a mut [u8; BITMAP_TAG_LEN],
     pub bump: &'a mut u8,
-    pub bitmap: &'a mut [u8; BITMAP_BYTES],
+    pub bitmap: &'a mut [u8; BYTES],
 }
 
 impl<'a> BitmapAccount<'a> {
+    /// Wrap account data for read-only access. Returns None if data is too small.
+    #[inline]
+    pub fn from_slice_ref(data: &'a [u8]) -> Option<BitmapAccountRef<'a>> {
+        BitmapAccountRef::from_slice(data)
+    }
+
     /// Wrap account data. Returns None if data is too small.
     #[inline]
     pub fn from_slice(data: &'a mut [u8]) -> Option<Self> {
-        if data.len() < BITMAP_ACCOUNT_SIZE {
+        Self::from_slice_sized(data)
+    }
+
+    /// Wrap freshly allocated account data, writing the current tag and
+    /// `bump`. Returns None if data is too small.
+    #[inline]
+    pub fn init(data: &'a mut [u8], bump: u8) -> Option<Self> {
+        Self::init_sized(data, bump)
+    }
+}
+
+impl<'a, const BYTES: usize> BitmapAccount<'a, BYTES> {
+    /// Sequences per bucket for this bitmap size.
+    pub const BITS: u64 = BYTES as u64 * 8;
+    /// Account size for this bitmap size.
+    pub const ACCOUNT_SIZE: usize = BITMAP_TAG_LEN + 1 + BYTES;
+
+    /// [`BitmapAccount::from_slice`] for an explicit bitmap size.
+    #[inline]
+    pub fn from_slice_sized(data: &'a mut [u8]) -> Option<Self> {
+        if data.len() < Self::ACCOUNT_SIZE {
             return None;
         }
-        let (bump, rest) = data.split_at_mut(1);
-        let bitmap = <&mut [u8; BITMAP_BYTES]>::try_from(&mut rest[..BITMAP_BYTES]).ok()?;
+        let (tag, rest) = data.split_at_mut(BITMAP_TAG_LEN);
+        let (bump, rest) = rest.split_at_mut(1);
+        let tag = <&mut [u8; BITMAP_TAG_LEN]>::try_from(tag).ok()?;
+        let bitmap = <&mut [u8; BYTES]>::try_from(&mut rest[..BYTES]).ok()?;
         Some(Self {
+            tag,
             bump: &mut bump[0],
             bitmap,
         })
     }
 
+    /// [`BitmapAccount::init`] for an explicit bitmap size.
+    #[inline]
+    pub fn init_sized(data: &'a mut [u8], bump: u8) -> Option<Self> {
+        let bitmap = Self::from_slice_sized(data)?;
+        *bitmap.tag = BITMAP_TAG;
+        *bitmap.bump = bump;
+        Some(bitmap)
+    }
+
     /// Check if a sequence number is marked as used.
     #[inline]
     pub fn is_used(&self, sequence: u64) -> bool {
-        let bit_index = (sequence % BITS_PER_BUCKET) as usize;
-        let byte_index = bit_index / 8;
-        let bit_offset = bit_index % 8;
-        self.bitmap[byte_index] & (1 << bit_offset) != 0
+        is_bit_set(self.bitmap, sequence)
     }
 
     /// Mark a sequence number as used. Returns true if it was already used.
     #[inline]
     pub fn mark_used(&mut self, sequence: u64) -> bool {
         let was_used = self.is_used(sequence);
-        let bit_index = (sequence % BITS_PER_BUCKET) as usize;
-        let byte_index = bit_index / 8;
-        let bit_offset = bit_index % 8;
+        let (byte_index, bit_offset) = bit_position_in::<BYTES>(sequence);
         self.bitmap[byte_index] |= 1 << bit_offset;
         was_used
     }
+
+    /// First unused sequence in this bucket, or `None` if it is full.
+    ///
+    /// The account doesn't record which bucket it is, so the caller passes
+    /// `bucket_index` to turn the bit index back into a global sequence.
+    #[inline]
+    pub fn first_unused_in_bucket(&self, bucket_index: u64) -> Option<u64> {
+        let (byte_index, byte) = self
+            .bitmap
+            .iter()
+            .enumerate()
+            .find(|(_, byte)| **byte != u8::MAX)?;
+        let bit_index = byte_index * 8 + byte.trailing_ones() as usize;
+        Some(bucket_index * Self::BITS + bit_index as u64)
+    }
+
+    /// Every used sequence in this bucket, ascending.
+    ///
+    /// As with [`BitmapAccount::first_unused_in_bucket`], the caller passes
+    /// `bucket_index` to turn bit indices into global sequences.
+    #[inline]
+    pub fn iter_used(&self, bucket_index: u64) -> impl Iterator<Item = u64> + '_ {
+        iter_set_bits(self.bitmap, bucket_index * Self::BITS)
+    }
+
+    /// Count the sequences marked as used in this bucket.
+    #[inline]
+    pub fn count_used(&self) -> u32 {
+        self.bitmap.iter().map(|byte| byte.count_ones()).sum()
+    }
+
+    /// Whether any of the `count` sequences starting at `start` is used.
+    ///
+    /// The run must lie within the bucket containing `start`.
+    #[inline]
+    pub fn any_used(&self, start: u64, count: u64) -> Result<bool, RangeError> {
+        Ok(first_used_in_range(self.bitmap, start, count)?.is_some())
+    }
+
+    /// Mark `count` consecutive sequences starting at `start` as used.
+    ///
+    /// The run must lie within the bucket containing `start`. Bits are checked
+    /// and set a byte at a time, so full bytes cost a single OR. On conflict
+    /// nothing is modified and the first already-used sequence is returned.
+    #[inline]
+    pub fn mark_range(&mut self, start: u64, count: u64) -> Result<(), RangeError> {
+        // First pass: reject if any bit in the range is already set
+        if let Some(used) = first_used_in_range(self.bitmap, start, count)? {
+            return Err(RangeError::AlreadyUsed(used));
+        }
+
+        // Second pass: set the whole range
+        let bit_start = bit_in::<BYTES>(start);
+        let bit_end = bit_start + count as usize;
+        let mut bit = bit_start;
+        while bit < bit_end {
+            let (byte_index, mask, len) = range_byte_mask(bit, bit_end);
+            self.bitmap[byte_index] |= mask;
+            bit += len;
+        }
+
+        Ok(())
+    }
+}
+
+/// First used sequence among the `count` starting at `start`, checked a
+/// byte at a time. Fails if the run extends past the bucket of `start`.
+#[inline]
+fn first_used_in_range<const BYTES: usize>(
+    bitmap: &[u8; BYTES],
+    start: u64,
+    count: u64,
+) -> Result<Option<u64>, RangeError> {
+    let bit_start = bit_in::<BYTES>(start);
+    if count > BYTES as u64 * 8 - bit_start as u64 {
+        return Err(RangeError::CrossesBucket);
+    }
+    let bit_end = bit_start + count as usize;
+    let base = start - bit_start as u64;
+
+    let mut bit = bit_start;
+    while bit < bit_end {
+        let (byte_index, mask, len) = range_byte_mask(bit, bit_end);
+        let used = bitmap[byte_index] & mask;
+        if used != 0 {
+            let first = byte_index * 8 + used.trailing_zeros() as usize;
+            return Ok(Some(base + first as u64));
+        }
+        bit += len;
+    }
+
+    Ok(None)
+}
+
+/// Mask covering bits `[bit, bit_end)` that fall within the byte containing `bit`.
+/// Returns `(byte_index, mask, bits_covered)`.
+#[inline]
+fn range_byte_mask(bit: usize, bit_end: usize) -> (usize, u8, usize) {
+    let offset = bit % 8;
+    let len = (8 - offset).min(bit_end - bit);
+    let mask = (((1u16 << len) - 1) << offset) as u8;
+    (bit / 8, mask, len)
+}
+
+/// Read-only counterpart of [`BitmapAccount`], for paths that only query.
+/// Layout: [tag: 8 bytes][bump: u8][bitmap: BYTES bytes]
+pub struct BitmapAccountRef<'a, const BYTES: usize = BITMAP_BYTES> {
+    pub tag: &'a [u8; BITMAP_TAG_LEN],
+    pub bump: &'a u8,
+    pub bitmap: &'a [u8; BYTES],
+}
+
+impl<'a> BitmapAccountRef<'a> {
+    /// Wrap account data. Returns None if data is too small.
+    #[inline]
+    pub fn from_slice(data: &'a [u8]) -> Option<Self> {
+        Self::from_slice_sized(data)
+    }
+}
+
+impl<'a, const BYTES: usize> BitmapAccountRef<'a, BYTES> {
+    /// [`BitmapAccountRef::from_slice`] for an explicit bitmap size.
+    #[inline]
+    pub fn from_slice_sized(data: &'a [u8]) -> Option<Self> {
+        let tag = <&[u8; BITMAP_TAG_LEN]>::try_from(data.get(..BITMAP_TAG_LEN)?).ok()?;
+        let (bump, rest) = data[BITMAP_TAG_LEN..].split_first()?;
+        let bitmap = <&[u8; BYTES]>::try_from(rest.get(..BYTES)?).ok()?;
+        Some(Self { tag, bump, bitmap })
+    }
+
+    /// Check if a sequence number is marked as used.
+    #[inline]
+    pub fn is_used(&self, sequence: u64) -> bool {
+        is_bit_set(self.bitmap, sequence)
+    }
+
+    /// Every used sequence in this bucket, ascending; see
+    /// [`BitmapAccount::iter_used`].
+    #[inline]
+    pub fn iter_used(&self, bucket_index: u64) -> impl Iterator<Item = u64> + '_ {
+        iter_set_bits(self.bitmap, bucket_index * BYTES as u64 * 8)
+    }
+
+    /// Whether any of the `count` sequences starting at `start` is used.
+    ///
+    /// The run must lie within the bucket containing `start`.
+    #[inline]
+    pub fn any_used(&self, start: u64, count: u64) -> Result<bool, RangeError> {
+        Ok(first_used_in_range(self.bitmap, start, count)?.is_some())
+    }
+}
+
+// The default instantiation is the program's layout.
+const _: () = assert!(<BitmapAccount<'static>>::BITS == BITS_PER_BUCKET);
+const _: () = assert!(<BitmapAccount<'static>>::ACCOUNT_SIZE == BITMAP_ACCOUNT_SIZE);
+
+/// Zero-copy wrapper for watermark account data.
+/// Layout: [tag: 8 bytes][bump: u8][is_set: u8][watermark: u64 LE]
+///
+/// Stores only the highest sequence marked so far, so every later sequence
+/// must be strictly greater. Wrapping doesn't check the tag; see
+/// [`check_watermark_tag`].
+pub struct WatermarkAccount<'a> {
+    pub tag: &'a mut [u8; BITMAP_TAG_LEN],
+    pub bump: &'a mut u8,
+    /// Non-zero once a sequence has been marked; before that every sequence,
+    /// including 0, is accepted.
+    pub is_set: &'a mut u8,
+    pub watermark: &'a mut [u8; 8],
+}
+
+impl<'a> WatermarkAccount<'a> {
+    /// Wrap account data. Returns None if data is too small.
+    #[inline]
+    pub fn from_slice(data: &'a mut [u8]) -> Option<Self> {
+        if data.len() < WATERMARK_ACCOUNT_SIZE {
+            return None;
+        }
+        let (tag, rest) = data.split_at_mut(BITMAP_TAG_LEN);
+        let (bump, rest) = rest.split_at_mut(1);
+        let (is_set, rest) = rest.split_at_mut(1);
+        Some(Self {
+            tag: <&mut [u8; BITMAP_TAG_LEN]>::try_from(tag).ok()?,
+            bump: &mut bump[0],
+            is_set: &mut is_set[0],
+            watermark: <&mut [u8; 8]>::try_from(&mut rest[..8]).ok()?,
+        })
+    }
+
+    /// Wrap freshly allocated account data, writing the current tag and
+    /// `bump`. Returns None if data is too small.
+    #[inline]
+    pub fn init(data: &'a mut [u8], bump: u8) -> Option<Self> {
+        let account = Self::from_slice(data)?;
+        *account.tag = WATERMARK_TAG;
+        *account.bump = bump;
+        Some(account)
+    }
+
+    /// Highest sequence marked so far, or `None` if none has been.
+    #[inline]
+    pub fn watermark(&self) -> Option<u64> {
+        (*self.is_set != 0).then(|| u64::from_le_bytes(*self.watermark))
+    }
+
+    /// Whether `sequence` is at or below the watermark.
+    #[inline]
+    pub fn is_used(&self, sequence: u64) -> bool {
+        self.watermark()
+            .is_some_and(|watermark| sequence <= watermark)
+    }
+
+    /// Raise the watermark to `sequence`. Returns true (leaving the account
+    /// unchanged) if it was already at or above it.
+    #[inline]
+    pub fn mark_above(&mut self, sequence: u64) -> bool {
+        if self.is_used(sequence) {
+            return true;
+        }
+        *self.is_set = 1;
+        *self.watermark = sequence.to_le_bytes();
+        false
+    }
+}
+
+/// Error returned by [`WindowAccount::mark`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowError {
+    /// The sequence is [`WINDOW_BITS`] or more below the highest one marked,
+    /// so the window no longer records whether it was used.
+    TooOld,
+    /// The sequence is inside the window and already marked.
+    AlreadyUsed,
+}
+
+/// Zero-copy wrapper for sliding-window account data.
+/// Layout: [tag: 8 bytes][bump: u8][is_set: u8][highest: u64 LE][window: 128 bytes]
+///
+/// Tracks the highest sequence marked plus which of the [`WINDOW_BITS`]
+/// sequences ending at it were marked: bit `k` of the window (byte `k / 8`,
+/// bit `k % 8`) is `highest - k`. Older sequences are rejected outright.
+/// Wrapping doesn't check the tag; see [`check_window_tag`].
+pub struct WindowAccount<'a> {
+    pub tag: &'a mut [u8; BITMAP_TAG_LEN],
+    pub bump: &'a mut u8,
+    /// Non-zero once a sequence has been marked; before that the window is
+    /// empty and every sequence is accepted.
+    pub is_set: &'a mut u8,
+    pub highest: &'a mut [u8; 8],
+    pub window: &'a mut [u8; WINDOW_BYTES],
+}
+
+impl<'a> WindowAccount<'a> {
+    /// Wrap account data. Returns None if data is too small.
+    #[inline]
+    pub fn from_slice(data: &'a mut [u8]) -> Option<Self> {
+        if data.len() < WINDOW_ACCOUNT_SIZE {
+            return None;
+        }
+        let (tag, rest) = data.split_at_mut(BITMAP_TAG_LEN);
+        let (bump, rest) = rest.split_at_mut(1);
+        let (is_set, rest) = rest.split_at_mut(1);
+        let (highest, rest) = rest.split_at_mut(8);
+        Some(Self {
+            tag: <&mut [u8; BITMAP_TAG_LEN]>::try_from(tag).ok()?,
+            bump: &mut bump[0],
+            is_set: &mut is_set[0],
+            highest: <&mut [u8; 8]>::try_from(highest).ok()?,
+            window: <&mut [u8; WINDOW_BYTES]>::try_from(&mut rest[..WINDOW_BYTES]).ok()?,
+        })
+    }
+
+    /// Wrap freshly allocated account data, writing the current tag and
+    /// `bump`. Returns None if data is too small.
+    #[inline]
+    pub fn init(data: &'a mut [u8], bump: u8) -> Option<Self> {
+        let account = Self::from_slice(data)?;
+        *account.tag = WINDOW_TAG;
+        *account.bump = bump;
+        Some(account)
+    }
+
+    /// Highest sequence marked so far, or `None` if none has been.
+    #[inline]
+    pub fn highest(&self) -> Option<u64> {
+        (*self.is_set != 0).then(|| u64::from_le_bytes(*self.highest))
+    }
+
+    /// Whether `sequence` would be rejected: marked within the window, or
+    /// too old for the window to tell.
+    #[inline]
+    pub fn is_used(&self, sequence: u64) -> bool {
+        match self.highest() {
+            Some(highest) if sequence <= highest => match highest - sequence {
+                offset if offset >= WINDOW_BITS => true,
+                offset => is_bit_set(self.window, offset),
+            },
+            _ => false,
+        }
+    }
+
+    /// Mark `sequence`, sliding the window forward if it is the new highest.
+    /// Leaves the account unchanged on error.
+    #[inline]
+    pub fn mark(&mut self, sequence: u64) -> Result<(), WindowError> {
+        let Some(highest) = self.highest() else {
+            *self.is_set = 1;
+            *self.highest = sequence.to_le_bytes();
+            self.window[0] = 1;
+            return Ok(());
+        };
+
+        if sequence > highest {
+            shift_window(self.window, sequence - highest);
+            *self.highest = sequence.to_le_bytes();
+            self.window[0] |= 1;
+            return Ok(());
+        }
+
+        let offset = highest - sequence;
+        if offset >= WINDOW_BITS {
+            return Err(WindowError::TooOld);
+        }
+        if is_bit_set(self.window, offset) {
+            return Err(WindowError::AlreadyUsed);
+        }
+        let (byte_index, bit_offset) = bit_position_in::<WINDOW_BYTES>(offset);
+        self.window[byte_index] |= 1 << bit_offset;
+        Ok(())
+    }
+}
+
+/// Move every bit of `window` `distance` positions older (to a higher
+/// index), dropping bits that fall off the end.
+#[inline]
+fn shift_window(window: &mut [u8; WINDOW_BYTES], distance: u64) {
+    if distance >= WINDOW_BITS {
+        *window = [0; WINDOW_BYTES];
+        return;
+    }
+    let bytes = (distance / 8) as usize;
+    let bits = (distance % 8) as u32;
+    // Descending, so each byte reads sources that haven't been overwritten
+    for i in (0..WINDOW_BYTES).rev() {
+        let high = i.checked_sub(bytes).map_or(0, |src| window[src] << bits);
+        let low = match i.checked_sub(bytes + 1) {
+            Some(src) if bits != 0 => window[src] >> (8 - bits),
+            _ => 0,
+        };
+        window[i] = high | low;
+    }
+}
+
+/// Zero-copy wrapper for namespace config account data.
+/// Layout: [tag: 8 bytes][bump: u8][current_authority: 32 bytes]
+///
+/// Records the key allowed to mark sequences for the `(authority, namespace)`
+/// the account is derived from. Wrapping doesn't check the tag; see
+/// [`check_config_tag`].
+pub struct ConfigAccount<'a> {
+    pub tag: &'a mut [u8; BITMAP_TAG_LEN],
+    pub bump: &'a mut u8,
+    pub current_authority: &'a mut [u8; 32],
+}
+
+impl<'a> ConfigAccount<'a> {
+    /// Wrap account data. Returns None if data is too small.
+    #[inline]
+    pub fn from_slice(data: &'a mut [u8]) -> Option<Self> {
+        if data.len() < CONFIG_ACCOUNT_SIZE {
+            return None;
+        }
+        let (tag, rest) = data.split_at_mut(BITMAP_TAG_LEN);
+        let (bump, rest) = rest.split_at_mut(1);
+        Some(Self {
+            tag: <&mut [u8; BITMAP_TAG_LEN]>::try_from(tag).ok()?,
+            bump: &mut bump[0],
+            current_authority: <&mut [u8; 32]>::try_from(&mut rest[..32]).ok()?,
+        })
+    }
+
+    /// Wrap freshly allocated account data, writing the current tag, `bump`
+    /// and `current_authority`. Returns None if data is too small.
+    #[inline]
+    pub fn init(data: &'a mut [u8], bump: u8, current_authority: &[u8; 32]) -> Option<Self> {
+        let account = Self::from_slice(data)?;
+        *account.tag = CONFIG_TAG;
+        *account.bump = bump;
+        *account.current_authority = *current_authority;
+        Some(account)
+    }
+}
+
+/// Next value of a committed bucket's accumulator after marking `sequence`
+/// for the message hashing to `message_hash`:
+/// `sha256(previous || sequence (u64 LE) || message_hash)`.
+///
+/// A new bucket's accumulator is all zeroes, so replaying a bucket's marks
+/// in order from zero reproduces its stored accumulator.
+#[inline]
+pub fn next_accumulator(
+    previous: &[u8; ACCUMULATOR_BYTES],
+    sequence: u64,
+    message_hash: &[u8; 32],
+) -> [u8; ACCUMULATOR_BYTES] {
+    solana_sha256_hasher::hashv(&[previous, &sequence.to_le_bytes(), message_hash]).to_bytes()
+}
+
+/// Zero-copy wrapper for committed bucket data.
+/// Layout: [tag: 8 bytes][bump: u8][accumulator: 32 bytes][bitmap: 128 bytes]
+///
+/// A bitmap bucket that also chains the hash of each marked message into an
+/// accumulator (see [`next_accumulator`]), a tamper-evident record of what
+/// was marked, in order. Wrapping doesn't check the tag; see
+/// [`check_committed_tag`].
+pub struct CommittedBitmapAccount<'a> {
+    pub tag: &'a mut [u8; BITMAP_TAG_LEN],
+    pub bump: &'a mut u8,
+    pub accumulator: &'a mut [u8; ACCUMULATOR_BYTES],
+    pub bitmap: &'a mut [u8; BITMAP_BYTES],
+}
+
+impl<'a> CommittedBitmapAccount<'a> {
+    /// Wrap account data. Returns None if data is too small.
+    #[inline]
+    pub fn from_slice(data: &'a mut [u8]) -> Option<Self> {
+        if data.len() < COMMITTED_ACCOUNT_SIZE {
+            return None;
+        }
+        let (tag, rest) = data.split_at_mut(BITMAP_TAG_LEN);
+        let (bump, rest) = rest.split_at_mut(1);
+        let (accumulator, rest) = rest.split_at_mut(ACCUMULATOR_BYTES);
+        Some(Self {
+            tag: <&mut [u8; BITMAP_TAG_LEN]>::try_from(tag).ok()?,
+            bump: &mut bump[0],
+            accumulator: <&mut [u8; ACCUMULATOR_BYTES]>::try_from(accumulator).ok()?,
+            bitmap: <&mut [u8; BITMAP_BYTES]>::try_from(&mut rest[..BITMAP_BYTES]).ok()?,
+        })
+    }
+
+    /// Wrap freshly allocated account data, writing the current tag and
+    /// `bump`. Returns None if data is too small.
+    #[inline]
+    pub fn init(data: &'a mut [u8], bump: u8) -> Option<Self> {
+        let account = Self::from_slice(data)?;
+        *account.tag = COMMITTED_TAG;
+        *account.bump = bump;
+        Some(account)
+    }
+
+    /// Check if a sequence number is marked as used.
+    #[inline]
+    pub fn is_used(&self, sequence: u64) -> bool {
+        is_bit_set(self.bitmap, sequence)
+    }
+
+    /// Mark `sequence` as used and fold `message_hash` into the accumulator.
+    /// Returns true (leaving the account unchanged) if it was already used.
+    #[inline]
+    pub fn commit(&mut self, sequence: u64, message_hash: &[u8; 32]) -> bool {
+        if self.is_used(sequence) {
+            return true;
+        }
+        let (byte_index, bit_offset) = bit_position(sequence);
+        self.bitmap[byte_index] |= 1 << bit_offset;
+        *self.accumulator = next_accumulator(self.accumulator, sequence, message_hash);
+        false
+    }
 }
@@ -1,4 +1,5 @@
-//! Benchmarks comparing compute unit usage for different account creation paths.
+//! Benchmarks comparing compute unit usage for different account creation paths
+//! and for the CheckUsed read path.
 //!
 //! Run with: cargo bench --package solana-noreplay-tests
 
@@ -7,40 +8,35 @@ use mollusk_svm_bencher::MolluskComputeUnitBencher;
 use solana_account::Account;
 use solana_instruction::{AccountMeta, Instruction};
 use solana_noreplay::client::{
-    derive_bitmap_pda, CreateBitmap, MarkUsed, BITMAP_ACCOUNT_SIZE, PROGRAM_ID,
+    derive_bitmap_pda, CheckUsed, CreateBitmap, MarkUsed, MarkUsedBatch, BITMAP_ACCOUNT_SIZE,
+    PROGRAM_ID,
 };
+use solana_noreplay::compat::{from_sdk, to_sdk};
 use solana_pubkey::Pubkey;
 
 const SYSTEM_PROGRAM_ID: Pubkey = solana_pubkey::pubkey!("11111111111111111111111111111111");
 
 fn program_id() -> Pubkey {
-    PROGRAM_ID.to_bytes().into()
+    from_sdk(&PROGRAM_ID)
 }
 
-/// Higher than rent-exempt minimum to ensure prefunded_full skips Transfer CPI
+/// Higher than rent-exempt minimum to ensure prefunded_full skips Transfer CPI.
+///
+/// Derived from the account size: a hardcoded amount went stale when the
+/// bitmap grew, silently turning prefunded_full into the Transfer path.
 fn rent_for_bitmap() -> u64 {
-    1_200_000
-}
-
-/// Convert solana_pubkey::Pubkey to solana_sdk::pubkey::Pubkey for derive_bitmap_pda
-fn to_sdk_pubkey(pubkey: &Pubkey) -> solana_sdk::pubkey::Pubkey {
-    pubkey.to_bytes().into()
-}
-
-/// Convert solana_sdk::pubkey::Pubkey to solana_pubkey::Pubkey
-fn from_sdk_pubkey(pubkey: solana_sdk::pubkey::Pubkey) -> Pubkey {
-    pubkey.to_bytes().into()
+    solana_noreplay::client::rent_for_bitmap(&solana_sdk::rent::Rent::default()) + 100_000
 }
 
 /// Convert solana_sdk::instruction::Instruction to solana_instruction::Instruction for mollusk.
 fn to_mollusk_instruction(ix: solana_sdk::instruction::Instruction) -> Instruction {
     Instruction {
-        program_id: ix.program_id.to_bytes().into(),
+        program_id: from_sdk(&ix.program_id),
         accounts: ix
             .accounts
             .into_iter()
             .map(|a| AccountMeta {
-                pubkey: a.pubkey.to_bytes().into(),
+                pubkey: from_sdk(&a.pubkey),
                 is_signer: a.is_signer,
                 is_writable: a.is_writable,
             })
@@ -56,8 +52,8 @@ fn build_create_bitmap_instruction(
     namespace: &[u8],
     sequence: u64,
 ) -> Instruction {
-    let sdk_payer = to_sdk_pubkey(payer);
-    let sdk_authority = to_sdk_pubkey(authority);
+    let sdk_payer = to_sdk(payer);
+    let sdk_authority = to_sdk(authority);
     to_mollusk_instruction(
         CreateBitmap {
             payer: &sdk_payer,
@@ -76,8 +72,8 @@ fn build_mark_used_instruction(
     namespace: &[u8],
     sequence: u64,
 ) -> Instruction {
-    let sdk_payer = to_sdk_pubkey(payer);
-    let sdk_authority = to_sdk_pubkey(authority);
+    let sdk_payer = to_sdk(payer);
+    let sdk_authority = to_sdk(authority);
     to_mollusk_instruction(
         MarkUsed {
             payer: &sdk_payer,
@@ -89,10 +85,47 @@ fn build_mark_used_instruction(
     )
 }
 
-/// Create an account with bump stored at offset 0
+/// Build instruction to mark several sequences sharing one bucket.
+fn build_mark_used_batch_instruction(
+    payer: &Pubkey,
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequences: &[u64],
+) -> Instruction {
+    let sdk_payer = to_sdk(payer);
+    let sdk_authority = to_sdk(authority);
+    to_mollusk_instruction(
+        MarkUsedBatch {
+            payer: &sdk_payer,
+            authority: &sdk_authority,
+            namespace,
+            sequences,
+        }
+        .instruction(),
+    )
+}
+
+/// Build instruction to check whether a sequence is marked.
+fn build_check_used_instruction(
+    authority: &Pubkey,
+    namespace: &[u8],
+    sequence: u64,
+) -> Instruction {
+    let sdk_authority = to_sdk(authority);
+    to_mollusk_instruction(
+        CheckUsed {
+            authority: &sdk_authority,
+            namespace,
+            sequence,
+        }
+        .instruction(),
+    )
+}
+
+/// Create an initialized bitmap account (tag and bump written)
 fn account_with_bump(lamports: u64, bump: u8, owner: &Pubkey) -> Account {
     let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
-    data[0] = bump; // Store bump at offset 0
+    solana_noreplay::BitmapAccount::init(&mut data, bump).unwrap();
     Account {
         lamports,
         data,
@@ -110,7 +143,7 @@ fn main() {
 
     let payer = Pubkey::new_unique();
     let authority = Pubkey::new_unique();
-    let sdk_authority = to_sdk_pubkey(&authority);
+    let sdk_authority = to_sdk(&authority);
     let namespace = b"bench";
     let rent_exempt_min = rent_for_bitmap();
 
@@ -127,7 +160,7 @@ fn main() {
     // MarkUsed: New account (0 lamports) -> single CreateAccount CPI
     let sequence_new = 1u64;
     let (pda_new, _) = derive_bitmap_pda(&sdk_authority, namespace, sequence_new);
-    let pda_new = from_sdk_pubkey(pda_new);
+    let pda_new = from_sdk(&pda_new);
     let ix_mark_new = build_mark_used_instruction(&payer, &authority, namespace, sequence_new);
     let accounts_mark_new: Vec<(Pubkey, Account)> = vec![
         (payer, Account::new(10_000_000_000, 0, &SYSTEM_PROGRAM_ID)),
@@ -139,7 +172,7 @@ fn main() {
     // MarkUsed: Partially pre-funded -> Transfer + Allocate + Assign (3 CPIs)
     let sequence_prefunded = 2u64;
     let (pda_prefunded, _) = derive_bitmap_pda(&sdk_authority, namespace, sequence_prefunded);
-    let pda_prefunded = from_sdk_pubkey(pda_prefunded);
+    let pda_prefunded = from_sdk(&pda_prefunded);
     let ix_mark_prefunded =
         build_mark_used_instruction(&payer, &authority, namespace, sequence_prefunded);
     let accounts_mark_prefunded: Vec<(Pubkey, Account)> = vec![
@@ -155,7 +188,7 @@ fn main() {
     // MarkUsed: Fully pre-funded -> Allocate + Assign (2 CPIs)
     let sequence_fully_funded = 3u64;
     let (pda_fully_funded, _) = derive_bitmap_pda(&sdk_authority, namespace, sequence_fully_funded);
-    let pda_fully_funded = from_sdk_pubkey(pda_fully_funded);
+    let pda_fully_funded = from_sdk(&pda_fully_funded);
     let ix_mark_fully_funded =
         build_mark_used_instruction(&payer, &authority, namespace, sequence_fully_funded);
     let accounts_mark_fully_funded: Vec<(Pubkey, Account)> = vec![
@@ -168,11 +201,27 @@ fn main() {
         (SYSTEM_PROGRAM_ID, system_program_account.clone()),
     ];
 
+    // MarkUsed: Fully pre-funded and already allocated -> Assign only (1 CPI)
+    let sequence_allocated = 5u64;
+    let (pda_allocated, _) = derive_bitmap_pda(&sdk_authority, namespace, sequence_allocated);
+    let pda_allocated = from_sdk(&pda_allocated);
+    let ix_mark_allocated =
+        build_mark_used_instruction(&payer, &authority, namespace, sequence_allocated);
+    let accounts_mark_allocated: Vec<(Pubkey, Account)> = vec![
+        (payer, Account::new(10_000_000_000, 0, &SYSTEM_PROGRAM_ID)),
+        (authority, Account::new(0, 0, &SYSTEM_PROGRAM_ID)),
+        (
+            pda_allocated,
+            Account::new(rent_exempt_min, BITMAP_ACCOUNT_SIZE, &SYSTEM_PROGRAM_ID),
+        ),
+        (SYSTEM_PROGRAM_ID, system_program_account.clone()),
+    ];
+
     // MarkUsed: Account already exists (owned by program) -> 0 CPIs
     let sequence_existing = 4u64;
     let (pda_existing, bump_existing) =
         derive_bitmap_pda(&sdk_authority, namespace, sequence_existing);
-    let pda_existing = from_sdk_pubkey(pda_existing);
+    let pda_existing = from_sdk(&pda_existing);
     let ix_mark_existing =
         build_mark_used_instruction(&payer, &authority, namespace, sequence_existing);
     let accounts_mark_existing: Vec<(Pubkey, Account)> = vec![
@@ -192,7 +241,7 @@ fn main() {
     // CreateBitmap: New account (0 lamports) -> single CreateAccount CPI
     let sequence_create_new = 10u64;
     let (pda_create_new, _) = derive_bitmap_pda(&sdk_authority, namespace, sequence_create_new);
-    let pda_create_new = from_sdk_pubkey(pda_create_new);
+    let pda_create_new = from_sdk(&pda_create_new);
     let ix_create_new =
         build_create_bitmap_instruction(&payer, &authority, namespace, sequence_create_new);
     let accounts_create_new: Vec<(Pubkey, Account)> = vec![
@@ -206,7 +255,7 @@ fn main() {
     let sequence_create_existing = 11u64;
     let (pda_create_existing, bump_create_existing) =
         derive_bitmap_pda(&sdk_authority, namespace, sequence_create_existing);
-    let pda_create_existing = from_sdk_pubkey(pda_create_existing);
+    let pda_create_existing = from_sdk(&pda_create_existing);
     let ix_create_existing =
         build_create_bitmap_instruction(&payer, &authority, namespace, sequence_create_existing);
     let accounts_create_existing: Vec<(Pubkey, Account)> = vec![
@@ -219,6 +268,126 @@ fn main() {
         (SYSTEM_PROGRAM_ID, system_program_account.clone()),
     ];
 
+    // =========================================================================
+    // MarkUsedBatch benchmarks (compare against BATCH_SIZE x mark_used)
+    // =========================================================================
+
+    const BATCH_SIZE: u64 = 8;
+
+    // MarkUsedBatch: New account -> one CreateAccount CPI for the whole batch
+    let batch_new: Vec<u64> = (2048..2048 + BATCH_SIZE).collect();
+    let (pda_batch_new, _) = derive_bitmap_pda(&sdk_authority, namespace, batch_new[0]);
+    let pda_batch_new = from_sdk(&pda_batch_new);
+    let ix_batch_new = build_mark_used_batch_instruction(&payer, &authority, namespace, &batch_new);
+    let accounts_batch_new: Vec<(Pubkey, Account)> = vec![
+        (payer, Account::new(10_000_000_000, 0, &SYSTEM_PROGRAM_ID)),
+        (authority, Account::new(0, 0, &SYSTEM_PROGRAM_ID)),
+        (pda_batch_new, Account::default()),
+        (SYSTEM_PROGRAM_ID, system_program_account.clone()),
+    ];
+
+    // MarkUsedBatch: Account already exists -> 0 CPIs
+    let batch_existing: Vec<u64> = (3072..3072 + BATCH_SIZE).collect();
+    let (pda_batch_existing, bump_batch_existing) =
+        derive_bitmap_pda(&sdk_authority, namespace, batch_existing[0]);
+    let pda_batch_existing = from_sdk(&pda_batch_existing);
+    let ix_batch_existing =
+        build_mark_used_batch_instruction(&payer, &authority, namespace, &batch_existing);
+    let accounts_batch_existing: Vec<(Pubkey, Account)> = vec![
+        (payer, Account::new(10_000_000_000, 0, &SYSTEM_PROGRAM_ID)),
+        (authority, Account::new(0, 0, &SYSTEM_PROGRAM_ID)),
+        (
+            pda_batch_existing,
+            account_with_bump(rent_exempt_min, bump_batch_existing, &program_id),
+        ),
+        (SYSTEM_PROGRAM_ID, system_program_account.clone()),
+    ];
+
+    // =========================================================================
+    // CheckUsed benchmarks (the defensive read, compare against mark_used)
+    // =========================================================================
+
+    // CheckUsed: PDA doesn't exist yet -> answers 0 without reading data
+    let sequence_check_missing = 4096u64;
+    let (pda_check_missing, _) =
+        derive_bitmap_pda(&sdk_authority, namespace, sequence_check_missing);
+    let pda_check_missing = from_sdk(&pda_check_missing);
+    let ix_check_missing =
+        build_check_used_instruction(&authority, namespace, sequence_check_missing);
+    let accounts_check_missing: Vec<(Pubkey, Account)> = vec![
+        (authority, Account::new(0, 0, &SYSTEM_PROGRAM_ID)),
+        (pda_check_missing, Account::default()),
+    ];
+
+    // CheckUsed: Existing PDA, bit unset -> bump verification + bit read
+    let sequence_check_unset = 5120u64;
+    let (pda_check_unset, bump_check_unset) =
+        derive_bitmap_pda(&sdk_authority, namespace, sequence_check_unset);
+    let pda_check_unset = from_sdk(&pda_check_unset);
+    let ix_check_unset = build_check_used_instruction(&authority, namespace, sequence_check_unset);
+    let accounts_check_unset: Vec<(Pubkey, Account)> = vec![
+        (authority, Account::new(0, 0, &SYSTEM_PROGRAM_ID)),
+        (
+            pda_check_unset,
+            account_with_bump(rent_exempt_min, bump_check_unset, &program_id),
+        ),
+    ];
+
+    // CheckUsed: Existing PDA, bit set -> same path, different answer
+    let sequence_check_set = 6144u64;
+    let (pda_check_set, bump_check_set) =
+        derive_bitmap_pda(&sdk_authority, namespace, sequence_check_set);
+    let pda_check_set = from_sdk(&pda_check_set);
+    let ix_check_set = build_check_used_instruction(&authority, namespace, sequence_check_set);
+    let mut account_check_set = account_with_bump(rent_exempt_min, bump_check_set, &program_id);
+    solana_noreplay::BitmapAccount::from_slice(&mut account_check_set.data)
+        .unwrap()
+        .mark_used(sequence_check_set);
+    let accounts_check_set: Vec<(Pubkey, Account)> = vec![
+        (authority, Account::new(0, 0, &SYSTEM_PROGRAM_ID)),
+        (pda_check_set, account_check_set),
+    ];
+
+    // Skipping Allocate must show up as a cheaper creation
+    let cus = |ix: &Instruction, accounts: &[(Pubkey, Account)]| {
+        mollusk
+            .process_instruction(ix, accounts)
+            .compute_units_consumed
+    };
+    assert!(
+        cus(&ix_mark_allocated, &accounts_mark_allocated)
+            < cus(&ix_mark_fully_funded, &accounts_mark_fully_funded)
+    );
+
+    // MarkUsedBatch size sweep: a new bucket's creation is paid once per
+    // batch, so CU per sequence falls towards the bare cost of setting a bit.
+    // Batches past ~120 sequences no longer fit a transaction; they're swept
+    // anyway to show where the curve flattens.
+    let sweep: Vec<(u64, u64)> = (0..=8)
+        .map(|shift| {
+            let size = 1u64 << shift;
+            let sequences: Vec<u64> = (8192..8192 + size).collect();
+            let (pda, _) = derive_bitmap_pda(&sdk_authority, namespace, sequences[0]);
+            let ix = build_mark_used_batch_instruction(&payer, &authority, namespace, &sequences);
+            let accounts: Vec<(Pubkey, Account)> = vec![
+                (payer, Account::new(10_000_000_000, 0, &SYSTEM_PROGRAM_ID)),
+                (authority, Account::new(0, 0, &SYSTEM_PROGRAM_ID)),
+                (from_sdk(&pda), Account::default()),
+                (SYSTEM_PROGRAM_ID, system_program_account.clone()),
+            ];
+            (size, cus(&ix, &accounts))
+        })
+        .collect();
+    println!("| batch size | CUs | CUs per sequence |");
+    println!("|-----------:|----:|-----------------:|");
+    for (size, cus) in &sweep {
+        println!("| {size} | {cus} | {} |", cus / size);
+    }
+    // Every doubling must still be cheaper per sequence than the last
+    assert!(sweep
+        .windows(2)
+        .all(|pair| pair[1].1 * pair[0].0 < pair[0].1 * pair[1].0));
+
     MolluskComputeUnitBencher::new(mollusk)
         // MarkUsed scenarios
         .bench(("mark_used__new_account", &ix_mark_new, &accounts_mark_new))
@@ -232,11 +401,27 @@ fn main() {
             &ix_mark_fully_funded,
             &accounts_mark_fully_funded,
         ))
+        .bench((
+            "mark_used__prefunded_allocated",
+            &ix_mark_allocated,
+            &accounts_mark_allocated,
+        ))
         .bench((
             "mark_used__existing_account",
             &ix_mark_existing,
             &accounts_mark_existing,
         ))
+        // MarkUsedBatch scenarios (BATCH_SIZE sequences each)
+        .bench((
+            "mark_used_batch_8__new_account",
+            &ix_batch_new,
+            &accounts_batch_new,
+        ))
+        .bench((
+            "mark_used_batch_8__existing_account",
+            &ix_batch_existing,
+            &accounts_batch_existing,
+        ))
         // CreateBitmap scenarios
         .bench((
             "create_bitmap__new_account",
@@ -248,6 +433,22 @@ fn main() {
             &ix_create_existing,
             &accounts_create_existing,
         ))
+        // CheckUsed scenarios
+        .bench((
+            "check_used__missing_account",
+            &ix_check_missing,
+            &accounts_check_missing,
+        ))
+        .bench((
+            "check_used__existing_unset",
+            &ix_check_unset,
+            &accounts_check_unset,
+        ))
+        .bench((
+            "check_used__existing_set",
+            &ix_check_set,
+            &accounts_check_set,
+        ))
         .must_pass(true)
         .out_dir("../target/benches")
         .execute();
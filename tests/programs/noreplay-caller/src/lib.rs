@@ -0,0 +1,168 @@
+//! Minimal Anchor program that consumes sequences through the NoReplay
+//! anchor-interface, using a PDA of this program as the authority.
+//!
+//! Only used by the `anchor-e2e` tests; mirrors the usage documented in
+//! `solana_noreplay_interface`.
+
+use anchor_lang::prelude::*;
+use solana_noreplay_interface::cpi;
+
+declare_id!("9N5Sc2FZ9ob5GwiU3SP3dxEma2uKFTb3rTHRcUjWDtdV");
+
+/// Seed of the PDA this program signs with as the NoReplay authority.
+pub const EMITTER_SEED: &[u8] = b"emitter";
+
+/// Seed of the PDA authority of this program's global namespace, which has
+/// no keypair and so can only be marked through `consume_global`.
+pub const GLOBAL_AUTHORITY_SEED: &[u8] = b"noreplay-global";
+
+#[program]
+pub mod noreplay_caller {
+    use super::*;
+
+    /// Mark `sequence` as used in `namespace`, failing on replay.
+    ///
+    /// Returns the [`cpi::MarkResult`] as return data.
+    pub fn consume(ctx: Context<Consume>, namespace: Vec<u8>, sequence: u64) -> Result<Consumed> {
+        let bump = [ctx.bumps.emitter];
+        let authority_seeds: &[&[u8]] = &[EMITTER_SEED, &bump];
+
+        let result = cpi::mark_used(
+            ctx.accounts.mark_used_ctx(&[authority_seeds]),
+            &namespace,
+            sequence,
+        )?;
+
+        Ok(Consumed {
+            pda: result.pda,
+            newly_created: result.newly_created,
+            bucket: result.bucket,
+            newly_marked: result.newly_marked,
+        })
+    }
+
+    /// Mark `sequence` as used in `namespace` of the global replay space,
+    /// failing on replay.
+    pub fn consume_global(
+        ctx: Context<ConsumeGlobal>,
+        namespace: Vec<u8>,
+        sequence: u64,
+    ) -> Result<()> {
+        let bump = [ctx.bumps.global_authority];
+        let authority_seeds: &[&[u8]] = &[GLOBAL_AUTHORITY_SEED, &bump];
+
+        cpi::mark_used(
+            ctx.accounts.mark_used_ctx(&[authority_seeds]),
+            &namespace,
+            sequence,
+        )?;
+
+        Ok(())
+    }
+
+    /// Mark `sequence` as used in `namespace` unless it already is.
+    ///
+    /// Returns whether it was newly marked as return data; a replay is not
+    /// an error.
+    pub fn consume_if_unused(
+        ctx: Context<Consume>,
+        namespace: Vec<u8>,
+        sequence: u64,
+    ) -> Result<bool> {
+        let bump = [ctx.bumps.emitter];
+        let authority_seeds: &[&[u8]] = &[EMITTER_SEED, &bump];
+
+        cpi::mark_if_unused(
+            ctx.accounts.mark_used_ctx(&[authority_seeds]),
+            &namespace,
+            sequence,
+        )
+    }
+}
+
+/// [`cpi::MarkResult`], borsh-encoded as `consume`'s return data.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct Consumed {
+    pub pda: Pubkey,
+    pub newly_created: bool,
+    pub bucket: u64,
+    pub newly_marked: bool,
+}
+
+#[derive(Accounts)]
+pub struct Consume<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: PDA signer only; holds no data.
+    #[account(seeds = [EMITTER_SEED], bump)]
+    pub emitter: UncheckedAccount<'info>,
+
+    /// CHECK: Verified (and created if needed) by the NoReplay program.
+    #[account(mut)]
+    pub replay_bitmap: UncheckedAccount<'info>,
+
+    /// CHECK: Address constrained to the NoReplay program.
+    #[account(address = solana_noreplay_interface::ID)]
+    pub noreplay_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Same accounts as [`Consume`], with the global authority PDA in place of
+/// the emitter.
+#[derive(Accounts)]
+pub struct ConsumeGlobal<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: PDA signer only; holds no data.
+    #[account(seeds = [GLOBAL_AUTHORITY_SEED], bump)]
+    pub global_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Verified (and created if needed) by the NoReplay program.
+    #[account(mut)]
+    pub replay_bitmap: UncheckedAccount<'info>,
+
+    /// CHECK: Address constrained to the NoReplay program.
+    #[account(address = solana_noreplay_interface::ID)]
+    pub noreplay_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ConsumeGlobal<'info> {
+    fn mark_used_ctx<'a, 'b, 'c>(
+        &self,
+        signer_seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, cpi::MarkUsed<'info>> {
+        CpiContext::new_with_signer(
+            self.noreplay_program.to_account_info(),
+            cpi::MarkUsed {
+                payer: self.payer.to_account_info(),
+                authority: self.global_authority.to_account_info(),
+                bitmap: self.replay_bitmap.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            signer_seeds,
+        )
+    }
+}
+
+impl<'info> Consume<'info> {
+    fn mark_used_ctx<'a, 'b, 'c>(
+        &self,
+        signer_seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, cpi::MarkUsed<'info>> {
+        CpiContext::new_with_signer(
+            self.noreplay_program.to_account_info(),
+            cpi::MarkUsed {
+                payer: self.payer.to_account_info(),
+                authority: self.emitter.to_account_info(),
+                bitmap: self.replay_bitmap.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            signer_seeds,
+        )
+    }
+}
@@ -1,33 +1,102 @@
-use solana_sdk::rent::Rent;
+use litesvm::LiteSVM;
+use solana_noreplay::client::DecodedBitmap;
+use solana_sdk::{
+    account::Account, pubkey::Pubkey, rent::Rent, signature::Keypair, signer::Signer,
+    transaction::Transaction,
+};
 
 // Re-export from the program's client module
 pub use solana_noreplay::client::{
     build_instruction_data, derive_bitmap_pda, CreateBitmap, MarkUsed, BITMAP_ACCOUNT_SIZE,
-    BITS_PER_BUCKET, CREATE_BITMAP, MARK_USED, MAX_NAMESPACE_LEN, PROGRAM_ID,
+    BITMAP_BYTES, BITS_PER_BUCKET, CREATE_BITMAP, MARK_USED, MAX_NAMESPACE_LEN, PROGRAM_ID,
 };
 
+// The client re-exports must be the program's own constants; a local
+// redefinition would derive different bucket PDAs.
+const _: () = assert!(BITS_PER_BUCKET == solana_noreplay::state::BITS_PER_BUCKET);
+const _: () = assert!(BITS_PER_BUCKET == BITMAP_BYTES as u64 * 8);
+const _: () = assert!(BITMAP_ACCOUNT_SIZE == solana_noreplay::state::BITMAP_ACCOUNT_SIZE);
+
 pub fn load_program() -> Vec<u8> {
     std::fs::read("../target/deploy/solana_noreplay.so")
         .expect("Program not built. Run `cargo build-sbf` first.")
 }
 
-/// Rent cost for a bitmap PDA
+/// Rent cost for a bitmap PDA under the default rent parameters
 pub fn rent_for_bitmap() -> u64 {
-    Rent::default().minimum_balance(BITMAP_ACCOUNT_SIZE)
+    solana_noreplay::client::rent_for_bitmap(&Rent::default())
+}
+
+/// Build a bitmap account with `bump` stored and `used_sequences` already set.
+///
+/// Sequences are reduced modulo `BITS_PER_BUCKET`, so pass ones from the
+/// bucket the account will be installed at (e.g. with `LiteSVM::set_account`).
+pub fn bitmap_account_fixture(
+    lamports: u64,
+    bump: u8,
+    used_sequences: &[u64],
+    owner: &Pubkey,
+) -> Account {
+    let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+    let mut bitmap = solana_noreplay::state::BitmapAccount::init(&mut data, bump).unwrap();
+    for sequence in used_sequences {
+        bitmap.mark_used(*sequence);
+    }
+    Account {
+        lamports,
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Submit MarkUsed, then fetch the bucket and check the bit is now set.
+///
+/// Catches a transaction that succeeds but writes to the wrong PDA: the
+/// bucket is re-derived independently of the instruction builder.
+pub fn mark_and_verify(
+    svm: &mut LiteSVM,
+    payer: &Keypair,
+    authority: &Keypair,
+    namespace: &[u8],
+    sequence: u64,
+) -> Result<(), String> {
+    let ix = MarkUsed {
+        payer: &payer.pubkey(),
+        authority: &authority.pubkey(),
+        namespace,
+        sequence,
+    }
+    .instruction();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&payer.pubkey()),
+        &[payer, authority],
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx)
+        .map_err(|e| format!("MarkUsed failed: {:?}", e.err))?;
+
+    let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+    let account = svm
+        .get_account(&pda)
+        .ok_or_else(|| format!("bucket {pda} missing after MarkUsed"))?;
+    let bitmap = DecodedBitmap::try_decode(&account.data)
+        .map_err(|e| format!("bucket {pda} undecodable: {e:?}"))?;
+    if !bitmap.is_used(sequence) {
+        return Err(format!("sequence {sequence} not set in bucket {pda}"));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use litesvm::LiteSVM;
     use proptest::prelude::*;
     use solana_sdk::{
         instruction::{AccountMeta, Instruction as SdkInstruction},
         native_token::LAMPORTS_PER_SOL,
-        pubkey::Pubkey,
-        signature::Keypair,
-        signer::Signer,
-        transaction::Transaction,
     };
 
     #[test]
@@ -154,6 +223,178 @@ mod tests {
         );
     }
 
+    #[test]
+    fn overfunded_pda_keeps_excess_lamports() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let namespace = b"test";
+        let sequence = 321u64;
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+
+        // Fund the PDA above rent before it exists
+        let overfunded = rent_for_bitmap() + 500_000;
+        svm.airdrop(&pda, overfunded).unwrap();
+        let authority_before = svm.get_balance(&authority.pubkey()).unwrap();
+
+        svm.expire_blockhash();
+        let ix = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        // Excess stays in the bucket; the payer is charged only the fee
+        let account = svm.get_account(&pda).unwrap();
+        assert_eq!(account.owner, PROGRAM_ID);
+        assert_eq!(account.lamports, overfunded);
+        assert_eq!(
+            authority_before - svm.get_balance(&authority.pubkey()).unwrap(),
+            5000
+        );
+    }
+
+    #[test]
+    fn existing_bucket_needs_no_lamports_beyond_fee() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let funder = Keypair::new();
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        svm.airdrop(&funder.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let namespace = b"test";
+        let sequence = 77u64;
+
+        // Someone else pre-creates the bucket
+        let ix = CreateBitmap {
+            payer: &funder.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&funder.pubkey()),
+            &[&funder],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        // Payer holds exactly the fee for two signatures, nothing for rent
+        let fee = 2 * 5000;
+        svm.airdrop(&payer.pubkey(), fee).unwrap();
+
+        let ix = MarkUsed {
+            payer: &payer.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer.pubkey()),
+            &[&payer, &authority],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx);
+        assert!(
+            result.is_ok(),
+            "Existing bucket should not charge the payer rent: {:?}",
+            result
+        );
+        assert_eq!(svm.get_balance(&payer.pubkey()).unwrap_or(0), 0);
+    }
+
+    /// Fund a fresh payer with `balance`, then submit a MarkUsed that creates
+    /// a new bucket. Returns the payer, the bucket PDA and the send result.
+    fn mark_new_bucket_with_payer_balance(
+        svm: &mut LiteSVM,
+        balance: u64,
+    ) -> (Keypair, Pubkey, litesvm::types::TransactionResult) {
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        svm.airdrop(&payer.pubkey(), balance).unwrap();
+
+        let ix = MarkUsed {
+            payer: &payer.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"test",
+            sequence: 5,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer.pubkey()),
+            &[&payer, &authority],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx);
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), b"test", 5);
+        (payer, pda, result)
+    }
+
+    #[test]
+    fn payer_with_exactly_rent_and_fee_can_create_bucket() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let fee = 2 * 5000;
+        let payer_minimum = Rent::default().minimum_balance(0);
+
+        // Left at its own rent-exempt minimum: the payer survives
+        let (payer, _, result) =
+            mark_new_bucket_with_payer_balance(&mut svm, payer_minimum + rent_for_bitmap() + fee);
+        assert!(result.is_ok(), "Tight budget should succeed: {:?}", result);
+        let account = svm.get_account(&payer.pubkey()).expect("payer reaped");
+        assert_eq!(account.lamports, payer_minimum);
+
+        // Drained to exactly zero is also allowed
+        let (payer, _, result) =
+            mark_new_bucket_with_payer_balance(&mut svm, rent_for_bitmap() + fee);
+        assert!(result.is_ok(), "Exact budget should succeed: {:?}", result);
+        assert_eq!(svm.get_balance(&payer.pubkey()).unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn payer_left_below_own_rent_minimum_fails_transaction() {
+        use solana_sdk::transaction::TransactionError;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        // One lamport left over: non-zero but not rent-exempt
+        let fee = 2 * 5000;
+        let (_, pda, result) =
+            mark_new_bucket_with_payer_balance(&mut svm, rent_for_bitmap() + fee + 1);
+        let err = result.expect_err("payer below its rent minimum must fail");
+        assert!(
+            matches!(err.err, TransactionError::InsufficientFundsForRent { .. }),
+            "unexpected error: {:?}",
+            err.err
+        );
+
+        // No bucket was created
+        assert!(svm.get_account(&pda).is_none());
+    }
+
     proptest! {
         #![proptest_config(ProptestConfig::with_cases(20))]
 
@@ -255,6 +496,93 @@ mod tests {
             prop_assert!(result.is_ok(), "Different sequence {} should succeed: {:?}", seq2, result);
         }
 
+        /// Property: MarkUsedBatch is a pure optimization. It leaves the
+        /// bucket byte-identical to marking the same sequences one by one,
+        /// and fails exactly when the individual marks first hit a replay.
+        #[test]
+        fn batch_matches_individual_marks(
+            bucket in 0u64..1_000_000,
+            offsets in proptest::collection::btree_set(0..BITS_PER_BUCKET, 1..32)
+                .prop_map(Vec::from_iter)
+                .prop_shuffle(),
+            premarked in proptest::collection::btree_set(0..BITS_PER_BUCKET, 0..4),
+        ) {
+            use solana_noreplay::client::MarkUsedBatch;
+            use solana_noreplay::NoReplayError;
+            use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+            let authority = Keypair::new();
+            let namespace = b"batch";
+            let base = bucket * BITS_PER_BUCKET;
+            let sequences: Vec<u64> = offsets.iter().map(|offset| base + offset).collect();
+            let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, base);
+
+            let send = |svm: &mut LiteSVM, ix: SdkInstruction| {
+                svm.expire_blockhash();
+                let tx = Transaction::new_signed_with_payer(
+                    &[ix],
+                    Some(&authority.pubkey()),
+                    &[&authority],
+                    svm.latest_blockhash(),
+                );
+                svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+            };
+            let mark = |sequence| MarkUsed {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction();
+            let batch = |sequences| MarkUsedBatch {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequences,
+            }
+            .instruction();
+            let state = |svm: &LiteSVM| svm.get_account(&pda).map(|account| (account.lamports, account.data));
+
+            // Two chains with the same sequences already marked
+            let [mut individual, mut batched] = [(); 2].map(|_| {
+                let mut svm = LiteSVM::new();
+                svm.add_program(PROGRAM_ID, &load_program());
+                svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+                for offset in &premarked {
+                    send(&mut svm, mark(base + offset)).unwrap();
+                }
+                svm
+            });
+
+            // One by one, up to the first replay
+            let conflict = sequences
+                .iter()
+                .position(|&sequence| send(&mut individual, mark(sequence)).is_err());
+
+            // The batch of everything before it succeeds with the same result
+            let marked = &sequences[..conflict.unwrap_or(sequences.len())];
+            if !marked.is_empty() {
+                prop_assert_eq!(send(&mut batched, batch(marked)), Ok(()));
+            }
+            prop_assert_eq!(state(&batched), state(&individual));
+
+            // Adding the conflicting sequence fails the batch as a replay,
+            // leaving the bucket untouched
+            if let Some(conflict) = conflict {
+                prop_assert!(premarked.contains(&offsets[conflict]));
+                prop_assert_eq!(
+                    send(&mut batched, batch(&sequences[..=conflict])),
+                    Err(TransactionError::InstructionError(
+                        0,
+                        InstructionError::Custom(NoReplayError::SequenceAlreadyUsed as u32)
+                    ))
+                );
+                prop_assert_eq!(state(&batched), state(&individual));
+            } else {
+                prop_assert!(offsets.iter().all(|offset| !premarked.contains(offset)));
+            }
+        }
+
         /// Property: Incremental sequence numbers all work correctly
         #[test]
         fn incremental_sequences_all_work(base in 0u64..u64::MAX - 10) {
@@ -422,6 +750,7 @@ mod tests {
         );
     }
 
+    #[cfg(not(feature = "strict-namespace"))]
     #[test]
     fn empty_namespace_works() {
         let mut svm = LiteSVM::new();
@@ -527,7 +856,7 @@ mod tests {
         svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
             .unwrap();
 
-        // 64-byte namespace (maximum allowed = 2 chunks * 32 bytes)
+        // 96-byte namespace (maximum allowed = 3 chunks * 32 bytes)
         let namespace = [0xCDu8; MAX_NAMESPACE_LEN];
         let sequence = 1u64;
 
@@ -548,7 +877,7 @@ mod tests {
         let result = svm.send_transaction(tx);
         assert!(
             result.is_ok(),
-            "64-byte namespace should work: {:?}",
+            "96-byte namespace should work: {:?}",
             result
         );
     }
@@ -562,9 +891,9 @@ mod tests {
         svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
             .unwrap();
 
-        // 65-byte namespace (one byte over maximum)
+        // 97-byte namespace (one byte over maximum)
         // Can't use MarkUsed helper because PDA derivation panics with oversized namespace
-        // (chunk 1 would be 33 bytes, exceeding Solana's 32-byte seed limit)
+        // (chunk 2 would be 33 bytes, exceeding Solana's 32-byte seed limit)
         let namespace = [0xEFu8; MAX_NAMESPACE_LEN + 1];
         let sequence = 1u64;
         let data = build_instruction_data(MARK_USED, &namespace, sequence);
@@ -589,7 +918,154 @@ mod tests {
             blockhash,
         );
         let result = svm.send_transaction(tx);
-        assert!(result.is_err(), "65-byte namespace should fail");
+        assert!(result.is_err(), "97-byte namespace should fail");
+    }
+
+    #[test]
+    fn discriminators_are_unique_and_exhaustive() {
+        use solana_noreplay::Discriminator;
+
+        // Strictly ascending implies unique
+        for pair in Discriminator::ALL.windows(2) {
+            assert!((pair[0] as u8) < (pair[1] as u8), "{:?}", pair);
+        }
+
+        // Every defined byte parses back to its variant; every other byte is rejected
+        for byte in 0..=u8::MAX {
+            let defined = Discriminator::ALL.iter().find(|d| **d as u8 == byte);
+            match (Discriminator::try_from(byte), defined) {
+                (Ok(parsed), Some(expected)) => assert_eq!(parsed, *expected),
+                (Err(_), None) => {}
+                (parsed, defined) => panic!("byte {byte}: {:?} vs {:?}", parsed, defined),
+            }
+        }
+
+        // The public constants are the enum's values
+        assert_eq!(CREATE_BITMAP, Discriminator::CreateBitmap as u8);
+        assert_eq!(MARK_USED, Discriminator::MarkUsed as u8);
+    }
+
+    #[test]
+    fn parser_rejects_namespace_len_longer_than_namespace() {
+        use solana_noreplay::InstructionData;
+        use solana_sdk::program_error::ProgramError;
+
+        // namespace_len claims 4 bytes but only 2 follow before the sequence
+        let mut data = 4u16.to_le_bytes().to_vec();
+        data.extend_from_slice(b"ab");
+        data.extend_from_slice(&7u64.to_le_bytes());
+
+        let err = InstructionData::try_from(&data[..]).err().map(u64::from);
+        assert_eq!(err, Some(u64::from(ProgramError::InvalidInstructionData)));
+    }
+
+    #[test]
+    fn parser_rejects_malformed_lengths() {
+        use solana_noreplay::{InstructionData, NoReplayError};
+        use solana_sdk::program_error::ProgramError;
+
+        let parse = |data: &[u8]| InstructionData::try_from(data).err().map(u64::from);
+        let invalid = Some(u64::from(ProgramError::InvalidInstructionData));
+        let valid = build_instruction_data(MARK_USED, b"ns", 7);
+        let body = &valid[1..];
+        assert!(parse(body).is_none());
+
+        // namespace_len beyond MAX_NAMESPACE_LEN, up to u16::MAX, is rejected
+        // before any slicing
+        for len in [MAX_NAMESPACE_LEN as u16 + 1, u16::MAX] {
+            let mut data = body.to_vec();
+            data[..2].copy_from_slice(&len.to_le_bytes());
+            assert_eq!(
+                parse(&data),
+                Some(u64::from(ProgramError::Custom(
+                    NoReplayError::NamespaceTooLong as u32
+                )))
+            );
+        }
+
+        // Truncated sequence
+        assert_eq!(parse(&body[..body.len() - 1]), invalid);
+        assert_eq!(parse(&body[..9]), invalid);
+
+        // Trailing bytes
+        let mut data = body.to_vec();
+        data.push(0);
+        assert_eq!(parse(&data), invalid);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        /// Property: the parser never panics, and anything it accepts is
+        /// exactly the bytes the client would build for it
+        #[test]
+        fn parser_accepts_only_canonical_bytes(
+            namespace_len in 0u16..=80,
+            rest in proptest::collection::vec(any::<u8>(), 0..96),
+        ) {
+            use solana_noreplay::InstructionData;
+
+            // A small length prefix keeps lengths around the valid ones
+            let mut data = namespace_len.to_le_bytes().to_vec();
+            data.extend_from_slice(&rest);
+            if let Ok(parsed) = InstructionData::try_from(&data[..]) {
+                let rebuilt = build_instruction_data(MARK_USED, parsed.namespace, parsed.sequence);
+                prop_assert_eq!(&rebuilt[1..], &data[..]);
+            }
+        }
+
+        /// Property: client-built data parses back to its inputs
+        #[test]
+        fn built_instruction_data_round_trips(
+            namespace in proptest::collection::vec(any::<u8>(), solana_noreplay::MIN_NAMESPACE_LEN..=MAX_NAMESPACE_LEN),
+            sequence: u64,
+        ) {
+            use solana_noreplay::InstructionData;
+
+            let data = build_instruction_data(MARK_USED, &namespace, sequence);
+            let parsed = InstructionData::try_from(&data[1..]);
+            prop_assert!(parsed.is_ok());
+            let parsed = parsed.unwrap();
+            prop_assert_eq!(parsed.namespace, &namespace[..]);
+            prop_assert_eq!(parsed.sequence, sequence);
+        }
+
+        #[test]
+        fn typed_instruction_matches_built_bytes(
+            discriminator: u8,
+            namespace in proptest::collection::vec(any::<u8>(), solana_noreplay::MIN_NAMESPACE_LEN..=MAX_NAMESPACE_LEN),
+            sequence: u64,
+        ) {
+            use solana_noreplay::client::NoReplayInstruction;
+
+            let typed = NoReplayInstruction {
+                discriminator,
+                namespace: namespace.clone(),
+                sequence,
+            };
+            let bytes = typed.to_bytes();
+            prop_assert_eq!(&bytes, &build_instruction_data(discriminator, &namespace, sequence));
+            prop_assert_eq!(NoReplayInstruction::from_bytes(&bytes), Some(typed));
+        }
+
+        #[test]
+        fn typed_instruction_accepts_what_the_program_accepts(
+            namespace_len in 0u16..=80,
+            rest in proptest::collection::vec(any::<u8>(), 0..100),
+        ) {
+            use solana_noreplay::client::NoReplayInstruction;
+            use solana_noreplay::InstructionData;
+
+            let mut data = namespace_len.to_le_bytes().to_vec();
+            data.extend_from_slice(&rest);
+            let mut bytes = vec![MARK_USED];
+            bytes.extend_from_slice(&data);
+
+            prop_assert_eq!(
+                NoReplayInstruction::from_bytes(&bytes).is_some(),
+                InstructionData::try_from(&data[..]).is_ok()
+            );
+        }
     }
 
     #[test]
@@ -631,38 +1107,4809 @@ mod tests {
     }
 
     #[test]
-    fn separate_payer_and_authority_works() {
+    fn mark_used_idempotent_succeeds_twice() {
+        use solana_noreplay::client::MarkUsedIdempotent;
+
         let mut svm = LiteSVM::new();
         svm.add_program(PROGRAM_ID, &load_program());
 
-        let payer = Keypair::new();
         let authority = Keypair::new();
-        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
-        // Authority doesn't need SOL since payer pays
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
 
-        let namespace = b"test";
-        let sequence = 1u64;
+        let builder = MarkUsedIdempotent {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"test",
+            sequence: 42,
+        };
+        let (pda, _) = builder.pda();
+        assert!(svm.get_account(&pda).is_none());
+
+        let mut states = Vec::new();
+        let mut statuses = Vec::new();
+        for _ in 0..2 {
+            let tx = Transaction::new_signed_with_payer(
+                &[builder.instruction()],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            let result = svm.send_transaction(tx);
+            assert!(
+                result.is_ok(),
+                "Idempotent mark should succeed: {:?}",
+                result
+            );
+            let meta = result.unwrap();
+            assert_eq!(meta.return_data.program_id, PROGRAM_ID);
+            statuses.push(meta.return_data.data);
+            states.push(svm.get_account(&pda).expect("bucket should exist").data);
+            svm.expire_blockhash();
+        }
+        assert_eq!(states[0], states[1]);
+        assert!(DecodedBitmap::try_decode(&states[1]).unwrap().is_used(42));
+        // Newly marked, then already marked
+        assert_eq!(statuses, vec![vec![1], vec![0]]);
 
+        // Plain MarkUsed still sees the sequence as used
         let ix = MarkUsed {
-            payer: &payer.pubkey(),
+            payer: &authority.pubkey(),
             authority: &authority.pubkey(),
-            namespace,
-            sequence,
+            namespace: b"test",
+            sequence: 42,
         }
         .instruction();
-
-        let blockhash = svm.latest_blockhash();
         let tx = Transaction::new_signed_with_payer(
             &[ix],
-            Some(&payer.pubkey()),
-            &[&payer, &authority], // Both sign
-            blockhash,
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
         );
-        let result = svm.send_transaction(tx);
-        assert!(
-            result.is_ok(),
-            "Should work with separate payer and authority: {:?}",
-            result
+        assert!(svm.send_transaction(tx).is_err());
+    }
+
+    #[test]
+    fn conflicting_marks_in_same_block_one_succeeds() {
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        let relayer_a = Keypair::new();
+        let relayer_b = Keypair::new();
+        svm.airdrop(&relayer_a.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        svm.airdrop(&relayer_b.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let namespace = b"test";
+        let sequence = 9u64;
+
+        // Two relayers race to mark the same sequence against the same blockhash
+        let blockhash = svm.latest_blockhash();
+        let results: Vec<_> = [&relayer_a, &relayer_b]
+            .into_iter()
+            .map(|relayer| {
+                let ix = MarkUsed {
+                    payer: &relayer.pubkey(),
+                    authority: &authority.pubkey(),
+                    namespace,
+                    sequence,
+                }
+                .instruction();
+                let tx = Transaction::new_signed_with_payer(
+                    &[ix],
+                    Some(&relayer.pubkey()),
+                    &[relayer, &authority],
+                    blockhash,
+                );
+                svm.send_transaction(tx)
+            })
+            .collect();
+
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(
+                    solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn multi_bucket_mark_spans_three_buckets() {
+        use solana_noreplay::client::{DecodedBitmap, MultiBucketMark};
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        let sequences = [
+            3,
+            BITS_PER_BUCKET + 1,
+            4,
+            5 * BITS_PER_BUCKET + 9,
+            BITS_PER_BUCKET + 2,
+        ];
+        let ix = MultiBucketMark {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequences: &sequences,
+        }
+        .instruction();
+        assert_eq!(ix.accounts.len(), 3 + 3);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        for sequence in sequences {
+            let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+            let bitmap = DecodedBitmap::try_decode(&svm.get_account(&pda).unwrap().data).unwrap();
+            assert!(bitmap.is_used(sequence), "sequence {sequence} not marked");
+        }
+
+        // Any already-marked entry fails the whole instruction
+        let ix = MultiBucketMark {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequences: &[2 * BITS_PER_BUCKET, 5 * BITS_PER_BUCKET + 9],
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        let err = svm.send_transaction(tx).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(
+                    solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                )
+            )
+        );
+        let (untouched, _) = derive_bitmap_pda(&authority.pubkey(), namespace, 2 * BITS_PER_BUCKET);
+        assert!(svm.get_account(&untouched).is_none());
+    }
+
+    #[test]
+    fn separate_payer_and_authority_works() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        // Authority doesn't need SOL since payer pays
+
+        let namespace = b"test";
+        let sequence = 1u64;
+
+        let ix = MarkUsed {
+            payer: &payer.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        }
+        .instruction();
+
+        let blockhash = svm.latest_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer.pubkey()),
+            &[&payer, &authority], // Both sign
+            blockhash,
+        );
+        let result = svm.send_transaction(tx);
+        assert!(
+            result.is_ok(),
+            "Should work with separate payer and authority: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn short_bitmap_account_is_malformed() {
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{
+            account::Account, instruction::InstructionError, transaction::TransactionError,
+        };
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let namespace = b"test";
+        let sequence = 7u64;
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+
+        // Program-owned account at the right address, but truncated
+        svm.set_account(
+            pda,
+            Account {
+                lamports: LAMPORTS_PER_SOL,
+                data: vec![0u8; BITMAP_ACCOUNT_SIZE - 1],
+                owner: PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+
+        let ix = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        let err = svm.send_transaction(tx).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::MalformedBitmapAccount as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn mark_used_delegated_requires_authority_signature() {
+        use solana_noreplay::client::{delegation_message, ed25519_instruction, MarkUsedDelegated};
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        let relayer = Keypair::new();
+        let attacker = Keypair::new();
+        svm.airdrop(&relayer.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let namespace = b"test";
+        let sequence = 42u64;
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+        let delegated = MarkUsedDelegated {
+            payer: &relayer.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        };
+        let message = delegation_message(&authority.pubkey(), namespace, sequence);
+
+        // Forged: attacker signs, claiming their own key
+        let forged = attacker.sign_message(&message);
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                ed25519_instruction(&attacker.pubkey(), &forged, &message),
+                delegated.instruction(),
+            ],
+            Some(&relayer.pubkey()),
+            &[&relayer],
+            svm.latest_blockhash(),
+        );
+        let err = svm.send_transaction(tx).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(1, InstructionError::MissingRequiredSignature)
+        );
+
+        // Forged: attacker signs, claiming the authority's key (precompile rejects)
+        let tx = Transaction::new_signed_with_payer(
+            &delegated.instructions(&forged),
+            Some(&relayer.pubkey()),
+            &[&relayer],
+            svm.latest_blockhash(),
+        );
+        assert!(svm.send_transaction(tx).is_err());
+
+        // Authorization for a different sequence is rejected
+        let other = authority.sign_message(&delegation_message(
+            &authority.pubkey(),
+            namespace,
+            sequence + 1,
+        ));
+        let tx = Transaction::new_signed_with_payer(
+            &delegated.instructions(&other),
+            Some(&relayer.pubkey()),
+            &[&relayer],
+            svm.latest_blockhash(),
+        );
+        assert!(svm.send_transaction(tx).is_err());
+        assert!(svm.get_account(&pda).is_none());
+
+        // Valid: authority signs off-chain, relayer submits
+        let signature = authority.sign_message(&message);
+        let tx = Transaction::new_signed_with_payer(
+            &delegated.instructions(&signature),
+            Some(&relayer.pubkey()),
+            &[&relayer],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+        let account = svm.get_account(&pda).unwrap();
+        assert!(
+            solana_noreplay::client::DecodedBitmap::try_decode(&account.data)
+                .unwrap()
+                .is_used(sequence)
+        );
+
+        svm.expire_blockhash();
+
+        // Re-submitting the same authorization is a replay
+        let tx = Transaction::new_signed_with_payer(
+            &delegated.instructions(&signature),
+            Some(&relayer.pubkey()),
+            &[&relayer],
+            svm.latest_blockhash(),
+        );
+        let err = svm.send_transaction(tx).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(
+                    solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                )
+            )
+        );
+    }
+
+    // ============================================================================
+    // Decoder tests
+    // ============================================================================
+
+    #[test]
+    fn decoder_reports_unexpected_size() {
+        use solana_noreplay::client::{BitmapDecoder, DecodeError, DecodedBitmap};
+
+        let good = Pubkey::new_unique();
+        let legacy = Pubkey::new_unique();
+
+        let good_data = bitmap_account_fixture(0, 254, &[1], &PROGRAM_ID).data;
+        let legacy_data = vec![0u8; 33];
+
+        assert_eq!(
+            DecodedBitmap::try_decode(&legacy_data),
+            Err(DecodeError::UnexpectedSize {
+                got: 33,
+                expected: BITMAP_ACCOUNT_SIZE,
+            })
+        );
+
+        let mut decoder = BitmapDecoder::new();
+        let decoded = decoder
+            .decode(&good, &good_data)
+            .expect("correctly sized account should decode");
+        assert_eq!(decoded.bump, 254);
+        assert!(decoded.is_used(1));
+        assert!(!decoded.is_used(0));
+        assert!(decoder.decode(&legacy, &legacy_data).is_none());
+
+        let diagnostics = decoder.into_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].address, legacy);
+        assert_eq!(
+            diagnostics[0].error,
+            DecodeError::UnexpectedSize {
+                got: 33,
+                expected: BITMAP_ACCOUNT_SIZE,
+            }
+        );
+    }
+
+    #[test]
+    fn assert_bucket_size_matches_deployed_program() {
+        use solana_noreplay::client::{assert_bucket_size, BucketSizeMismatch};
+        use solana_sdk::account::Account;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let ix = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"test",
+            sequence: 7,
+        };
+        let (pda, _) = ix.pda();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix.instruction()],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        let account = svm.get_account(&pda).expect("bitmap should exist");
+        assert_eq!(assert_bucket_size(&account, &PROGRAM_ID), Ok(()));
+
+        // Account written by a deployment compiled with 32-byte bitmaps
+        let legacy = Account {
+            lamports: account.lamports,
+            data: vec![0u8; 33],
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let err = assert_bucket_size(&legacy, &PROGRAM_ID).unwrap_err();
+        assert_eq!(
+            err,
+            BucketSizeMismatch::BitmapBytes {
+                deployed: 32,
+                compiled: BITMAP_BYTES,
+            }
+        );
+        assert!(err.to_string().contains("BITMAP_BYTES = 128"));
+
+        let foreign = Account {
+            owner: Pubkey::new_unique(),
+            ..account
+        };
+        assert!(matches!(
+            assert_bucket_size(&foreign, &PROGRAM_ID),
+            Err(BucketSizeMismatch::WrongOwner { .. })
+        ));
+    }
+
+    // ============================================================================
+    // BitmapAccount tests
+    // ============================================================================
+
+    #[test]
+    fn count_used_popcount() {
+        use solana_noreplay::state::BitmapAccount;
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+        assert_eq!(bitmap.count_used(), 0);
+
+        for sequence in [0, 7, 8, 500, BITS_PER_BUCKET - 1] {
+            bitmap.mark_used(sequence);
+        }
+        assert_eq!(bitmap.count_used(), 5);
+
+        // The bump byte is not counted
+        *bitmap.bump = 0xFF;
+        assert_eq!(bitmap.count_used(), 5);
+
+        bitmap.bitmap.fill(0xFF);
+        assert_eq!(bitmap.count_used(), BITS_PER_BUCKET as u32);
+    }
+
+    #[test]
+    fn first_unused_in_bucket() {
+        use solana_noreplay::state::BitmapAccount;
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+        let base = 5 * BITS_PER_BUCKET;
+
+        // Empty: the bucket base
+        assert_eq!(bitmap.first_unused_in_bucket(5), Some(base));
+
+        // Partially filled, with the gap inside a byte past the first
+        for offset in (0..11).chain([12, 100]) {
+            bitmap.mark_used(base + offset);
+        }
+        assert_eq!(bitmap.first_unused_in_bucket(5), Some(base + 11));
+
+        // Only the very last sequence free
+        bitmap.bitmap.fill(0xFF);
+        bitmap.bitmap[BITMAP_BYTES - 1] = 0x7F;
+        assert_eq!(
+            bitmap.first_unused_in_bucket(5),
+            Some(base + BITS_PER_BUCKET - 1)
+        );
+
+        // Full
+        bitmap.mark_used(base + BITS_PER_BUCKET - 1);
+        assert_eq!(bitmap.first_unused_in_bucket(5), None);
+    }
+
+    #[test]
+    fn from_slice_ref_reads_without_mutable_borrow() {
+        use solana_noreplay::state::BitmapAccount;
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        {
+            let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+            *bitmap.bump = 254;
+            bitmap.mark_used(9);
+        }
+
+        let data = data;
+        let view = BitmapAccount::from_slice_ref(&data).unwrap();
+        assert_eq!(*view.bump, 254);
+        assert!(view.is_used(9));
+        assert!(!view.is_used(10));
+
+        assert!(BitmapAccount::from_slice_ref(&data[..BITMAP_ACCOUNT_SIZE - 1]).is_none());
+        assert!(BitmapAccount::from_slice_ref(&[]).is_none());
+    }
+
+    #[test]
+    fn mark_range_byte_aligned() {
+        use solana_noreplay::state::BitmapAccount;
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+
+        let base = 3 * BITS_PER_BUCKET;
+        assert_eq!(bitmap.mark_range(base + 8, 16), Ok(()));
+        assert_eq!(bitmap.bitmap[0], 0x00);
+        assert_eq!(bitmap.bitmap[1], 0xFF);
+        assert_eq!(bitmap.bitmap[2], 0xFF);
+        assert_eq!(bitmap.bitmap[3], 0x00);
+    }
+
+    #[test]
+    fn mark_range_unaligned() {
+        use solana_noreplay::state::BitmapAccount;
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+
+        // Bits 3..13 span the tail of byte 0 and the head of byte 1
+        assert_eq!(bitmap.mark_range(3, 10), Ok(()));
+        assert_eq!(bitmap.bitmap[0], 0b1111_1000);
+        assert_eq!(bitmap.bitmap[1], 0b0001_1111);
+        for sequence in 0..32 {
+            assert_eq!(bitmap.is_used(sequence), (3..13).contains(&sequence));
+        }
+
+        // Range ending exactly at the bucket boundary is allowed
+        assert_eq!(bitmap.mark_range(BITS_PER_BUCKET - 5, 5), Ok(()));
+        assert_eq!(bitmap.bitmap[BITMAP_BYTES - 1], 0b1111_1000);
+    }
+
+    #[test]
+    fn mark_range_conflict_is_atomic() {
+        use solana_noreplay::state::{BitmapAccount, RangeError};
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+
+        let base = BITS_PER_BUCKET;
+        bitmap.mark_used(base + 20);
+        bitmap.mark_used(base + 22);
+
+        assert_eq!(
+            bitmap.mark_range(base + 16, 10),
+            Err(RangeError::AlreadyUsed(base + 20))
+        );
+        // Nothing else was set
+        assert_eq!(bitmap.bitmap[2], 0b0101_0000);
+        assert_eq!(bitmap.bitmap[3], 0);
+    }
+
+    #[test]
+    fn mark_range_rejects_crossing_buckets() {
+        use solana_noreplay::state::{BitmapAccount, RangeError};
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+
+        assert_eq!(
+            bitmap.mark_range(BITS_PER_BUCKET - 4, 5),
+            Err(RangeError::CrossesBucket)
+        );
+        assert_eq!(
+            bitmap.mark_range(0, BITS_PER_BUCKET + 1),
+            Err(RangeError::CrossesBucket)
+        );
+        assert!(bitmap.bitmap.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn bitmap_account_bit_math_follows_its_size() {
+        use solana_noreplay::state::{BitmapAccount, BitmapAccountRef, RangeError};
+
+        type Small<'a> = BitmapAccount<'a, 32>;
+        assert_eq!(Small::BITS, 256);
+        assert_eq!(Small::ACCOUNT_SIZE, BITMAP_ACCOUNT_SIZE - BITMAP_BYTES + 32);
+
+        let mut data = vec![0u8; Small::ACCOUNT_SIZE];
+        assert!(Small::from_slice_sized(&mut data[..Small::ACCOUNT_SIZE - 1]).is_none());
+        {
+            let mut bitmap = Small::init_sized(&mut data, 9).unwrap();
+            // Sequence 256 is the first bit of the next 256-bit bucket
+            assert!(!bitmap.mark_used(255));
+            assert!(!bitmap.mark_used(256 + 3));
+            assert_eq!(bitmap.count_used(), 2);
+            assert_eq!(bitmap.first_unused_in_bucket(1), Some(256));
+            assert_eq!(bitmap.mark_range(250, 7), Err(RangeError::CrossesBucket));
+            assert_eq!(bitmap.mark_range(250, 6), Err(RangeError::AlreadyUsed(255)));
+        }
+
+        let view = BitmapAccountRef::<32>::from_slice_sized(&data).unwrap();
+        assert_eq!(*view.bump, 9);
+        assert!(view.is_used(255) && view.is_used(3) && view.is_used(512 + 3));
+        assert!(!view.is_used(4));
+
+        // The default instantiation is the program's 1024-bit bucket
+        assert_eq!(<BitmapAccount>::BITS, BITS_PER_BUCKET);
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::from_slice(&mut data).unwrap();
+        bitmap.mark_used(256);
+        assert!(bitmap.is_used(256) && !bitmap.is_used(0));
+    }
+
+    #[test]
+    fn bitmap_layout_covers_account_exactly() {
+        use solana_noreplay::client::BITMAP_LAYOUT;
+
+        // Fields are contiguous, in offset order, with no gaps or overlap
+        let mut expected_offset = 0;
+        for (name, offset, size) in BITMAP_LAYOUT {
+            assert_eq!(*offset, expected_offset, "field {name} is misplaced");
+            assert!(*size > 0, "field {name} is empty");
+            expected_offset += size;
+        }
+        assert_eq!(expected_offset, BITMAP_ACCOUNT_SIZE);
+    }
+
+    // ============================================================================
+    // Client helper tests
+    // ============================================================================
+
+    #[test]
+    fn classify_send_error_maps_representative_errors() {
+        use solana_noreplay::client::{classify_send_error, ErrorClass};
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        assert_eq!(
+            classify_send_error(&TransactionError::BlockhashNotFound),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            classify_send_error(&TransactionError::AccountInUse),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            classify_send_error(&TransactionError::InsufficientFundsForFee),
+            ErrorClass::PermanentOther
+        );
+        assert_eq!(
+            classify_send_error(&TransactionError::InstructionError(
+                0,
+                InstructionError::MissingRequiredSignature
+            )),
+            ErrorClass::PermanentOther
+        );
+    }
+
+    #[test]
+    fn no_replay_error_round_trips_through_program_error() {
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::program_error::ProgramError;
+
+        let errors = [
+            NoReplayError::MalformedBitmapAccount,
+            NoReplayError::BucketIndexTooLarge,
+            NoReplayError::BucketNotFullyUsed,
+            NoReplayError::SequenceAlreadyUsed,
+            NoReplayError::NamespaceTooLong,
+            NoReplayError::InvalidBucket,
+            NoReplayError::UnsupportedFormatVersion,
+            NoReplayError::AccountSizeMismatch,
+            NoReplayError::AuthorityMismatch,
+            NoReplayError::SequenceTooOld,
+            NoReplayError::BitmapPdaMismatch,
+            NoReplayError::ReservedNamespace,
+        ];
+        for error in errors {
+            // Encoded as the program does, decoded as a client sees it
+            let encoded =
+                ProgramError::from(u64::from(pinocchio::error::ProgramError::from(error)));
+            assert_eq!(encoded, ProgramError::Custom(error as u32));
+            assert_eq!(NoReplayError::from_program_error(&encoded), Some(error));
+        }
+
+        // Unrelated errors aren't mistaken for the program's
+        assert_eq!(
+            NoReplayError::from_program_error(&ProgramError::MissingRequiredSignature),
+            None
+        );
+        assert_eq!(
+            NoReplayError::from_program_error(&ProgramError::Custom(errors.len() as u32)),
+            None
+        );
+    }
+
+    #[test]
+    fn classify_send_error_treats_replay_as_permanent() {
+        use solana_noreplay::client::{classify_send_error, ErrorClass};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let ix = MarkUsed {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace: b"test",
+                sequence: 9,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            results.push(svm.send_transaction(tx));
+            svm.expire_blockhash();
+        }
+
+        assert!(results[0].is_ok());
+        let failure = results.pop().unwrap().unwrap_err();
+        assert_eq!(
+            classify_send_error(&failure.err),
+            ErrorClass::PermanentReplay
+        );
+    }
+
+    #[test]
+    fn derive_bucket_pda_matches_sequence_derivation() {
+        use solana_noreplay::client::derive_bucket_pda;
+
+        let authority = Pubkey::new_unique();
+        let namespace = b"test";
+
+        assert_eq!(
+            derive_bucket_pda(&authority, namespace, 1),
+            derive_bitmap_pda(&authority, namespace, BITS_PER_BUCKET)
+        );
+        assert_eq!(
+            derive_bucket_pda(&authority, namespace, 0),
+            derive_bitmap_pda(&authority, namespace, BITS_PER_BUCKET - 1)
+        );
+        assert_ne!(
+            derive_bucket_pda(&authority, namespace, 0),
+            derive_bucket_pda(&authority, namespace, 1)
+        );
+    }
+
+    #[test]
+    fn existing_bucket_for_other_seeds_is_rejected() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let sequence = 5u64;
+
+        // Mark the sequence in namespace "a", and create an (empty) bucket for "b"
+        let mark = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"a",
+            sequence,
+        }
+        .instruction();
+        let create = CreateBitmap {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"b",
+            sequence,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[mark, create],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+        svm.expire_blockhash();
+
+        // Replay sequence 5 in "a", substituting the program-owned bucket of "b"
+        let (other_pda, _) = derive_bitmap_pda(&authority.pubkey(), b"b", sequence);
+        let ix = SdkInstruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(other_pda, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            ],
+            data: build_instruction_data(MARK_USED, b"a", sequence),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx);
+        assert!(
+            result.is_err(),
+            "Substituting another bucket must not bypass replay protection"
+        );
+    }
+
+    #[test]
+    fn sequence_paired_with_other_bucket_is_rejected() {
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        // Create bucket 0 (empty)
+        let create = CreateBitmap {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence: 0,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[create],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        // Sequence in bucket 1 paired with bucket 0's PDA would set bit 5 of
+        // bucket 0, i.e. mark sequence 5
+        let sequence = BITS_PER_BUCKET + 5;
+        let (bucket_0, _) = derive_bitmap_pda(&authority.pubkey(), namespace, 0);
+        let ix = SdkInstruction {
+            program_id: PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(bucket_0, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            ],
+            data: build_instruction_data(MARK_USED, namespace, sequence),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        let err = svm.send_transaction(tx).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(solana_noreplay::NoReplayError::BitmapPdaMismatch as u32)
+            )
+        );
+
+        let account = svm.get_account(&bucket_0).unwrap();
+        assert_eq!(
+            DecodedBitmap::try_decode(&account.data)
+                .unwrap()
+                .count_used(),
+            0
+        );
+    }
+
+    #[test]
+    fn count_unique_accounts_dedupes_across_instructions() {
+        use solana_noreplay::client::count_unique_accounts;
+
+        let payer = Pubkey::new_unique();
+        let other_payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+
+        let mut instructions: Vec<_> = (0..3)
+            .map(|bucket| {
+                MarkUsed {
+                    payer: &payer,
+                    authority: &authority,
+                    namespace: b"test",
+                    sequence: bucket * BITS_PER_BUCKET,
+                }
+                .instruction()
+            })
+            .collect();
+
+        // payer, authority, 3 PDAs, system program, NoReplay program
+        assert_eq!(count_unique_accounts(&instructions), 7);
+
+        // Same bucket as the first instruction, different payer: one new key
+        instructions.push(
+            CreateBitmap {
+                payer: &other_payer,
+                authority: &authority,
+                namespace: b"test",
+                sequence: 0,
+            }
+            .instruction(),
+        );
+        assert_eq!(count_unique_accounts(&instructions), 8);
+
+        assert_eq!(count_unique_accounts(&[]), 0);
+    }
+
+    #[test]
+    fn minimal_creates_one_per_distinct_bucket() {
+        use solana_noreplay::client::minimal_creates;
+
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let namespace = b"test";
+
+        let ixs = minimal_creates(&payer, &authority, namespace, &[5, 2000, 5000]);
+        assert_eq!(ixs.len(), 3);
+
+        let expected: Vec<_> = [5, 2000, 5000]
+            .iter()
+            .map(|sequence| derive_bitmap_pda(&authority, namespace, *sequence).0)
+            .collect();
+        let created: Vec<_> = ixs.iter().map(|ix| ix.accounts[2].pubkey).collect();
+        assert_eq!(created, expected);
+        assert!(ixs.iter().all(|ix| ix.data[0] == CREATE_BITMAP));
+
+        // Sequences sharing a bucket collapse into one create
+        let ixs = minimal_creates(&payer, &authority, namespace, &[1, 2, 1023, 1, 1024]);
+        assert_eq!(ixs.len(), 2);
+    }
+
+    #[test]
+    fn chunk_instructions_respects_account_limit() {
+        use solana_noreplay::client::{chunk_instructions, DEFAULT_MAX_ACCOUNTS_PER_TX};
+
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let instructions: Vec<_> = (0..10)
+            .map(|bucket| {
+                MarkUsed {
+                    payer: &payer,
+                    authority: &authority,
+                    namespace: b"test",
+                    sequence: bucket * BITS_PER_BUCKET,
+                }
+                .instruction()
+            })
+            .collect();
+
+        let chunks = chunk_instructions(&instructions, &payer, DEFAULT_MAX_ACCOUNTS_PER_TX);
+        assert_eq!(chunks.len(), 1);
+
+        // payer, authority, system program, NoReplay program + 4 PDAs
+        let chunks = chunk_instructions(&instructions, &payer, 8);
+        let sizes: Vec<_> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![4, 4, 2]);
+
+        // Order is preserved across chunks
+        let flattened: Vec<_> = chunks.into_iter().flatten().collect();
+        assert_eq!(flattened, instructions);
+    }
+
+    #[test]
+    fn decoded_bitmap_to_rle() {
+        use solana_noreplay::client::DecodedBitmap;
+
+        let bucket_index = 7;
+        let base = bucket_index * BITS_PER_BUCKET;
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        {
+            let mut bitmap = solana_noreplay::state::BitmapAccount::init(&mut data, 0).unwrap();
+            for sequence in (0..10).chain(50..52) {
+                bitmap.mark_used(base + sequence);
+            }
+        }
+
+        let decoded = DecodedBitmap::try_decode(&data).unwrap();
+        assert_eq!(
+            decoded.to_rle(bucket_index),
+            vec![(base, 10), (base + 50, 2)]
+        );
+
+        // Full bucket is a single run; empty bucket has none
+        let full = DecodedBitmap {
+            bump: 0,
+            bitmap: [0xFF; BITMAP_BYTES],
+        };
+        assert_eq!(full.to_rle(0), vec![(0, BITS_PER_BUCKET)]);
+        let empty = DecodedBitmap {
+            bump: 0,
+            bitmap: [0; BITMAP_BYTES],
+        };
+        assert!(empty.to_rle(0).is_empty());
+    }
+
+    #[test]
+    fn decoded_bitmap_grid_marks_corners() {
+        use solana_noreplay::client::DecodedBitmap;
+
+        let bucket_index = 3;
+        let base = bucket_index * BITS_PER_BUCKET;
+        let mut bitmap = [0u8; BITMAP_BYTES];
+        bitmap[0] = 0b0000_0001;
+        bitmap[BITMAP_BYTES - 1] = 0b1000_0000;
+        let decoded = DecodedBitmap { bump: 0, bitmap };
+
+        let grid = decoded.to_string_grid(bucket_index);
+        let mut lines = grid.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            format!("bucket 3: sequences {}..={}", base, base + 1023)
+        );
+
+        let rows: Vec<&str> = lines.map(|line| line.rsplit(' ').next().unwrap()).collect();
+        assert_eq!(rows.len(), 32);
+        assert!(rows.iter().all(|row| row.len() == 32));
+        assert_eq!(rows[0], format!("#{}", ".".repeat(31)));
+        assert_eq!(rows[31], format!("{}#", ".".repeat(31)));
+        assert!(rows[1..31].iter().all(|row| !row.contains('#')));
+        assert!(grid.contains(&format!("{} #", base)));
+        assert!(grid.contains(&format!("{} ", base + 992)));
+    }
+
+    #[test]
+    fn namespace_hygiene_flags_padding() {
+        use solana_noreplay::client::{check_namespace_hygiene, Hygiene};
+
+        let mut padded = [0u8; 16];
+        padded[..6].copy_from_slice(b"bridge");
+        assert_eq!(
+            check_namespace_hygiene(&padded),
+            vec![Hygiene::TrailingZeroBytes]
+        );
+
+        assert!(check_namespace_hygiene(b"bridge").is_empty());
+        assert_eq!(
+            check_namespace_hygiene(b" bridge"),
+            vec![Hygiene::SurroundingWhitespace]
+        );
+        assert_eq!(check_namespace_hygiene(b""), vec![Hygiene::Empty]);
+        assert_eq!(
+            check_namespace_hygiene(&[0xFF, 0x01]),
+            vec![Hygiene::NonUtf8]
+        );
+        assert_eq!(
+            check_namespace_hygiene(&[b'a'; MAX_NAMESPACE_LEN]),
+            vec![Hygiene::NearMaxLength]
+        );
+    }
+
+    #[test]
+    fn used_cache_answers_from_fetched_bitmap() {
+        use solana_noreplay::client::{DecodedBitmap, UsedCache};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        let ixs: Vec<_> = [3u64, 10]
+            .iter()
+            .map(|sequence| {
+                MarkUsed {
+                    payer: &authority.pubkey(),
+                    authority: &authority.pubkey(),
+                    namespace,
+                    sequence: *sequence,
+                }
+                .instruction()
+            })
+            .collect();
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        // Single fetch of bucket 0
+        let mut cache = UsedCache::new();
+        assert_eq!(
+            cache.missing_buckets(&authority.pubkey(), namespace, &[3, 4, 2000]),
+            vec![0, 1]
+        );
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, 0);
+        let account = svm.get_account(&pda).unwrap();
+        cache.insert(
+            &authority.pubkey(),
+            namespace,
+            0,
+            DecodedBitmap::try_decode(&account.data).unwrap(),
+        );
+
+        // Answered locally, no further fetch needed for bucket 0
+        assert_eq!(cache.is_used(&authority.pubkey(), namespace, 3), Some(true));
+        assert_eq!(
+            cache.is_used(&authority.pubkey(), namespace, 10),
+            Some(true)
+        );
+        assert_eq!(
+            cache.is_used(&authority.pubkey(), namespace, 4),
+            Some(false)
+        );
+        assert_eq!(cache.is_used(&authority.pubkey(), namespace, 2000), None);
+        assert_eq!(cache.is_used(&authority.pubkey(), b"other", 3), None);
+        assert_eq!(
+            cache.missing_buckets(&authority.pubkey(), namespace, &[3, 4, 2000]),
+            vec![1]
+        );
+
+        cache.invalidate(&authority.pubkey(), namespace, 0);
+        assert_eq!(cache.is_used(&authority.pubkey(), namespace, 3), None);
+
+        // Entries expire after the TTL
+        let mut expiring = UsedCache::with_ttl(std::time::Duration::ZERO);
+        expiring.insert(
+            &authority.pubkey(),
+            namespace,
+            0,
+            DecodedBitmap::try_decode(&account.data).unwrap(),
+        );
+        assert_eq!(expiring.is_used(&authority.pubkey(), namespace, 3), None);
+    }
+
+    #[test]
+    fn priority_transaction_prepends_compute_budget() {
+        use solana_noreplay::client::recommended_compute_budget;
+        use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let payer_key = payer.pubkey();
+        let authority_key = authority.pubkey();
+        let namespace = b"test";
+
+        let builder = || MarkUsed {
+            payer: &payer_key,
+            authority: &authority_key,
+            namespace,
+            sequence: 42,
+        };
+        let expected_mark = builder().instruction();
+        let tx = builder().into_transaction_with_priority(
+            solana_sdk::hash::Hash::default(),
+            &payer,
+            &authority,
+            5_000,
+            None,
+        );
+
+        let message = &tx.message;
+        assert_eq!(message.instructions.len(), 3);
+        let program_ids: Vec<_> = message
+            .instructions
+            .iter()
+            .map(|ix| *ix.program_id(&message.account_keys))
+            .collect();
+        assert_eq!(
+            program_ids,
+            vec![compute_budget::id(), compute_budget::id(), PROGRAM_ID]
+        );
+        assert_eq!(
+            message.instructions[0].data,
+            ComputeBudgetInstruction::set_compute_unit_price(5_000).data
+        );
+        assert_eq!(
+            message.instructions[1].data,
+            ComputeBudgetInstruction::set_compute_unit_limit(recommended_compute_budget(1)).data
+        );
+        assert_eq!(message.instructions[2].data, expected_mark.data);
+        assert!(tx.is_signed());
+    }
+
+    #[test]
+    fn detect_squatted_bucket_statuses() {
+        use solana_noreplay::client::{detect_squatted_bucket, SquatStatus};
+        use solana_sdk::account::Account;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        let (pda, bump) = derive_bitmap_pda(&authority.pubkey(), namespace, 0);
+        assert_eq!(
+            detect_squatted_bucket(
+                svm.get_account(&pda).as_ref(),
+                &authority.pubkey(),
+                namespace,
+                0
+            ),
+            SquatStatus::Absent
+        );
+
+        let ix = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence: 0,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        let healthy = svm.get_account(&pda).unwrap();
+        assert_eq!(
+            detect_squatted_bucket(Some(&healthy), &authority.pubkey(), namespace, 0),
+            SquatStatus::Healthy
+        );
+
+        // Simulated squat: same data, foreign owner
+        let foreign = Account {
+            owner: Pubkey::new_unique(),
+            ..healthy.clone()
+        };
+        assert_eq!(
+            detect_squatted_bucket(Some(&foreign), &authority.pubkey(), namespace, 0),
+            SquatStatus::ForeignOwner
+        );
+
+        let mut corrupted = healthy.clone();
+        corrupted.data[solana_noreplay::BITMAP_TAG_LEN] = bump.wrapping_sub(1);
+        assert_eq!(
+            detect_squatted_bucket(Some(&corrupted), &authority.pubkey(), namespace, 0),
+            SquatStatus::NonCanonicalBump
+        );
+
+        let mut truncated = healthy;
+        truncated.data.truncate(BITMAP_ACCOUNT_SIZE - 1);
+        assert_eq!(
+            detect_squatted_bucket(Some(&truncated), &authority.pubkey(), namespace, 0),
+            SquatStatus::Malformed
+        );
+    }
+
+    #[test]
+    fn parallelizable_groups_have_disjoint_writable_sets() {
+        use solana_noreplay::client::parallelizable_groups;
+        use std::collections::HashSet;
+
+        let payers: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let authority = Pubkey::new_unique();
+        let namespace = b"test";
+
+        // 3 payers x 4 distinct buckets each, plus a repeat of the first
+        let mut ixs = Vec::new();
+        for round in 0..4u64 {
+            for (i, payer) in payers.iter().enumerate() {
+                ixs.push(
+                    CreateBitmap {
+                        payer,
+                        authority: &authority,
+                        namespace,
+                        sequence: (round * payers.len() as u64 + i as u64) * BITS_PER_BUCKET,
+                    }
+                    .instruction(),
+                );
+            }
+        }
+        ixs.push(ixs[0].clone());
+
+        let groups = parallelizable_groups(&ixs);
+        assert_eq!(groups.iter().map(Vec::len).sum::<usize>(), ixs.len());
+
+        for group in &groups {
+            let mut seen = HashSet::new();
+            for ix in group {
+                for meta in ix.accounts.iter().filter(|meta| meta.is_writable) {
+                    assert!(seen.insert(meta.pubkey), "writable account shared in group");
+                }
+            }
+        }
+
+        // Each payer appears once per group, so 3 payers give 3-wide groups
+        assert!(groups.iter().all(|group| group.len() <= payers.len()));
+        assert_eq!(groups[0].len(), payers.len());
+    }
+
+    #[test]
+    fn mark_and_verify_detects_set_bit() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        mark_and_verify(&mut svm, &payer, &authority, b"test", 5).unwrap();
+        mark_and_verify(&mut svm, &payer, &authority, b"test", 6 * BITS_PER_BUCKET).unwrap();
+
+        svm.expire_blockhash();
+        let err = mark_and_verify(&mut svm, &payer, &authority, b"test", 5).unwrap_err();
+        assert!(err.starts_with("MarkUsed failed"), "{err}");
+    }
+
+    #[test]
+    fn derive_bucket_pdas_parallel_matches_sequential() {
+        use solana_noreplay::client::{derive_bucket_pdas, derive_bucket_pdas_parallel};
+
+        let authority = Pubkey::new_unique();
+        let namespace = b"test";
+
+        let sequential = derive_bucket_pdas(&authority, namespace, 100..612);
+        let parallel = derive_bucket_pdas_parallel(&authority, namespace, 100..612);
+        assert_eq!(parallel, sequential);
+        assert_eq!(sequential.first().map(|entry| entry.0), Some(100));
+        assert_eq!(sequential.len(), 512);
+    }
+
+    #[test]
+    fn consumption_stats_aggregates_buckets() {
+        use solana_noreplay::client::{consumption_stats, MAX_MULTIPLE_ACCOUNTS};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        // Buckets 0 and 2 created; bucket 1 left empty
+        for sequence in [1, 2, 2 * BITS_PER_BUCKET + 7] {
+            mark_and_verify(&mut svm, &authority, &authority, namespace, sequence).unwrap();
+        }
+
+        let mut fetches = 0;
+        let stats = consumption_stats(&authority.pubkey(), namespace, 150, |addresses| {
+            assert!(addresses.len() <= MAX_MULTIPLE_ACCOUNTS);
+            fetches += 1;
+            Ok::<_, ()>(
+                addresses
+                    .iter()
+                    .map(|address| svm.get_account(address))
+                    .collect(),
+            )
+        })
+        .unwrap();
+
+        assert_eq!(fetches, 2);
+        assert_eq!(stats.buckets_created, 2);
+        assert_eq!(stats.total_used, 3);
+        assert_eq!(stats.highest_used, Some(2 * BITS_PER_BUCKET + 7));
+        assert_eq!(stats.density, 3.0 / (2.0 * BITS_PER_BUCKET as f64));
+    }
+
+    #[test]
+    fn const_namespace_builders_match_slice_builders() {
+        use solana_noreplay::client::{bit_of, bucket_of};
+        use solana_noreplay::BitmapPdaSeeds;
+
+        const NAMESPACE: [u8; 8] = *b"myapp-v1";
+        const SEQUENCE: u64 = 3 * BITS_PER_BUCKET + 17;
+        const SEEDS: BitmapPdaSeeds<'static> = BitmapPdaSeeds::from_array(&NAMESPACE, SEQUENCE);
+        const BUCKET: u64 = bucket_of(SEQUENCE);
+        const BIT: usize = bit_of(SEQUENCE);
+
+        assert_eq!(BUCKET, 3);
+        assert_eq!(BIT, 17);
+        assert_eq!(SEEDS.bucket_index_u128(), BUCKET as u128);
+        assert_eq!(SEEDS.ns_chunks, [&NAMESPACE[..], &[][..], &[][..]]);
+
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let from_array = MarkUsed::with_namespace(&payer, &authority, &NAMESPACE, SEQUENCE);
+        let from_slice = MarkUsed {
+            payer: &payer,
+            authority: &authority,
+            namespace: &NAMESPACE[..],
+            sequence: SEQUENCE,
+        };
+        assert_eq!(from_array.instruction(), from_slice.instruction());
+        assert_eq!(
+            CreateBitmap::with_namespace(&payer, &authority, &NAMESPACE, SEQUENCE).pda(),
+            from_slice.pda()
+        );
+    }
+
+    #[test]
+    fn dual_mark_spans_namespace_transition() {
+        use solana_noreplay::client::dual_mark;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let (old_ns, new_ns) = (b"app-v1".as_slice(), b"app-v2".as_slice());
+
+        // Consumed under the old namespace before the transition
+        mark_and_verify(&mut svm, &authority, &authority, old_ns, 4).unwrap();
+
+        let send_dual = |svm: &mut LiteSVM, sequence| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &dual_mark(
+                    &authority.pubkey(),
+                    &authority.pubkey(),
+                    old_ns,
+                    new_ns,
+                    sequence,
+                ),
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        // Old consumption blocks the dual mark
+        assert!(send_dual(&mut svm, 4).is_err());
+
+        // Dual mark consumes under both
+        send_dual(&mut svm, 5).unwrap();
+        assert!(send_dual(&mut svm, 5).is_err());
+        for namespace in [old_ns, new_ns] {
+            svm.expire_blockhash();
+            assert!(mark_and_verify(&mut svm, &authority, &authority, namespace, 5).is_err());
+        }
+    }
+
+    #[test]
+    fn pda_matches_only_its_own_triple() {
+        use solana_noreplay::client::pda_matches;
+
+        let authority = Pubkey::new_unique();
+        let namespace = b"test";
+        let sequence = 2 * BITS_PER_BUCKET + 1;
+        let (pda, bump) = derive_bitmap_pda(&authority, namespace, sequence);
+
+        assert_eq!(
+            pda_matches(&pda, &authority, namespace, sequence),
+            Some(bump)
+        );
+        // Any sequence in the same bucket matches
+        assert_eq!(
+            pda_matches(&pda, &authority, namespace, 2 * BITS_PER_BUCKET),
+            Some(bump)
+        );
+        assert_eq!(
+            pda_matches(&pda, &Pubkey::new_unique(), namespace, sequence),
+            None
+        );
+        assert_eq!(pda_matches(&pda, &authority, b"other", sequence), None);
+        assert_eq!(pda_matches(&pda, &authority, namespace, 0), None);
+    }
+
+    #[test]
+    fn fixture_bucket_rejects_prepopulated_sequence() {
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        let (pda, bump) = derive_bitmap_pda(&authority.pubkey(), namespace, 5);
+        svm.set_account(
+            pda,
+            bitmap_account_fixture(rent_for_bitmap(), bump, &[5], &PROGRAM_ID),
+        )
+        .unwrap();
+
+        let err = mark_and_verify(&mut svm, &authority, &authority, namespace, 5).unwrap_err();
+        assert!(
+            err.contains(&format!(
+                "{:?}",
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(
+                        solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                    )
+                )
+            )),
+            "{err}"
+        );
+        mark_and_verify(&mut svm, &authority, &authority, namespace, 6).unwrap();
+    }
+
+    #[test]
+    fn verify_bucket_reports_status() {
+        use solana_noreplay::client::{BucketStatus, VerifyBucket};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+        let sequence = 11u64;
+        let (pda, bump) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+
+        let verify = |svm: &mut LiteSVM| {
+            svm.expire_blockhash();
+            let ix = VerifyBucket {
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            let meta = svm.send_transaction(tx).unwrap();
+            assert_eq!(meta.return_data.program_id, PROGRAM_ID);
+            assert_eq!(meta.return_data.data.len(), 1);
+            BucketStatus::try_from(meta.return_data.data[0]).unwrap()
+        };
+
+        assert_eq!(verify(&mut svm), BucketStatus::NotOwned);
+
+        mark_and_verify(&mut svm, &authority, &authority, namespace, sequence).unwrap();
+        assert_eq!(verify(&mut svm), BucketStatus::Healthy);
+
+        // Simulated corruption: wrong stored bump
+        svm.set_account(
+            pda,
+            bitmap_account_fixture(
+                rent_for_bitmap(),
+                bump.wrapping_sub(1),
+                &[sequence],
+                &PROGRAM_ID,
+            ),
+        )
+        .unwrap();
+        assert_eq!(verify(&mut svm), BucketStatus::WrongAddress);
+
+        let mut truncated = bitmap_account_fixture(rent_for_bitmap(), bump, &[], &PROGRAM_ID);
+        truncated.data.truncate(BITMAP_ACCOUNT_SIZE - 1);
+        svm.set_account(pda, truncated).unwrap();
+        assert_eq!(verify(&mut svm), BucketStatus::WrongSize);
+
+        // Right size and bump, but no tag (e.g. a pre-tag account)
+        let mut untagged = bitmap_account_fixture(rent_for_bitmap(), bump, &[], &PROGRAM_ID);
+        untagged.data[..solana_noreplay::BITMAP_TAG_LEN].fill(0);
+        svm.set_account(pda, untagged).unwrap();
+        assert_eq!(verify(&mut svm), BucketStatus::WrongFormat);
+    }
+
+    #[test]
+    fn sequence_packing_round_trips_without_collisions() {
+        use solana_noreplay::client::SequencePacking;
+        use std::collections::HashSet;
+
+        let packing = SequencePacking { stride: 16 };
+
+        let mut seen = HashSet::new();
+        for ns_id in 0..8 {
+            for local in 0..16 {
+                let sequence = packing.pack_sequence(ns_id, local).unwrap();
+                assert_eq!(packing.unpack_sequence(sequence), (ns_id, local));
+                assert!(seen.insert(sequence), "collision at {sequence}");
+            }
+        }
+        // 8 logical namespaces x 16 sequences share a single bucket
+        assert!(seen.iter().all(|sequence| sequence / BITS_PER_BUCKET == 0));
+
+        // Overflowing the stride would land in the next namespace
+        assert_eq!(packing.pack_sequence(0, 16), None);
+        assert_eq!(packing.pack_sequence(u64::MAX, 0), None);
+    }
+
+    #[cfg(feature = "max-bucket")]
+    #[test]
+    fn max_bucket_index_is_enforced_at_boundary() {
+        use solana_noreplay::{client::MAX_BUCKET_INDEX, NoReplayError};
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        // Last sequence of the last allowed bucket
+        let last = (MAX_BUCKET_INDEX + 1) * BITS_PER_BUCKET - 1;
+        mark_and_verify(&mut svm, &authority, &authority, namespace, last).unwrap();
+
+        // First sequence of the next bucket
+        let err =
+            mark_and_verify(&mut svm, &authority, &authority, namespace, last + 1).unwrap_err();
+        assert!(
+            err.contains(&format!(
+                "{:?}",
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(NoReplayError::BucketIndexTooLarge as u32)
+                )
+            )),
+            "{err}"
+        );
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn replay_rejection_is_logged() {
+        use solana_noreplay::processor::REPLAY_LOG;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let mut send = || {
+            svm.expire_blockhash();
+            let ix = MarkUsed {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace: b"test",
+                sequence: 7,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let expected = format!("Program log: {REPLAY_LOG}");
+
+        // A successful mark logs nothing
+        let first = send().unwrap();
+        assert!(!first.logs.contains(&expected), "{:?}", first.logs);
+
+        let replay = send().unwrap_err();
+        assert!(
+            replay.meta.logs.contains(&expected),
+            "{:?}",
+            replay.meta.logs
+        );
+    }
+
+    #[test]
+    fn rent_delta_for_slot_header_upgrade() {
+        use solana_noreplay::client::{rent_delta_for_upgrade, total_rent_delta_for_upgrade};
+
+        let rent = Rent::default();
+        let delta = rent_delta_for_upgrade(129, 137);
+        assert_eq!(delta, rent.minimum_balance(137) - rent.minimum_balance(129));
+        assert_eq!(
+            delta,
+            8 * rent.lamports_per_byte_year * rent.exemption_threshold as u64
+        );
+        assert_eq!(rent_delta_for_upgrade(137, 129), 0);
+
+        assert_eq!(
+            total_rent_delta_for_upgrade([129, 129, 137], 137),
+            2 * delta
+        );
+    }
+
+    /// Address of the `noreplay-caller` test program.
+    #[cfg(feature = "anchor-e2e")]
+    const CALLER_ID: Pubkey = solana_sdk::pubkey!("9N5Sc2FZ9ob5GwiU3SP3dxEma2uKFTb3rTHRcUjWDtdV");
+
+    /// LiteSVM with NoReplay and `noreplay-caller` loaded, plus a funded payer
+    /// and the caller's emitter PDA (its NoReplay authority).
+    #[cfg(feature = "anchor-e2e")]
+    fn caller_svm() -> (LiteSVM, Keypair, Pubkey) {
+        let caller = std::fs::read("../target/deploy/noreplay_caller.so")
+            .expect("Caller not built. Run `just test-anchor`.");
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+        svm.add_program(CALLER_ID, &caller);
+
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let (emitter, _) = Pubkey::find_program_address(&[b"emitter"], &CALLER_ID);
+        (svm, payer, emitter)
+    }
+
+    /// A `noreplay-caller` instruction taking `(namespace, sequence)`.
+    #[cfg(feature = "anchor-e2e")]
+    fn caller_instruction(
+        method: &str,
+        payer: &Pubkey,
+        emitter: &Pubkey,
+        namespace: &[u8],
+        sequence: u64,
+    ) -> SdkInstruction {
+        // Anchor instruction: sighash("global:<method>") + borsh(Vec<u8>, u64)
+        let mut data = solana_sdk::hash::hashv(&[format!("global:{method}").as_bytes()]).to_bytes()
+            [..8]
+            .to_vec();
+        data.extend_from_slice(&(namespace.len() as u32).to_le_bytes());
+        data.extend_from_slice(namespace);
+        data.extend_from_slice(&sequence.to_le_bytes());
+        SdkInstruction {
+            program_id: CALLER_ID,
+            accounts: vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(*emitter, false),
+                AccountMeta::new(derive_bitmap_pda(emitter, namespace, sequence).0, false),
+                AccountMeta::new_readonly(PROGRAM_ID, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            ],
+            data,
+        }
+    }
+
+    #[cfg(feature = "anchor-e2e")]
+    #[test]
+    fn anchor_interface_cpi_marks_and_rejects_replay() {
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let (mut svm, payer, emitter) = caller_svm();
+        let namespace = b"anchor-e2e";
+        let sequence = 77u64;
+        let (bitmap, _) = derive_bitmap_pda(&emitter, namespace, sequence);
+        let consume = caller_instruction("consume", &payer.pubkey(), &emitter, namespace, sequence);
+
+        let send = |svm: &mut LiteSVM| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                std::slice::from_ref(&consume),
+                Some(&payer.pubkey()),
+                &[&payer],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        send(&mut svm).unwrap();
+        let account = svm.get_account(&bitmap).unwrap();
+        assert!(DecodedBitmap::try_decode(&account.data)
+            .unwrap()
+            .is_used(sequence));
+
+        // Replay through the same CPI path fails inside NoReplay
+        let err = send(&mut svm).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(
+                    solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                )
+            )
+        );
+    }
+
+    #[cfg(feature = "anchor-e2e")]
+    #[test]
+    fn global_namespace_is_only_marked_through_caller_cpi() {
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let (mut svm, payer, _) = caller_svm();
+        let (global, _) = Pubkey::find_program_address(&[b"noreplay-global"], &CALLER_ID);
+        let namespace = b"global";
+        let sequence = 9u64;
+        let (bitmap, _) = derive_bitmap_pda(&global, namespace, sequence);
+
+        let send = |svm: &mut LiteSVM, ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&payer.pubkey()),
+                &[&payer],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        // The caller signs for its global PDA via invoke_signed
+        send(
+            &mut svm,
+            caller_instruction(
+                "consume_global",
+                &payer.pubkey(),
+                &global,
+                namespace,
+                sequence,
+            ),
+        )
+        .unwrap();
+        let account = svm.get_account(&bitmap).unwrap();
+        assert!(DecodedBitmap::try_decode(&account.data)
+            .unwrap()
+            .is_used(sequence));
+
+        // No keypair exists for the PDA, so a direct mark can't carry its
+        // signature and is rejected
+        let mut direct = MarkUsed {
+            payer: &payer.pubkey(),
+            authority: &global,
+            namespace,
+            sequence: sequence + 1,
+        }
+        .instruction();
+        direct.accounts[1].is_signer = false;
+        let err = send(&mut svm, direct).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature)
+        );
+        let account = svm.get_account(&bitmap).unwrap();
+        assert!(!DecodedBitmap::try_decode(&account.data)
+            .unwrap()
+            .is_used(sequence + 1));
+    }
+
+    #[cfg(feature = "anchor-e2e")]
+    #[test]
+    fn anchor_interface_mark_result_reports_pda_and_creation() {
+        let (mut svm, payer, emitter) = caller_svm();
+        let namespace = b"anchor-e2e";
+
+        // `consume` returns the borsh-encoded MarkResult:
+        // pda (32) | newly_created (1) | bucket (8) | newly_marked (1)
+        let mut consume = |sequence: u64| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[caller_instruction(
+                    "consume",
+                    &payer.pubkey(),
+                    &emitter,
+                    namespace,
+                    sequence,
+                )],
+                Some(&payer.pubkey()),
+                &[&payer],
+                svm.latest_blockhash(),
+            );
+            let meta = svm.send_transaction(tx).unwrap();
+            assert_eq!(meta.return_data.program_id, CALLER_ID);
+            let data = meta.return_data.data;
+            assert_eq!(data.len(), 42);
+            (
+                Pubkey::try_from(&data[..32]).unwrap(),
+                data[32] != 0,
+                u64::from_le_bytes(data[33..41].try_into().unwrap()),
+                data[41] != 0,
+            )
+        };
+
+        // First mark in a bucket creates it
+        let sequence = 3 * BITS_PER_BUCKET + 5;
+        let (pda, _) = derive_bitmap_pda(&emitter, namespace, sequence);
+        assert_eq!(consume(sequence), (pda, true, 3, true));
+
+        // A second sequence in the same bucket reuses it
+        assert_eq!(consume(sequence + 1), (pda, false, 3, true));
+
+        // Another bucket has its own PDA
+        let sequence = 4 * BITS_PER_BUCKET;
+        let (pda, _) = derive_bitmap_pda(&emitter, namespace, sequence);
+        assert_eq!(consume(sequence), (pda, true, 4, true));
+    }
+
+    #[cfg(feature = "anchor-e2e")]
+    #[test]
+    fn anchor_interface_mark_if_unused_skips_replay() {
+        let (mut svm, payer, emitter) = caller_svm();
+        let namespace = b"anchor-e2e";
+        let sequence = 77u64;
+        let consume_if_unused = caller_instruction(
+            "consume_if_unused",
+            &payer.pubkey(),
+            &emitter,
+            namespace,
+            sequence,
+        );
+
+        // Borsh bool as return data: whether the sequence was newly marked
+        let mut send = || {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                std::slice::from_ref(&consume_if_unused),
+                Some(&payer.pubkey()),
+                &[&payer],
+                svm.latest_blockhash(),
+            );
+            let meta = svm.send_transaction(tx).unwrap();
+            assert_eq!(meta.return_data.program_id, CALLER_ID);
+            meta.return_data.data
+        };
+
+        assert_eq!(send(), [1]);
+        // The replay succeeds, reporting the sequence as already used
+        assert_eq!(send(), [0]);
+
+        let (bitmap, _) = derive_bitmap_pda(&emitter, namespace, sequence);
+        let account = svm.get_account(&bitmap).unwrap();
+        let decoded = DecodedBitmap::try_decode(&account.data).unwrap();
+        assert!(decoded.is_used(sequence));
+        assert_eq!(decoded.count_used(), 1);
+    }
+
+    #[test]
+    fn explain_derivation_reports_seeds() {
+        use solana_noreplay::client::explain_derivation;
+
+        let authority = Pubkey::new_unique();
+        let namespace = [7u8; 34];
+        let sequence = 5 * BITS_PER_BUCKET + 3;
+
+        let explained = explain_derivation(&authority, &namespace, sequence);
+        assert_eq!(explained.bucket_index, sequence / BITS_PER_BUCKET);
+        assert_eq!(explained.bucket_bytes, 5u64.to_le_bytes());
+        assert_eq!(explained.authority_bytes, authority.to_bytes());
+        assert_eq!(explained.ns_chunk_0, namespace[..32]);
+        assert_eq!(explained.ns_chunk_1, namespace[32..]);
+        assert_eq!(
+            (explained.pda, explained.bump),
+            derive_bitmap_pda(&authority, &namespace, sequence)
+        );
+    }
+
+    #[test]
+    fn check_used_returns_bitmap_state() {
+        use solana_noreplay::client::CheckUsed;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        let reader = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        svm.airdrop(&reader.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let namespace = b"test";
+
+        // Permissionless: signed only by an unrelated fee payer
+        let check = |svm: &mut LiteSVM, sequence| {
+            svm.expire_blockhash();
+            let ix = CheckUsed {
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&reader.pubkey()),
+                &[&reader],
+                svm.latest_blockhash(),
+            );
+            let meta = svm.send_transaction(tx).unwrap();
+            assert_eq!(meta.return_data.program_id, PROGRAM_ID);
+            meta.return_data.data
+        };
+
+        // Bucket doesn't exist yet
+        assert_eq!(check(&mut svm, 8), vec![0]);
+
+        mark_and_verify(&mut svm, &authority, &authority, namespace, 8).unwrap();
+        assert_eq!(check(&mut svm, 8), vec![1]);
+        assert_eq!(check(&mut svm, 9), vec![0]);
+    }
+
+    #[test]
+    fn mark_used_system_program_optional_for_existing_bucket() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+
+        let send = |svm: &mut LiteSVM, ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let authority_key = authority.pubkey();
+        let mark = |sequence| MarkUsed {
+            payer: &authority_key,
+            authority: &authority_key,
+            namespace,
+            sequence,
+        };
+
+        // Creation needs the system program
+        let result = send(&mut svm, mark(0).instruction_for_existing_bucket());
+        assert!(result.is_err(), "three-account create should fail");
+        let (pda, _) = mark(0).pda();
+        assert!(svm.get_account(&pda).is_none());
+
+        // Four-account path creates the bucket
+        send(&mut svm, mark(0).instruction()).unwrap();
+
+        // Bucket exists: three accounts are enough
+        let ix = mark(1).instruction_for_existing_bucket();
+        assert_eq!(ix.accounts.len(), 3);
+        send(&mut svm, ix).unwrap();
+
+        let account = svm.get_account(&pda).unwrap();
+        let decoded = DecodedBitmap::try_decode(&account.data).unwrap();
+        assert!(decoded.is_used(0));
+        assert!(decoded.is_used(1));
+
+        // Replay protection is unchanged on the three-account path
+        let result = send(&mut svm, mark(1).instruction_for_existing_bucket());
+        assert!(result.is_err(), "replay should fail");
+    }
+
+    #[test]
+    fn prefund_job_resumes_from_checkpoint() {
+        use solana_noreplay::client::PrefundJob;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let payer = Keypair::new();
+        let authority = Pubkey::new_unique();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let namespace = b"prefund";
+
+        let mut sent = Vec::new();
+        let mut send = |svm: &mut LiteSVM, batch: &[SdkInstruction]| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                batch,
+                Some(&payer.pubkey()),
+                &[&payer],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).unwrap();
+            sent.extend(batch.iter().map(|ix| ix.accounts[2].pubkey));
+        };
+
+        // First session: two confirmed batches, then a crash before the
+        // third batch is sent
+        let mut job = PrefundJob::new(&payer.pubkey(), &authority, namespace, 5..15);
+        for _ in 0..2 {
+            let batch = job.next_batch(3);
+            send(&mut svm, &batch);
+            job.confirm();
+        }
+        let _lost = job.next_batch(3);
+        let saved = job.to_bytes();
+        drop(job);
+
+        // Second session resumes from the checkpoint
+        let mut job = PrefundJob::from_bytes(&saved).unwrap();
+        assert_eq!(job.checkpoint(), 11);
+        while !job.is_complete() {
+            let batch = job.next_batch(3);
+            send(&mut svm, &batch);
+            job.confirm();
+        }
+        assert!(job.next_batch(3).is_empty());
+
+        // Every bucket created exactly once
+        let expected: Vec<Pubkey> = (5..15)
+            .map(|bucket| {
+                solana_noreplay::client::derive_bucket_pda(&authority, namespace, bucket).0
+            })
+            .collect();
+        assert_eq!(sent, expected);
+        for pda in &expected {
+            assert_eq!(svm.get_account(pda).unwrap().owner, PROGRAM_ID);
+        }
+
+        assert!(PrefundJob::from_bytes(&saved[..saved.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn mark_used_batch_marks_one_bucket_atomically() {
+        use solana_noreplay::client::MarkUsedBatch;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"batch";
+
+        let send = |svm: &mut LiteSVM, sequences: &[u64]| {
+            svm.expire_blockhash();
+            let ix = MarkUsedBatch {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequences,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, 0);
+        let bitmap = |svm: &LiteSVM| {
+            DecodedBitmap::try_decode(&svm.get_account(&pda).unwrap().data).unwrap()
+        };
+
+        // Creates the bucket and marks every sequence
+        send(&mut svm, &[3, 1, 1023]).unwrap();
+        let decoded = bitmap(&svm);
+        for sequence in [1, 3, 1023] {
+            assert!(decoded.is_used(sequence));
+        }
+        assert_eq!(decoded.count_used(), 3);
+
+        // Crossing into the next bucket is rejected
+        assert!(send(&mut svm, &[5, BITS_PER_BUCKET]).is_err());
+
+        // One replayed sequence fails the whole batch
+        assert!(send(&mut svm, &[4, 3, 6]).is_err());
+        assert!(send(&mut svm, &[7, 7]).is_err());
+        assert_eq!(bitmap(&svm).count_used(), 3);
+    }
+
+    #[test]
+    fn mark_used_range_marks_contiguous_span() {
+        use solana_noreplay::client::MarkUsedRange;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"range";
+
+        let send = |svm: &mut LiteSVM, start: u64, end: u64| {
+            svm.expire_blockhash();
+            let ix = MarkUsedRange {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                start,
+                end,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let base = BITS_PER_BUCKET;
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, base);
+        let bitmap = |svm: &LiteSVM| {
+            DecodedBitmap::try_decode(&svm.get_account(&pda).unwrap().data).unwrap()
+        };
+
+        send(&mut svm, base + 5, base + 20).unwrap();
+        let decoded = bitmap(&svm);
+        assert_eq!(decoded.count_used(), 16);
+        assert!(!decoded.is_used(base + 4));
+        assert!(decoded.is_used(base + 5));
+        assert!(decoded.is_used(base + 20));
+        assert!(!decoded.is_used(base + 21));
+
+        // Single-sequence range
+        send(&mut svm, base + 30, base + 30).unwrap();
+
+        // Inverted and bucket-crossing ranges are rejected
+        assert!(send(&mut svm, base + 50, base + 40).is_err());
+        assert!(send(&mut svm, base + 1000, 2 * base + 3).is_err());
+
+        // Overlap with a marked sequence fails without marking anything
+        assert!(send(&mut svm, base + 21, base + 30).is_err());
+        assert_eq!(bitmap(&svm).count_used(), 17);
+    }
+
+    // ========================================================================
+    // Host-side processor tests (no SVM)
+    // ========================================================================
+
+    /// Backing memory for an `AccountView` in the runtime's input layout:
+    /// the account header immediately followed by its data.
+    struct MockAccount {
+        buffer: Vec<u64>,
+    }
+
+    impl MockAccount {
+        fn new(
+            address: [u8; 32],
+            owner: [u8; 32],
+            is_signer: bool,
+            is_writable: bool,
+            data: &[u8],
+        ) -> Self {
+            use solana_account_view::RuntimeAccount;
+            use solana_address::Address;
+
+            let header = std::mem::size_of::<RuntimeAccount>();
+            let mut buffer = vec![0u64; (header + data.len()).div_ceil(8)];
+            let raw = buffer.as_mut_ptr() as *mut RuntimeAccount;
+            // SAFETY: The buffer is u64-aligned and large enough for the
+            // header plus data.
+            unsafe {
+                raw.write(RuntimeAccount {
+                    // All bits set: not borrowed
+                    borrow_state: u8::MAX,
+                    is_signer: is_signer as u8,
+                    is_writable: is_writable as u8,
+                    executable: 0,
+                    resize_delta: 0,
+                    address: Address::new_from_array(address),
+                    owner: Address::new_from_array(owner),
+                    lamports: 1_000_000,
+                    data_len: data.len() as u64,
+                });
+                std::ptr::copy_nonoverlapping(
+                    data.as_ptr(),
+                    (raw as *mut u8).add(header),
+                    data.len(),
+                );
+            }
+            Self { buffer }
+        }
+
+        fn view(&mut self) -> solana_account_view::AccountView {
+            // SAFETY: The buffer holds a valid header and outlives the view
+            // within each test.
+            unsafe {
+                solana_account_view::AccountView::new_unchecked(self.buffer.as_mut_ptr() as *mut _)
+            }
+        }
+    }
+
+    #[test]
+    fn host_process_instruction_rejects_unsigned_authority() {
+        use solana_address::Address;
+        use solana_noreplay::processor::process_instruction;
+        use solana_sdk::program_error::ProgramError;
+
+        let program_id = Address::new_from_array(PROGRAM_ID.to_bytes());
+        let system = [0u8; 32];
+        let authority = Pubkey::new_unique().to_bytes();
+        let mut payer = MockAccount::new(Pubkey::new_unique().to_bytes(), system, true, true, &[]);
+        let mut authority = MockAccount::new(authority, system, false, false, &[]);
+        let mut pda = MockAccount::new(Pubkey::new_unique().to_bytes(), system, false, true, &[]);
+        let mut system_program = MockAccount::new(system, system, false, false, &[]);
+        let accounts = [
+            payer.view(),
+            authority.view(),
+            pda.view(),
+            system_program.view(),
+        ];
+
+        let data = build_instruction_data(MARK_USED, b"host", 1);
+        let err = process_instruction(&program_id, &accounts, &data)
+            .err()
+            .map(u64::from);
+        assert_eq!(err, Some(u64::from(ProgramError::MissingRequiredSignature)));
+
+        // Unknown discriminator
+        let err = process_instruction(&program_id, &accounts, &[0xFF])
+            .err()
+            .map(u64::from);
+        assert_eq!(err, Some(u64::from(ProgramError::InvalidInstructionData)));
+    }
+
+    #[test]
+    fn host_check_used_missing_bucket_is_unused() {
+        use solana_address::Address;
+        use solana_noreplay::processor::process_instruction;
+
+        let program_id = Address::new_from_array(PROGRAM_ID.to_bytes());
+        let system = [0u8; 32];
+        let mut authority =
+            MockAccount::new(Pubkey::new_unique().to_bytes(), system, false, false, &[]);
+        let mut pda = MockAccount::new(Pubkey::new_unique().to_bytes(), system, false, false, &[]);
+        let accounts = [authority.view(), pda.view()];
+
+        // Not program-owned: reported as unused without touching the account
+        let data = build_instruction_data(solana_noreplay::CHECK_USED, b"host", 1);
+        assert!(process_instruction(&program_id, &accounts, &data).is_ok());
+    }
+
+    #[test]
+    fn host_mark_used_committed_chains_message_hashes() {
+        use solana_address::Address;
+        use solana_noreplay::client::{
+            derive_committed_pda, next_accumulator, DecodedCommittedBitmap, MarkUsedCommitted,
+            COMMITTED_ACCOUNT_SIZE, COMMITTED_TAG,
+        };
+        use solana_noreplay::processor::process_instruction;
+        use solana_sdk::program_error::ProgramError;
+
+        let program_id = Address::new_from_array(PROGRAM_ID.to_bytes());
+        let system = [0u8; 32];
+        let namespace = b"committed";
+
+        // Apply `marks` in order to a fresh bucket; returns each mark's result
+        // and the final account data.
+        let run = |marks: &[(u64, [u8; 32])]| {
+            let payer_key = Pubkey::new_unique();
+            let authority_key = Pubkey::new_from_array([7; 32]);
+            let (bucket_key, bump) = derive_committed_pda(&authority_key, namespace, 0);
+            let mut data = [0u8; COMMITTED_ACCOUNT_SIZE];
+            data[..COMMITTED_TAG.len()].copy_from_slice(&COMMITTED_TAG);
+            data[COMMITTED_TAG.len()] = bump;
+
+            let mut payer = MockAccount::new(payer_key.to_bytes(), system, true, true, &[]);
+            let mut authority =
+                MockAccount::new(authority_key.to_bytes(), system, true, false, &[]);
+            let mut bucket = MockAccount::new(
+                bucket_key.to_bytes(),
+                PROGRAM_ID.to_bytes(),
+                false,
+                true,
+                &data,
+            );
+            let mut system_program = MockAccount::new(system, system, false, false, &[]);
+
+            let results: Vec<_> = marks
+                .iter()
+                .map(|(sequence, message_hash)| {
+                    let ix = MarkUsedCommitted {
+                        payer: &payer_key,
+                        authority: &authority_key,
+                        namespace,
+                        sequence: *sequence,
+                        message_hash,
+                    }
+                    .instruction();
+                    let accounts = [
+                        payer.view(),
+                        authority.view(),
+                        bucket.view(),
+                        system_program.view(),
+                    ];
+                    process_instruction(&program_id, &accounts, &ix.data)
+                        .err()
+                        .map(u64::from)
+                })
+                .collect();
+            let view = bucket.view();
+            // SAFETY: No other borrows of the account are live.
+            let data = unsafe { view.borrow_unchecked() }.to_vec();
+            (results, DecodedCommittedBitmap::try_decode(&data).unwrap())
+        };
+
+        let (first, second) = ([1u8; 32], [2u8; 32]);
+        let (results, bucket) = run(&[(3, first), (9, second)]);
+        assert_eq!(results, [None, None]);
+        assert!(bucket.is_used(3) && bucket.is_used(9) && !bucket.is_used(4));
+        let expected = next_accumulator(&next_accumulator(&[0; 32], 3, &first), 9, &second);
+        assert_eq!(bucket.accumulator, expected);
+
+        // Same marks, same accumulator
+        assert_eq!(run(&[(3, first), (9, second)]).1, bucket);
+
+        // A replay fails and leaves the accumulator alone, even with a
+        // different message hash
+        let (results, replayed) = run(&[(3, first), (9, second), (3, second)]);
+        assert_eq!(
+            results,
+            [
+                None,
+                None,
+                Some(u64::from(ProgramError::Custom(
+                    solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                )))
+            ]
+        );
+        assert_eq!(replayed, bucket);
+
+        // Order and message hashes are both committed
+        assert_ne!(run(&[(9, second), (3, first)]).1.accumulator, expected);
+        assert_ne!(run(&[(3, second), (9, first)]).1.accumulator, expected);
+    }
+
+    #[test]
+    fn tx_marks_sequence_matches_expected_mark() {
+        use solana_noreplay::client::tx_marks_sequence;
+
+        let authority = Keypair::new();
+        let namespace = b"proof";
+        let blockhash = solana_sdk::hash::Hash::new_unique();
+        let tx_for = |ix: SdkInstruction| {
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                blockhash,
+            )
+        };
+        let authority_key = authority.pubkey();
+        let mark = |sequence| MarkUsed {
+            payer: &authority_key,
+            authority: &authority_key,
+            namespace,
+            sequence,
+        };
+
+        let tx = tx_for(mark(42).instruction());
+        assert!(tx_marks_sequence(
+            &tx,
+            &PROGRAM_ID,
+            &authority.pubkey(),
+            namespace,
+            42
+        ));
+
+        // Different sequence, namespace, authority or program
+        assert!(!tx_marks_sequence(
+            &tx,
+            &PROGRAM_ID,
+            &authority.pubkey(),
+            namespace,
+            43
+        ));
+        assert!(!tx_marks_sequence(
+            &tx,
+            &PROGRAM_ID,
+            &authority.pubkey(),
+            b"other",
+            42
+        ));
+        assert!(!tx_marks_sequence(
+            &tx,
+            &PROGRAM_ID,
+            &Pubkey::new_unique(),
+            namespace,
+            42
+        ));
+        assert!(!tx_marks_sequence(
+            &tx,
+            &Pubkey::new_unique(),
+            &authority.pubkey(),
+            namespace,
+            42
+        ));
+
+        // Right data but the wrong bucket account
+        let mut ix = mark(42).instruction();
+        ix.accounts[2].pubkey = mark(BITS_PER_BUCKET).pda().0;
+        assert!(!tx_marks_sequence(
+            &tx_for(ix),
+            &PROGRAM_ID,
+            &authority.pubkey(),
+            namespace,
+            42
+        ));
+
+        // CreateBitmap for the same bucket is not a mark
+        let create = CreateBitmap {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence: 42,
+        }
+        .instruction();
+        assert!(!tx_marks_sequence(
+            &tx_for(create),
+            &PROGRAM_ID,
+            &authority.pubkey(),
+            namespace,
+            42
+        ));
+    }
+
+    #[test]
+    fn range_any_used_reports_partial_empty_and_full_ranges() {
+        use solana_noreplay::client::{MarkUsedRange, RangeAnyUsed};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"gaps";
+
+        let send = |svm: &mut LiteSVM, ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let query = |svm: &mut LiteSVM, start: u64, count: u64| {
+            let ix = RangeAnyUsed {
+                authority: &authority.pubkey(),
+                namespace,
+                start,
+                count,
+            }
+            .instruction();
+            send(svm, ix).map(|meta| meta.return_data.data)
+        };
+
+        // Bucket doesn't exist yet
+        assert_eq!(query(&mut svm, 0, BITS_PER_BUCKET).unwrap(), vec![0]);
+
+        let ix = MarkUsedRange {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            start: 20,
+            end: 29,
+        }
+        .instruction();
+        send(&mut svm, ix).unwrap();
+
+        // Empty: entirely before or after the marked run
+        assert_eq!(query(&mut svm, 0, 20).unwrap(), vec![0]);
+        assert_eq!(query(&mut svm, 30, BITS_PER_BUCKET - 30).unwrap(), vec![0]);
+        // Partial overlap at either edge
+        assert_eq!(query(&mut svm, 15, 6).unwrap(), vec![1]);
+        assert_eq!(query(&mut svm, 29, 100).unwrap(), vec![1]);
+        // Full: inside the run, and the whole bucket
+        assert_eq!(query(&mut svm, 22, 3).unwrap(), vec![1]);
+        assert_eq!(query(&mut svm, 0, BITS_PER_BUCKET).unwrap(), vec![1]);
+
+        // Empty and bucket-crossing runs are rejected
+        assert!(query(&mut svm, 10, 0).is_err());
+        assert!(query(&mut svm, 1000, 25).is_err());
+    }
+
+    #[test]
+    fn close_bitmap_requires_full_bucket_and_reopens_empty() {
+        use solana_noreplay::client::{CloseBitmap, MarkUsedRange};
+        use solana_sdk::instruction::InstructionError;
+        use solana_sdk::transaction::TransactionError;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"close";
+
+        let send = |svm: &mut LiteSVM, ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let mark_range = |start, end| {
+            MarkUsedRange {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                start,
+                end,
+            }
+            .instruction()
+        };
+        let close = CloseBitmap {
+            authority: &authority.pubkey(),
+            recipient: &recipient,
+            namespace,
+            sequence: 7,
+        }
+        .instruction();
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, 7);
+
+        // Partially used bucket can't be closed
+        send(&mut svm, mark_range(0, BITS_PER_BUCKET - 2)).unwrap();
+        let err = send(&mut svm, close.clone()).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(solana_noreplay::NoReplayError::BucketNotFullyUsed as u32)
+            )
+        );
+
+        // Authority must sign (a third party pays and signs instead)
+        let relayer = Keypair::new();
+        svm.airdrop(&relayer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let mut unsigned = close.clone();
+        unsigned.accounts[0].is_signer = false;
+        let tx = Transaction::new_signed_with_payer(
+            &[unsigned],
+            Some(&relayer.pubkey()),
+            &[&relayer],
+            svm.latest_blockhash(),
+        );
+        assert!(svm.send_transaction(tx).is_err());
+
+        // Fully used bucket closes and its lamports go to the recipient
+        send(
+            &mut svm,
+            mark_range(BITS_PER_BUCKET - 1, BITS_PER_BUCKET - 1),
+        )
+        .unwrap();
+        let rent = svm.get_account(&pda).unwrap().lamports;
+        send(&mut svm, close).unwrap();
+        assert_eq!(svm.get_account(&recipient).unwrap().lamports, rent);
+        assert!(svm
+            .get_account(&pda)
+            .is_none_or(|account| account.lamports == 0));
+
+        // A reopened bucket starts empty
+        mark_and_verify(&mut svm, &authority, &authority, namespace, 7).unwrap();
+        let account = svm.get_account(&pda).unwrap();
+        let decoded = DecodedBitmap::try_decode(&account.data).unwrap();
+        assert_eq!(decoded.count_used(), 1);
+    }
+
+    #[test]
+    fn rotated_out_authority_cannot_close_bitmap() {
+        use solana_noreplay::client::{CloseBitmap, InitConfig, MarkUsedRange, SetAuthority};
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::instruction::InstructionError;
+        use solana_sdk::transaction::TransactionError;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let first = Keypair::new();
+        let second = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let namespace = b"close-rotated";
+
+        let send = |svm: &mut LiteSVM, ix: SdkInstruction, signers: &[&Keypair]| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&payer.pubkey()),
+                signers,
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+
+        // Fill the bucket while the authority still signs for itself
+        let fill = MarkUsedRange {
+            payer: &payer.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            start: 0,
+            end: BITS_PER_BUCKET - 1,
+        }
+        .instruction();
+        assert_eq!(send(&mut svm, fill, &[&payer, &authority]), Ok(()));
+
+        let init = InitConfig {
+            payer: &payer.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            current_authority: &first.pubkey(),
+        }
+        .instruction();
+        assert_eq!(send(&mut svm, init, &[&payer, &authority]), Ok(()));
+        let rotate = SetAuthority {
+            current_authority: &first.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            new_authority: &second.pubkey(),
+        }
+        .instruction();
+        assert_eq!(send(&mut svm, rotate, &[&payer, &first]), Ok(()));
+
+        let close = CloseBitmap {
+            authority: &authority.pubkey(),
+            recipient: &recipient,
+            namespace,
+            sequence: 0,
+        };
+        for signer in [&authority, &first] {
+            assert_eq!(
+                send(
+                    &mut svm,
+                    close.instruction_with_config(&signer.pubkey()),
+                    &[&payer, signer]
+                ),
+                Err(TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(NoReplayError::AuthorityMismatch as u32)
+                ))
+            );
+        }
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, 0);
+        assert!(svm
+            .get_account(&pda)
+            .is_some_and(|account| account.lamports > 0));
+
+        assert_eq!(
+            send(
+                &mut svm,
+                close.instruction_with_config(&second.pubkey()),
+                &[&payer, &second]
+            ),
+            Ok(())
+        );
+        assert!(svm.get_account(&recipient).is_some());
+    }
+
+    #[test]
+    fn mark_used_base_instruction_stays_minimal() {
+        use solana_sdk::message::Message;
+
+        let authority = Pubkey::new_unique();
+        for namespace in [&b""[..], b"test", &[0xAB; MAX_NAMESPACE_LEN]] {
+            let ix = MarkUsed {
+                payer: &authority,
+                authority: &authority,
+                namespace,
+                sequence: u64::MAX,
+            }
+            .instruction();
+            assert_eq!(ix.data.len(), 1 + 2 + namespace.len() + 8);
+        }
+
+        // Serialized size of the smallest standalone MarkUsed transaction
+        let ix = MarkUsed {
+            payer: &authority,
+            authority: &authority,
+            namespace: b"test",
+            sequence: 1,
+        }
+        .instruction();
+        let tx_size = |ix: SdkInstruction| {
+            let message = Message::new(&[ix], Some(&authority));
+            1 + message.header.num_required_signatures as usize * 64 + message.serialize().len()
+        };
+        let existing = MarkUsed {
+            payer: &authority,
+            authority: &authority,
+            namespace: b"test",
+            sequence: 1,
+        }
+        .instruction_for_existing_bucket();
+        assert_eq!(tx_size(ix), 252);
+        assert_eq!(tx_size(existing), 252 - 33);
+    }
+
+    #[test]
+    fn validate_prefund_plan_classifies_buckets() {
+        use solana_noreplay::client::{derive_bucket_pda, validate_prefund_plan, SquatStatus};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let payer = Keypair::new();
+        let authority = Pubkey::new_unique();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let namespace = b"plan";
+        let pda = |bucket| derive_bucket_pda(&authority, namespace, bucket).0;
+
+        // Buckets 0 and 2 created, 3 only pre-funded, 4 foreign-owned, 1 and 5 absent
+        for bucket in [0, 2] {
+            let ix = CreateBitmap {
+                payer: &payer.pubkey(),
+                authority: &authority,
+                namespace,
+                sequence: bucket * BITS_PER_BUCKET,
+            }
+            .instruction();
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&payer.pubkey()),
+                &[&payer],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).unwrap();
+        }
+        svm.airdrop(&pda(3), 1_000_000).unwrap();
+        svm.set_account(
+            pda(4),
+            Account {
+                lamports: 1_000_000,
+                data: vec![0; BITMAP_ACCOUNT_SIZE],
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+
+        let mut fetches = 0;
+        let plan = validate_prefund_plan(&authority, namespace, 0..6, |addresses| {
+            fetches += 1;
+            Ok::<_, ()>(addresses.iter().map(|a| svm.get_account(a)).collect())
+        })
+        .unwrap();
+
+        assert_eq!(fetches, 1);
+        assert_eq!(plan.existing, vec![pda(0), pda(2)]);
+        assert_eq!(plan.to_create, vec![pda(1), pda(3), pda(5)]);
+        assert_eq!(plan.anomalies, vec![(pda(4), SquatStatus::ForeignOwner)]);
+    }
+
+    #[test]
+    fn replay_surfaces_stable_custom_error_code() {
+        use solana_noreplay::client::{classify_send_error, ErrorClass};
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::instruction::InstructionError;
+        use solana_sdk::transaction::TransactionError;
+
+        // Codes are part of the public interface; never renumber them
+        assert_eq!(NoReplayError::MalformedBitmapAccount as u32, 0);
+        assert_eq!(NoReplayError::BucketIndexTooLarge as u32, 1);
+        assert_eq!(NoReplayError::BucketNotFullyUsed as u32, 2);
+        assert_eq!(NoReplayError::SequenceAlreadyUsed as u32, 3);
+        assert_eq!(NoReplayError::NamespaceTooLong as u32, 4);
+        assert_eq!(NoReplayError::InvalidBucket as u32, 5);
+        assert_eq!(NoReplayError::UnsupportedFormatVersion as u32, 6);
+        assert_eq!(NoReplayError::AccountSizeMismatch as u32, 7);
+        assert_eq!(NoReplayError::AuthorityMismatch as u32, 8);
+        assert_eq!(NoReplayError::SequenceTooOld as u32, 9);
+        assert_eq!(NoReplayError::BitmapPdaMismatch as u32, 10);
+        assert_eq!(NoReplayError::ReservedNamespace as u32, 11);
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        mark_and_verify(&mut svm, &authority, &authority, b"codes", 3).unwrap();
+
+        svm.expire_blockhash();
+        let ix = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"codes",
+            sequence: 3,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        let err = svm.send_transaction(tx).unwrap_err().err;
+        assert_eq!(
+            err,
+            TransactionError::InstructionError(0, InstructionError::Custom(3))
+        );
+        assert_eq!(classify_send_error(&err), ErrorClass::PermanentReplay);
+    }
+
+    #[test]
+    fn mark_used_returns_status_only_when_flagged() {
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let send = |svm: &mut LiteSVM, ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let authority_key = authority.pubkey();
+        let builder = |sequence| MarkUsed {
+            payer: &authority_key,
+            authority: &authority_key,
+            namespace: b"status",
+            sequence,
+        };
+
+        // Minimal layout writes no return data
+        let meta = send(&mut svm, builder(1).instruction()).unwrap();
+        assert!(meta.return_data.data.is_empty());
+
+        // Flagged layout reports newly marked
+        let ix = builder(2).instruction_with_status();
+        assert_eq!(ix.data.len(), 11 + b"status".len() + 1);
+        let meta = send(&mut svm, ix).unwrap();
+        assert_eq!(meta.return_data.program_id, PROGRAM_ID);
+        assert_eq!(meta.return_data.data, vec![1]);
+
+        // Replay still fails with the flag set
+        let err = send(&mut svm, builder(2).instruction_with_status()).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(
+                    solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                )
+            )
+        );
+
+        // Unknown flag values are reserved
+        let mut ix = builder(3).instruction();
+        ix.data.push(2);
+        let err = send(&mut svm, ix).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+        );
+        assert!(!svm
+            .get_account(&builder(3).pda().0)
+            .is_some_and(|account| DecodedBitmap::try_decode(&account.data).unwrap().is_used(3)));
+    }
+
+    #[test]
+    fn domains_partition_the_pda_space() {
+        use solana_address::Address;
+        use solana_noreplay::client::{derive_bitmap_pda_domain, DOMAIN_SEED_TAG};
+
+        let authority = Pubkey::new_unique();
+        let namespace = b"shared";
+        let sequence = 4242;
+
+        let (plain, _) = derive_bitmap_pda(&authority, namespace, sequence);
+        let (domain_0, _) = derive_bitmap_pda_domain(&authority, 0, namespace, sequence);
+        let (domain_1, _) = derive_bitmap_pda_domain(&authority, 1, namespace, sequence);
+        assert_ne!(domain_0, domain_1);
+        assert_ne!(domain_0, plain);
+        assert_ne!(domain_1, plain);
+
+        // Same bucket within a domain
+        assert_eq!(
+            derive_bitmap_pda_domain(&authority, 1, namespace, sequence + 1).0,
+            domain_1
+        );
+
+        // An undomained namespace spelling out the domain prefix would land
+        // on the domained bucket, so it is reserved
+        let mut spoof = DOMAIN_SEED_TAG.to_vec();
+        spoof.extend_from_slice(DOMAIN_SEED_TAG);
+        spoof.push(1);
+        spoof.extend_from_slice(namespace);
+        assert_eq!(derive_bitmap_pda(&authority, &spoof, sequence).0, domain_1);
+        assert_eq!(
+            solana_noreplay::client::try_derive_bitmap_pda(&authority, &spoof, sequence),
+            Err(solana_noreplay::client::DerivePdaError::ReservedNamespace)
+        );
+
+        // Client and program derivations agree
+        let program_id = Address::new_from_array(PROGRAM_ID.to_bytes());
+        let (onchain, bump) = solana_noreplay::derive_bitmap_pda_domain(
+            &Address::new_from_array(authority.to_bytes()),
+            1,
+            namespace,
+            sequence,
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            (Pubkey::new_from_array(onchain.to_bytes()), bump),
+            derive_bitmap_pda_domain(&authority, 1, namespace, sequence)
+        );
+
+        // Undomained seeds keep their original 4-seed address
+        let (onchain, _) = solana_noreplay::derive_bitmap_pda(
+            &Address::new_from_array(authority.to_bytes()),
+            namespace,
+            sequence,
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(Pubkey::new_from_array(onchain.to_bytes()), plain);
+    }
+
+    #[test]
+    fn mark_used_domain_is_independent_of_plain_mark_used() {
+        use solana_noreplay::client::MarkUsedDomain;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"shared";
+        let sequence = 9;
+
+        let send = |svm: &mut LiteSVM, ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let authority_key = authority.pubkey();
+        let domain = |domain| MarkUsedDomain {
+            payer: &authority_key,
+            authority: &authority_key,
+            domain,
+            namespace,
+            sequence,
+        };
+
+        mark_and_verify(&mut svm, &authority, &authority, namespace, sequence).unwrap();
+
+        // The same (namespace, sequence) is fresh in each domain
+        send(&mut svm, domain(1).instruction()).unwrap();
+        send(&mut svm, domain(2).instruction()).unwrap();
+        for d in [1, 2] {
+            let account = svm.get_account(&domain(d).pda().0).unwrap();
+            assert_eq!(account.owner, PROGRAM_ID);
+            assert!(DecodedBitmap::try_decode(&account.data)
+                .unwrap()
+                .is_used(sequence));
+        }
+
+        // Replay within a domain is rejected
+        let err = send(&mut svm, domain(1).instruction()).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(
+                    solana_noreplay::NoReplayError::SequenceAlreadyUsed as u32
+                )
+            )
+        );
+
+        // Passing another domain's bucket fails seed verification
+        let mut ix = domain(3).instruction();
+        ix.accounts[2].pubkey = domain(1).pda().0;
+        let err = send(&mut svm, ix).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(solana_noreplay::NoReplayError::BitmapPdaMismatch as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn group_by_bucket_splits_and_dedups() {
+        use solana_noreplay::client::group_by_bucket;
+
+        let b = BITS_PER_BUCKET;
+        let groups = group_by_bucket(&[b + 5, 3, b + 5, 2 * b + 1, 3, 0, b]);
+        assert_eq!(
+            groups.into_iter().collect::<Vec<_>>(),
+            vec![(0, vec![0, 3]), (1, vec![b, b + 5]), (2, vec![2 * b + 1]),]
+        );
+        assert!(group_by_bucket(&[]).is_empty());
+    }
+
+    #[test]
+    fn batch_instructions_mark_every_sequence_once_per_bucket() {
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"grouped";
+
+        let b = BITS_PER_BUCKET;
+        let sequences = [7, b + 1, 7, 2, b + 1000, 3 * b];
+        let instructions = MarkUsed::batch_instructions(
+            &authority.pubkey(),
+            &authority.pubkey(),
+            namespace,
+            &sequences,
+        );
+        assert_eq!(instructions.len(), 3);
+
+        let tx = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+
+        for sequence in sequences {
+            let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+            let account = svm.get_account(&pda).unwrap();
+            assert!(DecodedBitmap::try_decode(&account.data)
+                .unwrap()
+                .is_used(sequence));
+        }
+        let (pda, _) = derive_bitmap_pda(&authority.pubkey(), namespace, 0);
+        let bucket_0 = DecodedBitmap::try_decode(&svm.get_account(&pda).unwrap().data).unwrap();
+        assert_eq!(bucket_0.count_used(), 2);
+    }
+
+    #[test]
+    fn client_derivation_rejects_oversized_namespace_cleanly() {
+        use solana_noreplay::client::{try_derive_bitmap_pda, DerivePdaError};
+
+        let authority = Pubkey::new_unique();
+        let namespace = [7u8; MAX_NAMESPACE_LEN + 1];
+
+        assert_eq!(
+            try_derive_bitmap_pda(&authority, &namespace, 0),
+            Err(DerivePdaError::NamespaceTooLong)
+        );
+
+        // At the limit it agrees with the infallible version
+        let namespace = &namespace[..MAX_NAMESPACE_LEN];
+        assert_eq!(
+            try_derive_bitmap_pda(&authority, namespace, 0),
+            Ok(derive_bitmap_pda(&authority, namespace, 0))
+        );
+
+        // The infallible version panics with a clear message, not inside
+        // find_program_address
+        let panic = std::panic::catch_unwind(|| {
+            derive_bitmap_pda(&authority, &[7u8; MAX_NAMESPACE_LEN + 1], 0)
+        })
+        .unwrap_err();
+        assert_eq!(
+            panic.downcast_ref::<&str>(),
+            Some(&"namespace exceeds MAX_NAMESPACE_LEN")
+        );
+    }
+
+    #[test]
+    fn bitmap_accounts_carry_format_tag() {
+        use solana_noreplay::client::{
+            BitmapDecoder, DecodeError, BITMAP_FORMAT_VERSION, BITMAP_MEMCMP_OFFSET, BITMAP_TAG,
+            BITMAP_TAG_LEN,
+        };
+        use solana_noreplay::{NoReplayError, TagError};
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"tagged";
+
+        // Creation writes the tag where the memcmp filter looks
+        mark_and_verify(&mut svm, &authority, &authority, namespace, 1).unwrap();
+        let (pda, bump) = derive_bitmap_pda(&authority.pubkey(), namespace, 1);
+        let account = svm.get_account(&pda).unwrap();
+        assert_eq!(
+            &account.data[BITMAP_MEMCMP_OFFSET..BITMAP_MEMCMP_OFFSET + BITMAP_TAG_LEN],
+            &BITMAP_TAG
+        );
+        assert_eq!(BITMAP_TAG[BITMAP_TAG_LEN - 1], BITMAP_FORMAT_VERSION);
+        assert_eq!(account.data[BITMAP_TAG_LEN], bump);
+
+        let mark = |svm: &mut LiteSVM| {
+            svm.expire_blockhash();
+            let ix = MarkUsed {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequence: 2,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|err| err.err)
+        };
+        let custom = |error: NoReplayError| {
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(error as u32),
+            ))
+        };
+
+        // An existing bucket without the magic is rejected, not marked
+        let mut untagged = account.clone();
+        untagged.data[..BITMAP_TAG_LEN].fill(0);
+        svm.set_account(pda, untagged.clone()).unwrap();
+        assert_eq!(
+            mark(&mut svm),
+            custom(NoReplayError::MalformedBitmapAccount)
+        );
+
+        // A future format version is rejected distinctly
+        let mut future = account.clone();
+        future.data[BITMAP_TAG_LEN - 1] = BITMAP_FORMAT_VERSION + 1;
+        svm.set_account(pda, future.clone()).unwrap();
+        assert_eq!(
+            mark(&mut svm),
+            custom(NoReplayError::UnsupportedFormatVersion)
+        );
+
+        // The off-chain decoder agrees
+        let mut decoder = BitmapDecoder::new();
+        assert!(decoder.decode(&pda, &account.data).is_some());
+        assert!(decoder.decode(&pda, &untagged.data).is_none());
+        assert!(decoder.decode(&pda, &future.data).is_none());
+        let errors: Vec<_> = decoder
+            .into_diagnostics()
+            .into_iter()
+            .map(|diagnostic| diagnostic.error)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                DecodeError::BadTag(TagError::NotABitmap),
+                DecodeError::BadTag(TagError::UnsupportedVersion(BITMAP_FORMAT_VERSION + 1)),
+            ]
+        );
+
+        // Restoring the tag makes the bucket usable again
+        svm.set_account(pda, account).unwrap();
+        assert_eq!(mark(&mut svm), Ok(()));
+    }
+
+    #[test]
+    fn mark_above_rejects_replays_and_out_of_order_sequences() {
+        use solana_noreplay::client::{derive_watermark_pda, MarkAbove, WATERMARK_ACCOUNT_SIZE};
+        use solana_noreplay::state::WatermarkAccount;
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"emitter";
+
+        let mut mark = |sequence| {
+            svm.expire_blockhash();
+            let ix = MarkAbove {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        let replay = Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(NoReplayError::SequenceAlreadyUsed as u32),
+        ));
+
+        // Sequence 0 is accepted by a fresh watermark
+        assert_eq!(mark(0), Ok(()));
+        assert_eq!(mark(0), replay);
+        assert_eq!(mark(5), Ok(()));
+        // Exact replay and anything older are rejected
+        assert_eq!(mark(5), replay);
+        assert_eq!(mark(3), replay);
+        assert_eq!(mark(6), Ok(()));
+        // Gaps are allowed, but skipped sequences can never be marked later
+        assert_eq!(mark(100), Ok(()));
+        assert_eq!(mark(50), replay);
+        assert_eq!(mark(u64::MAX), Ok(()));
+        assert_eq!(mark(u64::MAX), replay);
+
+        let (pda, bump) = derive_watermark_pda(&authority.pubkey(), namespace);
+        let mut account = svm.get_account(&pda).unwrap();
+        assert_eq!(account.owner, PROGRAM_ID);
+        assert_eq!(account.data.len(), WATERMARK_ACCOUNT_SIZE);
+        assert_eq!(
+            account.lamports,
+            Rent::default().minimum_balance(WATERMARK_ACCOUNT_SIZE)
+        );
+        assert!(account.lamports < rent_for_bitmap());
+        let watermark = WatermarkAccount::from_slice(&mut account.data).unwrap();
+        assert_eq!(*watermark.bump, bump);
+        assert_eq!(watermark.watermark(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn watermark_pda_never_aliases_a_bitmap_bucket() {
+        use solana_address::Address;
+        use solana_noreplay::client::{derive_watermark_pda, MarkAbove};
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let authority = Keypair::new();
+        let namespace = b"emitter";
+
+        // Without the tag seeds, a watermark namespace ending in eight bytes
+        // would hash like a bitmap namespace plus a bucket index
+        let mut spoof = namespace.to_vec();
+        spoof.extend_from_slice(&7u64.to_le_bytes());
+        let (watermark, _) = derive_watermark_pda(&authority.pubkey(), &spoof);
+        assert_ne!(
+            watermark,
+            derive_bitmap_pda(&authority.pubkey(), namespace, 7 * BITS_PER_BUCKET).0
+        );
+
+        // Client and program derivations agree
+        let program_id = Address::new_from_array(PROGRAM_ID.to_bytes());
+        let (onchain, bump) = solana_noreplay::derive_watermark_pda(
+            &Address::new_from_array(authority.pubkey().to_bytes()),
+            namespace,
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            (Pubkey::new_from_array(onchain.to_bytes()), bump),
+            derive_watermark_pda(&authority.pubkey(), namespace)
+        );
+
+        // A bitmap bucket passed as the watermark is rejected
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        mark_and_verify(&mut svm, &authority, &authority, namespace, 1).unwrap();
+
+        let mut ix = MarkAbove {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence: 2,
+        }
+        .instruction();
+        ix.accounts[2].pubkey = derive_bitmap_pda(&authority.pubkey(), namespace, 1).0;
+        svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        assert_eq!(
+            svm.send_transaction(tx).unwrap_err().err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::MalformedBitmapAccount as u32)
+            )
+        );
+    }
+
+    #[test]
+    fn u128_bucket_seed_round_trips() {
+        use solana_address::Address;
+        use solana_noreplay::client::{derive_bitmap_pda_u128, U128_SEED_TAG};
+        use solana_noreplay::pda::BitmapPdaSeeds;
+
+        let authority = Pubkey::new_unique();
+        let namespace = b"wide";
+        let sequence = u64::MAX as u128 + 5;
+
+        // The two bucket seeds hash as one 16-byte LE bucket index, after
+        // the u128 tag
+        let seeds = BitmapPdaSeeds::new_u128(namespace, sequence);
+        let bucket = sequence / BITS_PER_BUCKET as u128;
+        assert_eq!(seeds.bucket_index_u128(), bucket);
+        let authority_bytes = authority.to_bytes();
+        let concatenated: Vec<u8> = seeds.as_seeds(&authority_bytes).concat();
+        let mut expected = authority_bytes.to_vec();
+        expected.extend_from_slice(&U128_SEED_TAG.repeat(3));
+        expected.extend_from_slice(namespace);
+        expected.extend_from_slice(&bucket.to_le_bytes());
+        assert_eq!(concatenated, expected);
+
+        // Client and program derivations agree
+        let program_id = Address::new_from_array(PROGRAM_ID.to_bytes());
+        let (onchain, bump) = solana_noreplay::derive_bitmap_pda_u128(
+            &Address::new_from_array(authority_bytes),
+            namespace,
+            sequence,
+            &program_id,
+        )
+        .unwrap();
+        let wide = derive_bitmap_pda_u128(&authority, namespace, sequence);
+        assert_eq!((Pubkey::new_from_array(onchain.to_bytes()), bump), wide);
+
+        // Above u64::MAX doesn't wrap onto the truncated sequence's bucket,
+        // in either seed width
+        let truncated = sequence as u64;
+        assert_ne!(
+            wide.0,
+            derive_bitmap_pda(&authority, namespace, truncated).0
+        );
+        assert_ne!(
+            wide.0,
+            derive_bitmap_pda_u128(&authority, namespace, truncated as u128).0
+        );
+
+        // Wide buckets are separate from u64 ones even for small sequences
+        assert_ne!(
+            derive_bitmap_pda_u128(&authority, namespace, 7).0,
+            derive_bitmap_pda(&authority, namespace, 7).0
+        );
+    }
+
+    #[test]
+    fn u128_bucket_never_matches_a_u64_bucket_of_an_extended_namespace() {
+        use solana_noreplay::client::{derive_bitmap_pda_u128, derive_bucket_pda};
+
+        let authority = Pubkey::new_unique();
+        let namespace = b"wide";
+
+        for sequence in [7u128, u64::MAX as u128 + 5, u128::MAX] {
+            let bucket = sequence / BITS_PER_BUCKET as u128;
+            let (low, high) = (bucket as u64, (bucket >> 64) as u64);
+            let wide = derive_bitmap_pda_u128(&authority, namespace, sequence).0;
+
+            // Untagged, the wide bucket of `N` hashed like the u64 bucket
+            // `high` of `N || low`
+            let mut extended = namespace.to_vec();
+            extended.extend_from_slice(&low.to_le_bytes());
+            assert_ne!(
+                wide,
+                derive_bucket_pda(&authority, &extended, high).0,
+                "{sequence}"
+            );
+
+            // Nor any other bucket of `N || x`, for x of any length
+            for len in 0..=16 {
+                let extended = [namespace.as_slice(), &bucket.to_le_bytes()[..len]].concat();
+                for u64_bucket in [0, low, high, u64::MAX] {
+                    assert_ne!(
+                        wide,
+                        derive_bucket_pda(&authority, &extended, u64_bucket).0,
+                        "{sequence} {len} {u64_bucket}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mark_used_u128_rejects_replay_above_u64_max() {
+        use solana_noreplay::client::MarkUsedU128;
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"wide";
+        let sequence = u64::MAX as u128 + 5;
+
+        let builder = MarkUsedU128 {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        };
+        let mut send = || {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[builder.instruction()],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+
+        assert_eq!(send(), Ok(()));
+        assert_eq!(
+            send(),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::SequenceAlreadyUsed as u32)
+            ))
+        );
+
+        let account = svm.get_account(&builder.pda().0).unwrap();
+        assert_eq!(account.owner, PROGRAM_ID);
+        let decoded = DecodedBitmap::try_decode(&account.data).unwrap();
+        assert!(decoded.is_used((sequence % BITS_PER_BUCKET as u128) as u64));
+
+        // The truncated u64 sequence is a different bucket, still unused
+        mark_and_verify(&mut svm, &authority, &authority, namespace, sequence as u64).unwrap();
+    }
+
+    #[test]
+    fn compat_pubkey_conversions_round_trip() {
+        use solana_noreplay::compat::{from_sdk, to_sdk};
+
+        let sdk = Pubkey::new_unique();
+        let converted = from_sdk(&sdk);
+        assert_eq!(converted.to_bytes(), sdk.to_bytes());
+        assert_eq!(to_sdk(&converted), sdk);
+
+        let bytes: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let other = solana_pubkey::Pubkey::new_from_array(bytes);
+        assert_eq!(to_sdk(&other).to_bytes(), bytes);
+        assert_eq!(from_sdk(&to_sdk(&other)), other);
+
+        // String forms agree, so base58 addresses can be compared directly
+        assert_eq!(to_sdk(&other).to_string(), other.to_string());
+        assert_eq!(from_sdk(&PROGRAM_ID).to_string(), PROGRAM_ID.to_string());
+    }
+
+    #[test]
+    fn builders_target_an_overridden_program_id() {
+        use solana_noreplay::client::derive_bitmap_pda_for_program;
+
+        let authority = Keypair::new();
+        let namespace = b"clusters";
+        let sequence = 77;
+        let devnet = Pubkey::new_unique();
+        let localnet = Pubkey::new_unique();
+
+        // Each deployment has its own bucket; the default is PROGRAM_ID
+        let (devnet_pda, _) =
+            derive_bitmap_pda_for_program(&devnet, &authority.pubkey(), namespace, sequence);
+        let (localnet_pda, _) =
+            derive_bitmap_pda_for_program(&localnet, &authority.pubkey(), namespace, sequence);
+        assert_ne!(devnet_pda, localnet_pda);
+        assert_eq!(
+            derive_bitmap_pda_for_program(&PROGRAM_ID, &authority.pubkey(), namespace, sequence),
+            derive_bitmap_pda(&authority.pubkey(), namespace, sequence)
+        );
+
+        let mark = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        };
+        let create = CreateBitmap {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        };
+        assert_eq!(
+            mark.instruction(),
+            mark.instruction_for_program(&PROGRAM_ID)
+        );
+        let ix = mark.instruction_for_program(&devnet);
+        assert_eq!(ix.program_id, devnet);
+        assert_eq!(ix.accounts[2].pubkey, devnet_pda);
+        assert_eq!(mark.pda_for_program(&devnet).0, devnet_pda);
+        let ix = create.instruction_for_program(&localnet);
+        assert_eq!(ix.program_id, localnet);
+        assert_eq!(ix.accounts[2].pubkey, localnet_pda);
+        assert_eq!(create.pda_for_program(&localnet).0, localnet_pda);
+
+        // A second deployment of the same binary accepts its own buckets
+        let mut svm = LiteSVM::new();
+        svm.add_program(devnet, &load_program());
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[mark.instruction_for_program(&devnet)],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+        let account = svm.get_account(&devnet_pda).unwrap();
+        assert_eq!(account.owner, devnet);
+        assert!(DecodedBitmap::try_decode(&account.data)
+            .unwrap()
+            .is_used(sequence));
+    }
+
+    #[test]
+    fn check_program_id_flags_a_bucket_from_another_deployment() {
+        let authority = Pubkey::new_unique();
+        let namespace = b"clusters";
+        let devnet = Pubkey::new_unique();
+        let mark = MarkUsed {
+            payer: &authority,
+            authority: &authority,
+            namespace,
+            sequence: 77,
+        };
+        let create = CreateBitmap {
+            payer: &authority,
+            authority: &authority,
+            namespace,
+            sequence: 77,
+        };
+
+        // Builders agree with themselves for any deployment
+        assert!(mark.check_program_id(&mark.instruction()));
+        assert!(mark.check_program_id(&mark.instruction_for_program(&devnet)));
+        assert!(create.check_program_id(&create.instruction_for_program(&devnet)));
+
+        // The compile-time bucket in a devnet instruction warns
+        let mut mixed = mark.instruction_for_program(&devnet);
+        mixed.accounts[2].pubkey = mark.pda().0;
+        assert!(!mark.check_program_id(&mixed));
+        let mut mixed = create.instruction();
+        mixed.accounts[2].pubkey = create.pda_for_program(&devnet).0;
+        assert!(!create.check_program_id(&mixed));
+    }
+
+    #[test]
+    fn spoofed_system_program_is_rejected_before_creation() {
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+        let spoof = Pubkey::new_unique();
+
+        let mut send = |mut ix: SdkInstruction| {
+            ix.accounts[3].pubkey = spoof;
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        let incorrect = Err(TransactionError::InstructionError(
+            0,
+            InstructionError::IncorrectProgramId,
+        ));
+
+        let create = CreateBitmap {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence: 1,
+        };
+        assert_eq!(send(create.instruction()), incorrect);
+        let mark = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence: 1,
+        };
+        assert_eq!(send(mark.instruction()), incorrect);
+        assert!(svm.get_account(&mark.pda().0).is_none());
+
+        // Marking into an existing bucket makes no CPI, so the account isn't
+        // consulted
+        mark_and_verify(&mut svm, &authority, &authority, namespace, 1).unwrap();
+        let mut ix = MarkUsed {
+            sequence: 2,
+            ..mark
+        }
+        .instruction();
+        ix.accounts[3].pubkey = spoof;
+        svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+    }
+
+    #[test]
+    fn oversized_existing_bucket_is_rejected() {
+        use solana_noreplay::client::{BucketStatus, VerifyBucket};
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"test";
+        let sequence = 3;
+        let (pda, bump) = derive_bitmap_pda(&authority.pubkey(), namespace, sequence);
+
+        // Well-formed tag, bump and bitmap, followed by stale bytes from a
+        // larger layout
+        let mut account = bitmap_account_fixture(LAMPORTS_PER_SOL, bump, &[], &PROGRAM_ID);
+        account.data.extend_from_slice(&[0xAA; 8]);
+        svm.set_account(pda, account).unwrap();
+
+        let mut send = |ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        let mismatch = Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(NoReplayError::AccountSizeMismatch as u32),
+        ));
+
+        let mark = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        };
+        assert_eq!(send(mark.instruction()), mismatch);
+        let create = CreateBitmap {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence,
+        };
+        assert_eq!(send(create.instruction()), mismatch);
+
+        // The health check agrees instead of calling it healthy
+        svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[VerifyBucket {
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction()],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        let meta = svm.send_transaction(tx).unwrap();
+        assert_eq!(meta.return_data.data, [BucketStatus::WrongSize as u8]);
+    }
+
+    #[test]
+    fn iter_used_yields_exactly_the_marked_sequences() {
+        use solana_noreplay::state::BitmapAccount;
+
+        let bucket = 3;
+        let base = bucket * BITS_PER_BUCKET;
+        let marked = [
+            base,
+            base + 1,
+            base + 7,
+            base + 8,
+            base + 500,
+            base + BITS_PER_BUCKET - 1,
+        ];
+
+        let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::init(&mut data, 255).unwrap();
+        assert_eq!(bitmap.iter_used(bucket).next(), None);
+        // Marked out of order, yielded ascending
+        for sequence in marked.iter().rev() {
+            bitmap.mark_used(*sequence);
+        }
+        assert_eq!(bitmap.iter_used(bucket).collect::<Vec<_>>(), marked);
+        assert_eq!(
+            bitmap.iter_used(bucket).count(),
+            bitmap.count_used() as usize
+        );
+
+        let view = BitmapAccount::from_slice_ref(&data).unwrap();
+        assert_eq!(view.iter_used(bucket).collect::<Vec<_>>(), marked);
+
+        // A full bucket yields every sequence in it
+        let mut full = vec![0u8; BITMAP_ACCOUNT_SIZE];
+        let mut bitmap = BitmapAccount::init(&mut full, 255).unwrap();
+        bitmap.mark_range(0, BITS_PER_BUCKET).unwrap();
+        assert!(bitmap.iter_used(0).eq(0..BITS_PER_BUCKET));
+    }
+
+    #[test]
+    fn namespace_config_rotates_marking_authority() {
+        use solana_noreplay::client::{derive_config_pda, InitConfig, SetAuthority};
+        use solana_noreplay::state::ConfigAccount;
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::instruction::{Instruction, InstructionError};
+        use solana_sdk::transaction::TransactionError;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        // A separate fee payer, so the authority only signs where asked to
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let first = Keypair::new();
+        let second = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let namespace = b"rotating";
+
+        let mut send = |ix: Instruction, signers: &[&Keypair]| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&payer.pubkey()),
+                signers,
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        let mark_with = |sequence, current_authority: &Pubkey| {
+            MarkUsed {
+                payer: &payer.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction_with_config(current_authority)
+        };
+        let mismatch = Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(NoReplayError::AuthorityMismatch as u32),
+        ));
+
+        // No config yet: the new key can't mark
+        assert_eq!(
+            send(mark_with(1, &first.pubkey()), &[&payer, &first]),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::MissingRequiredSignature
+            ))
+        );
+
+        let init = InitConfig {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            current_authority: &first.pubkey(),
+        }
+        .instruction();
+        assert_eq!(send(init.clone(), &[&payer, &authority]), Ok(()));
+        assert_eq!(
+            send(init, &[&payer, &authority]),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::AccountAlreadyInitialized
+            ))
+        );
+
+        // The recorded key marks in the authority's buckets without its signature
+        assert_eq!(
+            send(mark_with(1, &first.pubkey()), &[&payer, &first]),
+            Ok(())
+        );
+        assert_eq!(
+            send(mark_with(2, &second.pubkey()), &[&payer, &second]),
+            mismatch
+        );
+
+        // Only the current authority can rotate
+        let rotate = |signer: &Keypair| {
+            SetAuthority {
+                current_authority: &signer.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                new_authority: &second.pubkey(),
+            }
+            .instruction()
+        };
+        assert_eq!(send(rotate(&second), &[&payer, &second]), mismatch);
+        assert_eq!(send(rotate(&first), &[&payer, &first]), Ok(()));
+
+        assert_eq!(
+            send(mark_with(2, &first.pubkey()), &[&payer, &first]),
+            mismatch
+        );
+        assert_eq!(
+            send(mark_with(2, &second.pubkey()), &[&payer, &second]),
+            Ok(())
+        );
+        // Same buckets as plain MarkUsed, so replays are still caught
+        assert_eq!(
+            send(mark_with(1, &second.pubkey()), &[&payer, &second]),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::SequenceAlreadyUsed as u32)
+            ))
+        );
+
+        // A config passed at any other address is rejected
+        let mut substituted = mark_with(3, &second.pubkey());
+        substituted.accounts[4].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            send(substituted, &[&payer, &second]),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidSeeds
+            ))
+        );
+
+        // Without a signing authority or a config, MarkUsed still fails
+        let mut unsigned = mark_with(3, &second.pubkey());
+        unsigned.accounts.truncate(4);
+        assert_eq!(
+            send(unsigned, &[&payer]),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::MissingRequiredSignature
+            ))
+        );
+
+        let (pda, bump) = derive_config_pda(&authority.pubkey(), namespace);
+        let mut account = svm.get_account(&pda).unwrap();
+        assert_eq!(account.owner, PROGRAM_ID);
+        let config = ConfigAccount::from_slice(&mut account.data).unwrap();
+        assert_eq!(*config.bump, bump);
+        assert_eq!(config.current_authority, &second.pubkey().to_bytes());
+    }
+
+    #[test]
+    fn set_authority_revokes_previous_keys_on_every_mark_path() {
+        use solana_noreplay::client::{
+            delegation_message, derive_config_pda, with_config_authority, InitConfig, MarkAbove,
+            MarkUsedBatch, MarkUsedDelegated, MarkUsedDomain, MarkUsedIdempotent, MarkUsedRange,
+            MarkUsedU128, MarkWindowed, MultiBucketMark, SetAuthority,
+        };
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::instruction::{Instruction, InstructionError};
+        use solana_sdk::transaction::TransactionError;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let first = Keypair::new();
+        let second = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let namespace = b"revoked";
+        let payer_key = payer.pubkey();
+        let authority_key = authority.pubkey();
+
+        let mut send = |ixs: &[Instruction], signers: &[&Keypair]| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                ixs,
+                Some(&payer.pubkey()),
+                signers,
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        let missing_signature = |index| {
+            Err(TransactionError::InstructionError(
+                index,
+                InstructionError::MissingRequiredSignature,
+            ))
+        };
+        let mismatch = Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(NoReplayError::AuthorityMismatch as u32),
+        ));
+
+        let init = InitConfig {
+            payer: &payer_key,
+            authority: &authority_key,
+            namespace,
+            current_authority: &first.pubkey(),
+        }
+        .instruction();
+        assert_eq!(send(&[init], &[&payer, &authority]), Ok(()));
+        let rotate = SetAuthority {
+            current_authority: &first.pubkey(),
+            authority: &authority_key,
+            namespace,
+            new_authority: &second.pubkey(),
+        }
+        .instruction();
+        assert_eq!(send(&[rotate], &[&payer, &first]), Ok(()));
+
+        let marks = [
+            MarkUsed {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequence: 1,
+            }
+            .instruction(),
+            MarkUsed {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequence: 2,
+            }
+            .instruction_with_status(),
+            MarkUsedIdempotent {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequence: 3,
+            }
+            .instruction(),
+            MarkUsedBatch {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequences: &[4, 5],
+            }
+            .instruction(),
+            MarkUsedRange {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                start: 6,
+                end: 7,
+            }
+            .instruction(),
+            MarkUsedDomain {
+                payer: &payer_key,
+                authority: &authority_key,
+                domain: 1,
+                namespace,
+                sequence: 8,
+            }
+            .instruction(),
+            MarkUsedU128 {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequence: 9,
+            }
+            .instruction(),
+            MultiBucketMark {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequences: &[10, BITS_PER_BUCKET + 10],
+            }
+            .instruction(),
+            MarkAbove {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequence: 11,
+            }
+            .instruction(),
+            MarkWindowed {
+                payer: &payer_key,
+                authority: &authority_key,
+                namespace,
+                sequence: 12,
+            }
+            .instruction(),
+        ];
+        let config = derive_config_pda(&authority_key, namespace).0;
+        for ix in marks {
+            // With the config passed, neither the seed authority nor the
+            // replaced config authority can mark; only the current one can
+            for signer in [&authority, &first] {
+                assert_eq!(
+                    send(
+                        &[with_config_authority(ix.clone(), &config, &signer.pubkey())],
+                        &[&payer, signer]
+                    ),
+                    mismatch
+                );
+            }
+            assert_eq!(
+                send(
+                    &[with_config_authority(ix, &config, &second.pubkey())],
+                    &[&payer, &second]
+                ),
+                Ok(())
+            );
+        }
+
+        // Delegated authorizations must come from the current authority too
+        let delegated = MarkUsedDelegated {
+            payer: &payer_key,
+            authority: &authority_key,
+            namespace,
+            sequence: 13,
+        };
+        let message = delegation_message(&authority_key, namespace, 13);
+        for signer in [&authority, &first] {
+            let ixs = delegated
+                .instructions_with_config(&signer.pubkey(), &signer.sign_message(&message));
+            assert_eq!(send(&ixs, &[&payer]), missing_signature(1));
+        }
+        let ixs =
+            delegated.instructions_with_config(&second.pubkey(), &second.sign_message(&message));
+        assert_eq!(send(&ixs, &[&payer]), Ok(()));
+    }
+
+    #[test]
+    fn delegated_authorization_is_bound_to_the_seed_authority() {
+        use solana_noreplay::client::{delegation_message, InitConfig, MarkUsedDelegated};
+        use solana_sdk::instruction::{Instruction, InstructionError};
+        use solana_sdk::transaction::TransactionError;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        // One key is the current authority of two authorities' configs
+        let payer = Keypair::new();
+        let shared = Keypair::new();
+        let authorities = [Keypair::new(), Keypair::new()];
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let namespace = b"shared";
+
+        let mut send = |ixs: &[Instruction], signers: &[&Keypair]| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                ixs,
+                Some(&payer.pubkey()),
+                signers,
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        for authority in &authorities {
+            let init = InitConfig {
+                payer: &payer.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                current_authority: &shared.pubkey(),
+            }
+            .instruction();
+            assert_eq!(send(&[init], &[&payer, authority]), Ok(()));
+        }
+
+        let payer_key = payer.pubkey();
+        let keys = authorities.each_ref().map(Keypair::pubkey);
+        let [first, second] = keys.each_ref().map(|authority| MarkUsedDelegated {
+            payer: &payer_key,
+            authority,
+            namespace,
+            sequence: 7,
+        });
+        let signature = shared.sign_message(&delegation_message(first.authority, namespace, 7));
+
+        // The signature for the first authority doesn't mark for the second
+        let mut ixs = second.instructions_with_config(&shared.pubkey(), &signature);
+        ixs[0] = first.instructions_with_config(&shared.pubkey(), &signature)[0].clone();
+        assert_eq!(
+            send(&ixs, &[&payer]),
+            Err(TransactionError::InstructionError(
+                1,
+                InstructionError::MissingRequiredSignature
+            ))
+        );
+
+        let ixs = first.instructions_with_config(&shared.pubkey(), &signature);
+        assert_eq!(send(&ixs, &[&payer]), Ok(()));
+    }
+
+    #[test]
+    fn bucket_and_bit_helpers_pin_boundary_values() {
+        use solana_noreplay::client::{bit_position, bucket_of};
+        use solana_noreplay::BitmapAccount;
+
+        let cases = [
+            (0, 0, (0, 0)),
+            (7, 0, (0, 7)),
+            (8, 0, (1, 0)),
+            (BITS_PER_BUCKET - 1, 0, (BITMAP_BYTES - 1, 7)),
+            (BITS_PER_BUCKET, 1, (0, 0)),
+            (BITS_PER_BUCKET + 9, 1, (1, 1)),
+            (u64::MAX, u64::MAX / BITS_PER_BUCKET, (BITMAP_BYTES - 1, 7)),
+        ];
+        for (sequence, bucket, position) in cases {
+            assert_eq!(bucket_of(sequence), bucket, "sequence {sequence}");
+            assert_eq!(bit_position(sequence), position, "sequence {sequence}");
+
+            // The account wrapper sets exactly that bit
+            let mut data = vec![0u8; BITMAP_ACCOUNT_SIZE];
+            let mut account = BitmapAccount::init(&mut data, 255).unwrap();
+            account.mark_used(sequence);
+            let (byte, offset) = position;
+            let mut expected = [0u8; BITMAP_BYTES];
+            expected[byte] = 1 << offset;
+            assert_eq!(*account.bitmap, expected, "sequence {sequence}");
+        }
+    }
+
+    #[test]
+    fn mark_windowed_slides_and_rejects_old_sequences() {
+        use solana_noreplay::client::{
+            derive_window_pda, MarkWindowed, WINDOW_ACCOUNT_SIZE, WINDOW_BITS,
+        };
+        use solana_noreplay::state::WindowAccount;
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"guardian-set";
+
+        let mut mark = |sequence| {
+            svm.expire_blockhash();
+            let ix = MarkWindowed {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        let error = |e: NoReplayError| {
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(e as u32),
+            ))
+        };
+        let replay = error(NoReplayError::SequenceAlreadyUsed);
+        let too_old = error(NoReplayError::SequenceTooOld);
+
+        // In-window replay, including out-of-order sequences below the highest
+        assert_eq!(mark(2000), Ok(()));
+        assert_eq!(mark(2000), replay);
+        assert_eq!(mark(1990), Ok(()));
+        assert_eq!(mark(1990), replay);
+        assert_eq!(mark(2001), Ok(()));
+        assert_eq!(mark(2000), replay);
+
+        // The oldest sequence still inside the window is tracked; one below isn't
+        assert_eq!(mark(2001 - (WINDOW_BITS - 1)), Ok(()));
+        assert_eq!(mark(2001 - (WINDOW_BITS - 1)), replay);
+        assert_eq!(mark(2001 - WINDOW_BITS), too_old);
+
+        // Advancing by less than the window keeps the shifted bits
+        assert_eq!(mark(2020), Ok(()));
+        assert_eq!(mark(1990), replay);
+        assert_eq!(mark(2001), replay);
+        assert_eq!(mark(1995), Ok(()));
+
+        // Advancing beyond the window forgets everything before it
+        let far = 2020 + WINDOW_BITS + 5;
+        assert_eq!(mark(far), Ok(()));
+        assert_eq!(mark(2020), too_old);
+        assert_eq!(mark(far - (WINDOW_BITS - 1)), Ok(()));
+        assert_eq!(mark(far), replay);
+
+        let (pda, bump) = derive_window_pda(&authority.pubkey(), namespace);
+        let mut account = svm.get_account(&pda).unwrap();
+        assert_eq!(account.owner, PROGRAM_ID);
+        assert_eq!(account.data.len(), WINDOW_ACCOUNT_SIZE);
+        let window = WindowAccount::from_slice(&mut account.data).unwrap();
+        assert_eq!(*window.bump, bump);
+        assert_eq!(window.highest(), Some(far));
+        assert!(window.is_used(far - (WINDOW_BITS - 1)));
+        assert!(!window.is_used(far - 1));
+    }
+
+    #[test]
+    fn migrate_bucket_keeps_marks_of_a_pre_tag_bucket() {
+        use solana_noreplay::client::{DecodedBitmap, MigrateBucket, LEGACY_BITMAP_ACCOUNT_SIZE};
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let namespace = b"legacy";
+        let base = 2 * BITS_PER_BUCKET;
+        let marked = [base, base + 7, base + 1000];
+        let (pda, bump) = derive_bitmap_pda(&authority.pubkey(), namespace, base);
+
+        // Pre-tag layout: [bump][bitmap], funded for its own size only
+        let tagged = bitmap_account_fixture(0, bump, &marked, &PROGRAM_ID);
+        let mut data = vec![bump];
+        data.extend_from_slice(&tagged.data[BITMAP_ACCOUNT_SIZE - BITMAP_BYTES..]);
+        assert_eq!(data.len(), LEGACY_BITMAP_ACCOUNT_SIZE);
+        let legacy = Account {
+            lamports: Rent::default().minimum_balance(LEGACY_BITMAP_ACCOUNT_SIZE),
+            data,
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        svm.set_account(pda, legacy.clone()).unwrap();
+
+        let mut send = |ix: SdkInstruction| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[&authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err)
+        };
+        let mark = |sequence| {
+            MarkUsed {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequence,
+            }
+            .instruction()
+        };
+        let migrate = MigrateBucket {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace,
+            sequence: base + 500,
+        }
+        .instruction();
+
+        // Unusable until migrated
+        assert_eq!(
+            send(mark(base + 1)),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::AccountSizeMismatch as u32)
+            ))
+        );
+
+        assert_eq!(send(migrate.clone()), Ok(()));
+        assert_eq!(
+            send(migrate),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::AccountAlreadyInitialized
+            ))
+        );
+
+        // Prior marks survive; the rest of the bucket, up to its last bit, is usable
+        for sequence in marked {
+            assert_eq!(
+                send(mark(sequence)),
+                Err(TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(NoReplayError::SequenceAlreadyUsed as u32)
+                ))
+            );
+        }
+        assert_eq!(send(mark(base + 1)), Ok(()));
+        assert_eq!(send(mark(base + BITS_PER_BUCKET - 1)), Ok(()));
+
+        let account = svm.get_account(&pda).unwrap();
+        assert_eq!(account.data.len(), BITMAP_ACCOUNT_SIZE);
+        assert_eq!(account.lamports, rent_for_bitmap());
+        let decoded = DecodedBitmap::try_decode(&account.data).unwrap();
+        assert_eq!(decoded.bump, bump);
+
+        // A bucket with a different bitmap size can't be grown in place
+        let (other_pda, other_bump) =
+            derive_bitmap_pda(&authority.pubkey(), namespace, 5 * BITS_PER_BUCKET);
+        let mut small = legacy;
+        small.data = vec![other_bump; 33];
+        svm.set_account(other_pda, small).unwrap();
+        svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[MigrateBucket {
+                payer: &authority.pubkey(),
+                authority: &authority.pubkey(),
+                namespace,
+                sequence: 5 * BITS_PER_BUCKET,
+            }
+            .instruction()],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        assert_eq!(
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::AccountSizeMismatch as u32)
+            ))
+        );
+    }
+
+    #[test]
+    fn bitmap_account_size_matches_layout() {
+        use solana_noreplay::client::BITMAP_TAG_LEN;
+
+        // [tag][bump][bitmap]; the pre-tag [bump][bitmap] size is only for migration
+        assert_eq!(BITMAP_ACCOUNT_SIZE, BITMAP_TAG_LEN + 1 + BITMAP_BYTES);
+        assert_eq!(
+            solana_noreplay::client::LEGACY_BITMAP_ACCOUNT_SIZE,
+            1 + BITMAP_BYTES
+        );
+        assert_eq!(
+            rent_for_bitmap(),
+            Rent::default().minimum_balance(BITMAP_TAG_LEN + 1 + BITMAP_BYTES)
+        );
+    }
+
+    #[cfg(not(feature = "strict-namespace"))]
+    #[test]
+    fn parser_accepts_empty_namespace_by_default() {
+        use solana_noreplay::{InstructionData, MIN_NAMESPACE_LEN};
+
+        assert_eq!(MIN_NAMESPACE_LEN, 0);
+        let data = build_instruction_data(MARK_USED, b"", 7);
+        let parsed = InstructionData::try_from(&data[1..]).unwrap();
+        assert!(parsed.namespace.is_empty());
+        assert_eq!(parsed.sequence, 7);
+    }
+
+    #[cfg(feature = "strict-namespace")]
+    #[test]
+    fn strict_namespace_rejects_empty_namespace() {
+        use solana_noreplay::{instruction::MarkUsedRangeData, InstructionData, MIN_NAMESPACE_LEN};
+        use solana_sdk::{
+            instruction::InstructionError, program_error::ProgramError,
+            transaction::TransactionError,
+        };
+
+        assert_eq!(MIN_NAMESPACE_LEN, 1);
+        let data = build_instruction_data(MARK_USED, b"", 7);
+        assert_eq!(
+            InstructionData::try_from(&data[1..]).err().map(u64::from),
+            Some(u64::from(ProgramError::InvalidInstructionData))
+        );
+        assert!(
+            InstructionData::try_from(&build_instruction_data(MARK_USED, b"a", 7)[1..]).is_ok()
+        );
+
+        // Every parser shares the check, not just the single-sequence one
+        let mut range = 0u16.to_le_bytes().to_vec();
+        range.extend_from_slice(&0u64.to_le_bytes());
+        range.extend_from_slice(&3u64.to_le_bytes());
+        assert_eq!(
+            MarkUsedRangeData::try_from(&range[..]).err().map(u64::from),
+            Some(u64::from(ProgramError::InvalidInstructionData))
+        );
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        let ix = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"",
+            sequence: 7,
+        }
+        .instruction();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        assert_eq!(
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+    }
+
+    #[test]
+    fn wrong_bitmap_account_fails_with_pda_mismatch() {
+        use solana_noreplay::NoReplayError;
+        use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+
+        // A not-yet-created account at an address the seeds don't derive
+        let wrong = Pubkey::new_unique();
+        let mut ix = MarkUsed {
+            payer: &authority.pubkey(),
+            authority: &authority.pubkey(),
+            namespace: b"test",
+            sequence: 7,
+        }
+        .instruction();
+        ix.accounts[2].pubkey = wrong;
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[&authority],
+            svm.latest_blockhash(),
+        );
+        let err = svm.send_transaction(tx).unwrap_err();
+        assert_eq!(
+            err.err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::BitmapPdaMismatch as u32)
+            )
+        );
+        assert!(svm.get_account(&wrong).is_none());
+
+        #[cfg(feature = "logging")]
+        {
+            use solana_noreplay::processor::PDA_MISMATCH_LOG;
+
+            let expected = format!("Program log: {PDA_MISMATCH_LOG}");
+            assert!(err.meta.logs.contains(&expected), "{:?}", err.meta.logs);
+        }
+    }
+
+    #[test]
+    fn account_helpers_answer_from_fetched_data() {
+        use solana_noreplay::client::{bitmap_exists, is_used_from_account};
+
+        let (_, bump) = derive_bitmap_pda(&Pubkey::new_unique(), b"test", 0);
+        let account = bitmap_account_fixture(rent_for_bitmap(), bump, &[3, 1023], &PROGRAM_ID);
+        assert!(bitmap_exists(Some(&account)));
+        assert_eq!(is_used_from_account(&account.data, 3), Some(true));
+        assert_eq!(is_used_from_account(&account.data, 4), Some(false));
+        // Sequences map into the bucket by offset
+        assert_eq!(
+            is_used_from_account(&account.data, 5 * BITS_PER_BUCKET + 1023),
+            Some(true)
+        );
+
+        assert!(!bitmap_exists(None));
+        let mut foreign = account.clone();
+        foreign.owner = Pubkey::new_unique();
+        assert!(!bitmap_exists(Some(&foreign)));
+
+        let mut truncated = account.clone();
+        truncated.data.truncate(BITMAP_ACCOUNT_SIZE - 1);
+        assert!(!bitmap_exists(Some(&truncated)));
+        assert_eq!(is_used_from_account(&truncated.data, 3), None);
+
+        let mut untagged = account;
+        untagged.data[0] ^= 0xFF;
+        assert_eq!(is_used_from_account(&untagged.data, 3), None);
+    }
+
+    #[test]
+    fn verify_pda_checks_cached_bump_and_address() {
+        use solana_noreplay::client::verify_pda;
+
+        let authority = Pubkey::new_unique();
+        let namespace = [0x5A; 40];
+        let sequence = 3 * BITS_PER_BUCKET + 17;
+        let (pda, bump) = derive_bitmap_pda(&authority, &namespace, sequence);
+
+        assert!(verify_pda(&authority, &namespace, sequence, bump, &pda));
+        // Any sequence in the same bucket shares the PDA
+        assert!(verify_pda(
+            &authority,
+            &namespace,
+            3 * BITS_PER_BUCKET,
+            bump,
+            &pda
+        ));
+
+        // Wrong bump
+        for wrong_bump in [bump.wrapping_sub(1), bump.wrapping_add(1)] {
+            assert!(!verify_pda(
+                &authority, &namespace, sequence, wrong_bump, &pda
+            ));
+        }
+
+        // Wrong expected address
+        let (other, other_bump) = derive_bitmap_pda(&authority, &namespace, 4 * BITS_PER_BUCKET);
+        assert!(!verify_pda(&authority, &namespace, sequence, bump, &other));
+        assert!(!verify_pda(
+            &authority, &namespace, sequence, other_bump, &other
+        ));
+        assert!(!verify_pda(
+            &authority,
+            &namespace,
+            sequence,
+            bump,
+            &Pubkey::new_unique()
+        ));
+
+        assert!(!verify_pda(
+            &authority,
+            &[0; MAX_NAMESPACE_LEN + 1],
+            sequence,
+            bump,
+            &pda
+        ));
+    }
+
+    #[test]
+    fn three_chunk_namespaces_mark_and_derive_consistently() {
+        use solana_address::Address;
+        use solana_noreplay::client::explain_derivation;
+
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 10 * LAMPORTS_PER_SOL)
+            .unwrap();
+        let program_id = Address::new_from_array(PROGRAM_ID.to_bytes());
+        let sequence = 2 * BITS_PER_BUCKET + 9;
+
+        for len in [65, MAX_NAMESPACE_LEN] {
+            let namespace: Vec<u8> = (0..len as u8).collect();
+
+            // Client, program and explain agree on the third chunk
+            let (pda, bump) = derive_bitmap_pda(&authority.pubkey(), &namespace, sequence);
+            let (onchain, onchain_bump) = solana_noreplay::derive_bitmap_pda(
+                &Address::new_from_array(authority.pubkey().to_bytes()),
+                &namespace,
+                sequence,
+                &program_id,
+            )
+            .unwrap();
+            assert_eq!((onchain.to_bytes(), onchain_bump), (pda.to_bytes(), bump));
+            let explained = explain_derivation(&authority.pubkey(), &namespace, sequence);
+            assert_eq!(explained.ns_chunk_1, namespace[32..64]);
+            assert_eq!(explained.ns_chunk_2, namespace[64..]);
+
+            // The third chunk keeps it apart from the bucket of its 64-byte prefix
+            assert_ne!(
+                pda,
+                derive_bitmap_pda(&authority.pubkey(), &namespace[..64], sequence).0
+            );
+
+            mark_and_verify(&mut svm, &authority, &authority, &namespace, sequence).unwrap();
+            assert!(
+                mark_and_verify(&mut svm, &authority, &authority, &namespace, sequence).is_err()
+            );
+        }
+
+        // One byte over the limit is rejected before derivation
+        assert_eq!(
+            solana_noreplay::derive_bitmap_pda(
+                &Address::new_from_array(authority.pubkey().to_bytes()),
+                &[0; MAX_NAMESPACE_LEN + 1],
+                sequence,
+                &program_id,
+            ),
+            Err(solana_noreplay::DerivePdaError::NamespaceTooLong)
+        );
+    }
+
+    #[test]
+    fn namespaces_spelling_out_a_seed_tag_are_reserved() {
+        use solana_noreplay::client::{
+            derive_watermark_pda, is_reserved_namespace, CreateBitmap, RESERVED_SEED_TAGS,
+            WATERMARK_SEED_TAG,
+        };
+        use solana_noreplay::{InstructionData, NoReplayError};
+        use solana_sdk::{
+            instruction::InstructionError, program_error::ProgramError,
+            transaction::TransactionError,
+        };
+
+        let victim = Pubkey::new_unique();
+        let bucket = 7u64;
+        let victim_namespace = bucket.to_le_bytes();
+
+        // A plain bucket of [tag x3] whose index bytes spell an 8-byte
+        // namespace is that namespace's watermark account
+        let squat: Vec<u8> = WATERMARK_SEED_TAG.repeat(3);
+        assert_eq!(
+            derive_bitmap_pda(&victim, &squat, bucket * BITS_PER_BUCKET).0,
+            derive_watermark_pda(&victim, &victim_namespace).0
+        );
+
+        for tag in RESERVED_SEED_TAGS {
+            let mut namespace = tag.to_vec();
+            namespace.resize(65, 0);
+            assert!(is_reserved_namespace(&namespace));
+            // Up to two chunks, length keeps the spaces apart
+            assert!(!is_reserved_namespace(&namespace[..64]));
+
+            let data = build_instruction_data(MARK_USED, &namespace, 0);
+            assert_eq!(
+                InstructionData::try_from(&data[1..]).err().map(u64::from),
+                Some(u64::from(ProgramError::Custom(
+                    NoReplayError::ReservedNamespace as u32
+                )))
+            );
+        }
+
+        // The permissionless squat fails on-chain
+        let mut svm = LiteSVM::new();
+        svm.add_program(PROGRAM_ID, &load_program());
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[CreateBitmap {
+                payer: &payer.pubkey(),
+                authority: &victim,
+                namespace: &squat,
+                sequence: bucket * BITS_PER_BUCKET,
+            }
+            .instruction()],
+            Some(&payer.pubkey()),
+            &[&payer],
+            svm.latest_blockhash(),
+        );
+        assert_eq!(
+            svm.send_transaction(tx).map(|_| ()).map_err(|e| e.err),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(NoReplayError::ReservedNamespace as u32)
+            ))
         );
     }
 }